@@ -0,0 +1,196 @@
+//! Derive macros for [llq](https://docs.rs/llq).
+//!
+//! This crate is re-exported by llq with the `derive` feature enabled, and
+//! should be used through that re-export.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Ident;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Type};
+
+/// Generates a bundle of queues with one queue per variant of a message enum.
+///
+/// For an enum `Message`, this generates:
+///
+/// - `MessageSender`, holding a producer for each variant, with a
+///   `send_<variant>(node)` method per variant which pushes a node without
+///   allocating, and a `send(message)` method which allocates a node for a
+///   message of any variant.
+/// - `MessageReceiver`, holding a consumer for each variant, with a
+///   `recv_<variant>()` method per variant, and a `recv()` method which pops
+///   from the queues in order of variant declaration, so that earlier variants
+///   take priority.
+/// - `Message::channels()`, which creates the queues and returns a sender and
+///   receiver.
+///
+/// Unit variants are carried as `()`, single-field tuple variants as the type
+/// of their field, and tuple variants with several fields as a tuple. Variants
+/// with named fields and generic enums are not supported.
+#[proc_macro_derive(Channels)]
+pub fn derive_channels(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_channels(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_channels(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "`Channels` can only be derived for enums",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`Channels` cannot be derived for generic enums",
+        ));
+    }
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let sender = format_ident!("{}Sender", name);
+    let receiver = format_ident!("{}Receiver", name);
+
+    let mut fields = Vec::new();
+    let mut types = Vec::new();
+    let mut send_arms = Vec::new();
+    let mut recv_values = Vec::new();
+    for variant in &data.variants {
+        let variant_name = &variant.ident;
+        let field = Ident::new(&snake_case(&variant_name.to_string()), variant_name.span());
+
+        let bindings: Vec<Ident> =
+            (0..variant.fields.len()).map(|i| format_ident!("field{}", i)).collect();
+        let (ty, pattern): (Type, _) = match &variant.fields {
+            Fields::Unit => (syn::parse_quote!(()), quote!(#name::#variant_name)),
+            Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                let ty = &unnamed.unnamed[0].ty;
+                (ty.clone(), quote!(#name::#variant_name(#(#bindings),*)))
+            }
+            Fields::Unnamed(unnamed) => {
+                let tys = unnamed.unnamed.iter().map(|field| &field.ty);
+                (syn::parse_quote!((#(#tys,)*)), quote!(#name::#variant_name(#(#bindings),*)))
+            }
+            Fields::Named(_) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "`Channels` does not support variants with named fields",
+                ))
+            }
+        };
+
+        let value = match &variant.fields {
+            Fields::Unit => quote!(()),
+            Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => quote!(field0),
+            _ => quote!((#(#bindings,)*)),
+        };
+        send_arms.push(quote! {
+            #pattern => self.#field.push(::llq::Node::new(#value)),
+        });
+        recv_values.push(quote! {
+            if let ::core::option::Option::Some(node) = self.#field.pop() {
+                let #value = ::llq::Node::into_inner(node);
+                return ::core::option::Option::Some(#pattern);
+            }
+        });
+
+        fields.push(field);
+        types.push(ty);
+    }
+
+    let send_fns = fields.iter().map(|field| format_ident!("send_{}", field));
+    let recv_fns = fields.iter().map(|field| format_ident!("recv_{}", field));
+    let consumers = fields.iter().map(|field| format_ident!("{}_consumer", field));
+    let consumers2 = consumers.clone();
+    let producers = fields.iter().map(|field| format_ident!("{}_producer", field));
+    let producers2 = producers.clone();
+
+    let sender_doc = format!("The sending half of the queues for [`{}`].", name);
+    let receiver_doc = format!("The receiving half of the queues for [`{}`].", name);
+
+    Ok(quote! {
+        #[doc = #sender_doc]
+        #vis struct #sender {
+            #(#fields: ::llq::Producer<#types>,)*
+        }
+
+        #[doc = #receiver_doc]
+        #vis struct #receiver {
+            #(#fields: ::llq::Consumer<#types>,)*
+        }
+
+        impl #name {
+            /// Creates a queue for each variant, returning the sending and
+            /// receiving halves.
+            #vis fn channels() -> (#sender, #receiver) {
+                #(let (#producers, #consumers) = ::llq::Queue::new().split();)*
+                (
+                    #sender { #(#fields: #producers2,)* },
+                    #receiver { #(#fields: #consumers2,)* },
+                )
+            }
+        }
+
+        impl #sender {
+            #(
+                /// Pushes a node onto this variant's queue.
+                #vis fn #send_fns(&mut self, node: ::llq::Node<#types>) {
+                    self.#fields.push(node);
+                }
+            )*
+
+            /// Allocates a node for a message and pushes it onto its
+            /// variant's queue.
+            #vis fn send(&mut self, message: #name) {
+                match message {
+                    #(#send_arms)*
+                }
+            }
+        }
+
+        impl #receiver {
+            #(
+                /// Pops a node from this variant's queue.
+                #vis fn #recv_fns(&mut self) -> ::core::option::Option<::llq::Node<#types>> {
+                    self.#fields.pop()
+                }
+            )*
+
+            /// Pops a message from the first non-empty queue, in order of
+            /// variant declaration, and frees its node.
+            #vis fn recv(&mut self) -> ::core::option::Option<#name> {
+                #(#recv_values)*
+                ::core::option::Option::None
+            }
+        }
+    })
+}
+
+/// Converts a `CamelCase` identifier to `snake_case`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let after_lower = i > 0 && !chars[i - 1].is_uppercase() && chars[i - 1] != '_';
+            let before_lower = i > 0
+                && chars[i - 1].is_uppercase()
+                && chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if after_lower || before_lower {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
@@ -0,0 +1,135 @@
+//! Accounting of the memory allocated by the crate.
+//!
+//! With the `accounting` feature enabled, every allocation made through the
+//! crate's allocator is counted, so the memory held by queues and nodes can
+//! be told apart from the rest of a program's heap. [`global()`] returns the
+//! number and total size of the allocations which are currently live.
+//! [`Pool::usage()`] and [`SharedPool::usage()`] return the share of them
+//! which a pool holds in reserve, and [`Pool::outstanding()`] and
+//! [`SharedPool::outstanding()`] the nodes taken from a pool which have not
+//! been recycled into it yet, so that a pool whose nodes pile up elsewhere
+//! can be found.
+//!
+//! Each node is one allocation, including the sentinel node of each queue,
+//! and so is the block of each [`Arena`]. Allocations released into the
+//! per-thread cache of the `std` feature no longer count as live.
+//!
+//! ```rust
+//! use llq::accounting;
+//! use llq::pool::Pool;
+//!
+//! let mut pool = Pool::<[u8; 64]>::with_capacity(16);
+//! assert_eq!(pool.usage().allocations, 16);
+//! assert!(accounting::global().bytes >= pool.usage().bytes);
+//!
+//! let node = pool.alloc([0; 64]);
+//! assert_eq!(pool.usage().allocations, 15);
+//! assert_eq!(pool.outstanding().allocations, 1);
+//! pool.recycle(node);
+//! assert_eq!(pool.outstanding().allocations, 0);
+//! ```
+//!
+//! [`global()`]: crate::accounting::global
+//! [`Pool::usage()`]: crate::pool::Pool::usage
+//! [`SharedPool::usage()`]: crate::pool::SharedPool::usage
+//! [`Pool::outstanding()`]: crate::pool::Pool::outstanding
+//! [`SharedPool::outstanding()`]: crate::pool::SharedPool::outstanding
+//! [`Arena`]: crate::arena::Arena
+
+use crate::pool::{Pool, SharedPool};
+use crate::NodeInner;
+
+use core::alloc::Layout;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// As with the allocation error hook, these are plain atomics rather than ones
+// from `crate::sync`, since they are not part of a queue's protocol.
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// A number of allocations and their total size.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of allocations.
+    pub allocations: usize,
+    /// The total size of the allocations in bytes.
+    pub bytes: usize,
+}
+
+/// Returns the allocations made by the crate which are currently live. The
+/// counts may be out of date by the time they are returned.
+pub fn global() -> Usage {
+    Usage { allocations: ALLOCATIONS.load(Ordering::Relaxed), bytes: BYTES.load(Ordering::Relaxed) }
+}
+
+/// Records that a block with the given layout was allocated.
+pub(crate) fn allocated(layout: Layout) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+}
+
+/// Records that a block with the given layout was deallocated.
+pub(crate) fn deallocated(layout: Layout) {
+    ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+    BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+}
+
+fn node_usage<T>(allocations: usize) -> Usage {
+    Usage { allocations, bytes: allocations * mem::size_of::<NodeInner<T>>() }
+}
+
+impl<T> Pool<T> {
+    /// Returns the allocations held in the pool's free list.
+    pub fn usage(&self) -> Usage {
+        node_usage::<T>(self.len())
+    }
+
+    /// Returns the nodes taken from the pool which have not been recycled
+    /// into it yet.
+    pub fn outstanding(&self) -> Usage {
+        node_usage::<T>(self.outstanding_nodes())
+    }
+}
+
+impl<T> SharedPool<T> {
+    /// Returns the allocations held in the pool's free list. The counts may be
+    /// out of date by the time they are returned.
+    pub fn usage(&self) -> Usage {
+        node_usage::<T>(self.len())
+    }
+
+    /// Returns the nodes taken from the pool which have not been recycled
+    /// into it yet. The counts may be out of date by the time they are
+    /// returned.
+    ///
+    /// Nodes which a queue adds to its reclaim pool count as returned, so
+    /// nodes allocated elsewhere offset those still outstanding.
+    pub fn outstanding(&self) -> Usage {
+        node_usage::<T>(self.outstanding_nodes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::arena::Arena;
+
+    #[test]
+    fn usage() {
+        let pool = SharedPool::<u64>::with_capacity(3);
+        let node = pool.try_alloc(0).unwrap();
+        assert_eq!(pool.usage(), node_usage::<u64>(2));
+        assert_eq!(pool.outstanding(), node_usage::<u64>(1));
+        pool.recycle(node);
+        assert_eq!(pool.usage().allocations, 3);
+        assert_eq!(pool.outstanding().allocations, 0);
+
+        // Other tests allocate and deallocate concurrently, but only their own
+        // allocations.
+        let arena = Arena::with_capacity(1 << 20);
+        assert!(global().allocations >= 4);
+        assert!(global().bytes >= arena.capacity() + pool.usage().bytes);
+    }
+}
@@ -0,0 +1,288 @@
+//! Nodes and queues allocated from a bump arena.
+//!
+//! An [`Arena`] hands out memory from a single preallocated block by bumping
+//! an offset, and releases all of it at once when it is dropped or
+//! [`reset()`]. [`ArenaNode`]s and the queues created with
+//! [`Arena::split_queue()`] borrow the arena, so the borrow checker ensures
+//! that none of them outlive it. This suits short-lived channels, such as the
+//! scratch channels of a single frame, which would otherwise free each node
+//! individually.
+//!
+//! As with other bump allocators, memory is only reclaimed in bulk: dropping
+//! an [`ArenaNode`] drops its value but does not make its memory available
+//! again. Elements still in an arena queue when its halves are dropped are
+//! leaked rather than dropped.
+//!
+//! ```rust
+//! use llq::arena::{Arena, ArenaNode};
+//!
+//! let mut arena = Arena::with_capacity(4096);
+//!
+//! for frame in 0..3 {
+//!     let (mut producer, mut consumer) = arena.split_queue().unwrap();
+//!
+//!     std::thread::scope(|scope| {
+//!         scope.spawn(|| {
+//!             for i in 0..4 {
+//!                 producer.push(ArenaNode::new_in(&arena, frame * 4 + i).unwrap());
+//!             }
+//!         });
+//!     });
+//!
+//!     let values: Vec<_> = std::iter::from_fn(|| consumer.pop()).map(|node| *node).collect();
+//!     assert_eq!(values, [frame * 4, frame * 4 + 1, frame * 4 + 2, frame * 4 + 3]);
+//!
+//!     drop((producer, consumer));
+//!     arena.reset();
+//! }
+//! ```
+//!
+//! [`Arena`]: crate::arena::Arena
+//! [`reset()`]: crate::arena::Arena::reset
+//! [`ArenaNode`]: crate::arena::ArenaNode
+//! [`Arena::split_queue()`]: crate::arena::Arena::split_queue
+
+use crate::error::AllocError;
+use crate::scoped::{ConsumerRef, ProducerRef};
+use crate::sync::{AtomicPtr, AtomicUsize, RELAXED};
+use crate::{heap, Node, NodeInner, Queue};
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::ptr::NonNull;
+
+/// The alignment of an arena's block. Allocations with a larger alignment are
+/// aligned within the block.
+const BLOCK_ALIGN: usize = 16;
+
+/// A bump allocator for nodes and queues.
+pub struct Arena {
+    block: NonNull<u8>,
+    capacity: usize,
+    used: AtomicUsize,
+}
+
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    /// Creates an arena with a block of `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Arena {
+        let block = heap::allocate(Arena::layout(capacity));
+        Arena {
+            block: unsafe { NonNull::new_unchecked(block) },
+            capacity,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the size of the arena's block in bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of bytes allocated from the arena, including
+    /// padding.
+    pub fn used(&self) -> usize {
+        self.used.load(RELAXED)
+    }
+
+    /// Makes the arena's entire block available again.
+    ///
+    /// Taking `&mut self` ensures that nothing allocated from the arena is
+    /// still in use.
+    pub fn reset(&mut self) {
+        *self.used.get_mut() = 0;
+    }
+
+    /// Splits a new queue, allocated from the arena, into producer and
+    /// consumer halves. Fails if the arena does not have room for the queue.
+    pub fn split_queue<T>(
+        &self,
+    ) -> Result<(ArenaProducer<'_, T>, ArenaConsumer<'_, T>), AllocError<()>> {
+        let queue = self.allocate(Layout::new::<Queue<T>>()).ok_or(AllocError::Exhausted(()))?;
+        let sentinel =
+            self.allocate(Layout::new::<NodeInner<T>>()).ok_or(AllocError::Exhausted(()))?;
+
+        unsafe {
+            let sentinel = sentinel.cast::<NodeInner<T>>().as_ptr();
+            sentinel.write(NodeInner::empty(MaybeUninit::uninit()));
+
+            let queue = queue.cast::<Queue<T>>().as_ptr();
+            queue.write(Queue::from_sentinel(sentinel));
+
+            // The queue is never dropped or accessed other than through the
+            // halves.
+            let (producer, consumer) = (*queue).split_borrowed();
+            Ok((ArenaProducer { producer }, ArenaConsumer { consumer }))
+        }
+    }
+
+    /// Allocates a block with the given layout, or returns `None` if the
+    /// arena is full.
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let base = self.block.as_ptr().addr();
+        let mut used = self.used.load(RELAXED);
+        loop {
+            let start = (base + used).checked_next_multiple_of(layout.align())? - base;
+            let end = start.checked_add(layout.size())?;
+            if end > self.capacity {
+                return None;
+            }
+
+            match self.used.compare_exchange_weak(used, end, RELAXED, RELAXED) {
+                Ok(_) => {
+                    return Some(unsafe { NonNull::new_unchecked(self.block.as_ptr().add(start)) })
+                }
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity.max(1), BLOCK_ALIGN).unwrap()
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe { heap::deallocate(self.block.as_ptr(), Arena::layout(self.capacity)) };
+    }
+}
+
+/// A node allocated from an [`Arena`].
+///
+/// [`Arena`]: crate::arena::Arena
+pub struct ArenaNode<'a, T> {
+    inner: NonNull<NodeInner<T>>,
+    phantom: PhantomData<(&'a Arena, T)>,
+}
+
+unsafe impl<'a, T: Send> Send for ArenaNode<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for ArenaNode<'a, T> {}
+
+impl<'a, T> ArenaNode<'a, T> {
+    /// Allocates a node containing `value` from `arena`. Returns the value in
+    /// an error if the arena is full.
+    pub fn new_in(arena: &'a Arena, value: T) -> Result<ArenaNode<'a, T>, AllocError<T>> {
+        let Some(block) = arena.allocate(Layout::new::<NodeInner<T>>()) else {
+            return Err(AllocError::Exhausted(value));
+        };
+
+        let inner = block.cast::<NodeInner<T>>();
+        unsafe { inner.as_ptr().write(NodeInner::empty(MaybeUninit::new(value))) };
+        Ok(ArenaNode { inner, phantom: PhantomData })
+    }
+
+    /// Returns the node's value. The node's memory is released along with
+    /// the rest of the arena.
+    pub fn into_inner(this: ArenaNode<'a, T>) -> T {
+        let this = mem::ManuallyDrop::new(this);
+        unsafe { ptr::read(this.inner.as_ref().data.as_ptr()) }
+    }
+}
+
+impl<'a, T> Deref for ArenaNode<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.inner.as_ref().data.as_ptr() }
+    }
+}
+
+impl<'a, T> DerefMut for ArenaNode<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.inner.as_mut().data.as_mut_ptr() }
+    }
+}
+
+impl<'a, T> Drop for ArenaNode<'a, T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.inner.as_mut().data.as_mut_ptr()) };
+    }
+}
+
+/// The producer half of a queue allocated from an [`Arena`].
+///
+/// [`Arena`]: crate::arena::Arena
+pub struct ArenaProducer<'a, T> {
+    producer: ProducerRef<'a, T>,
+}
+
+impl<'a, T> ArenaProducer<'a, T> {
+    /// Adds an element to the queue.
+    pub fn push(&mut self, node: ArenaNode<'a, T>) {
+        // The node is never dropped as a `Node`, since the queue is never
+        // dropped and the consumer converts popped nodes back.
+        let inner = node.inner;
+        mem::forget(node);
+        self.producer.push(Node { inner, phantom: PhantomData });
+    }
+}
+
+/// The consumer half of a queue allocated from an [`Arena`].
+///
+/// [`Arena`]: crate::arena::Arena
+pub struct ArenaConsumer<'a, T> {
+    consumer: ConsumerRef<'a, T>,
+}
+
+impl<'a, T> ArenaConsumer<'a, T> {
+    /// Attempts to remove and return an element from the queue. Returns `None`
+    /// if the queue is empty.
+    pub fn pop(&mut self) -> Option<ArenaNode<'a, T>> {
+        let node = self.consumer.pop()?;
+        let inner = node.inner;
+        mem::forget(node);
+        Some(ArenaNode { inner, phantom: PhantomData })
+    }
+}
+
+impl<T> NodeInner<T> {
+    /// Returns an unlinked node containing the given data.
+    fn empty(data: MaybeUninit<T>) -> NodeInner<T> {
+        NodeInner {
+            next: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "timestamps")]
+            pushed: None,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::rc::Rc;
+
+    #[test]
+    fn exhaust_and_reset() {
+        let mut arena = Arena::with_capacity(256);
+        let counter = Rc::new(());
+
+        {
+            let (mut producer, mut consumer) = arena.split_queue().unwrap();
+            let mut pushed = 0;
+            while let Ok(node) = ArenaNode::new_in(&arena, counter.clone()) {
+                producer.push(node);
+                pushed += 1;
+            }
+            assert!(pushed > 0);
+            assert_eq!(Rc::strong_count(&counter), pushed + 1);
+
+            let node = consumer.pop().unwrap();
+            assert_eq!(Rc::strong_count(&counter), pushed + 1);
+            drop(node);
+            assert_eq!(Rc::strong_count(&counter), pushed);
+        }
+
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+        assert!(ArenaNode::new_in(&arena, 0u64).is_ok());
+    }
+}
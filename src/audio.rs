@@ -0,0 +1,108 @@
+//! Sample-accurate event delivery for block-based audio processing.
+//!
+//! Events are pushed as [`Event`]s tagged with the sample frame at which they
+//! should take effect. On the audio thread, [`Consumer::pop_for_block()`]
+//! returns only the events which fall within the block currently being
+//! processed, leaving later events in the queue for subsequent blocks.
+//!
+//! ```rust
+//! use llq::audio::Event;
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, mut consumer) = Queue::new().split();
+//!
+//! producer.push(Node::new(Event::new(100, "note on")));
+//! producer.push(Node::new(Event::new(300, "note off")));
+//!
+//! // Process the block of 256 frames starting at frame 0.
+//! let (offset, event) = consumer.pop_for_block(0, 256).unwrap();
+//! assert_eq!((offset, event.data), (100, "note on"));
+//! assert!(consumer.pop_for_block(0, 256).is_none());
+//!
+//! // Process the next block.
+//! let (offset, event) = consumer.pop_for_block(256, 256).unwrap();
+//! assert_eq!((offset, event.data), (44, "note off"));
+//! ```
+//!
+//! [`Event`]: crate::audio::Event
+//! [`Consumer::pop_for_block()`]: crate::Consumer::pop_for_block
+
+use crate::{Consumer, Node};
+
+/// An event scheduled for a specific sample frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Event<T> {
+    /// The sample frame at which the event takes effect.
+    pub time: u64,
+    /// The event payload.
+    pub data: T,
+}
+
+impl<T> Event<T> {
+    /// Creates a new event scheduled for the given sample frame.
+    pub fn new(time: u64, data: T) -> Event<T> {
+        Event { time, data }
+    }
+}
+
+impl<T> Consumer<Event<T>> {
+    /// Removes and returns the next event scheduled before the end of the
+    /// block of `block_len` frames starting at `block_start`, along with its
+    /// frame offset within the block. Returns `None` if the queue is empty or
+    /// if the next event is scheduled for a later block.
+    ///
+    /// Events scheduled before `block_start` (i.e. events which arrived too
+    /// late for the block they were intended for) are returned with an offset
+    /// of zero.
+    ///
+    /// Events are expected to be pushed in nondecreasing order of time. An
+    /// event scheduled for a later block holds back any events queued behind
+    /// it.
+    pub fn pop_for_block(
+        &mut self,
+        block_start: u64,
+        block_len: usize,
+    ) -> Option<(usize, Node<Event<T>>)> {
+        let block_end = block_start.saturating_add(block_len as u64);
+
+        let time = self.peek()?.time;
+        if time >= block_end {
+            return None;
+        }
+
+        let offset = time.saturating_sub(block_start) as usize;
+        self.pop().map(|event| (offset, event))
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn block_boundaries() {
+        let (mut producer, mut consumer) = Queue::new().split();
+
+        for &time in &[10, 63, 64, 127, 200] {
+            producer.push(Node::new(Event::new(time, ())));
+        }
+
+        let mut offsets = [0; 8];
+        let mut count = 0;
+        while let Some((offset, _)) = consumer.pop_for_block(64, 64) {
+            offsets[count] = offset;
+            count += 1;
+        }
+
+        // Late events are delivered at the start of the block.
+        assert_eq!(&offsets[..count], &[0, 0, 0, 63]);
+
+        assert!(consumer.pop_for_block(128, 64).is_none());
+
+        let (offset, event) = consumer.pop_for_block(192, 64).unwrap();
+        assert_eq!(offset, 8);
+        assert_eq!(event.time, 200);
+        assert!(consumer.pop().is_none());
+    }
+}
@@ -0,0 +1,111 @@
+//! Batched publication of pushes.
+//!
+//! [`BatchingProducer`] collects pushed nodes into a local [`Chain`] and
+//! publishes them with a single atomic store on [`flush()`], or automatically
+//! once a threshold is reached. At very high message rates this amortizes the
+//! cost of publishing each node, and the consumer sees each batch at once.
+//!
+//! ```rust
+//! use llq::batch::BatchingProducer;
+//! use llq::{Node, Queue};
+//!
+//! let (producer, mut consumer) = Queue::new().split();
+//! let mut producer = BatchingProducer::new(producer, 64);
+//!
+//! producer.push(Node::new(1));
+//! producer.push(Node::new(2));
+//! assert!(consumer.pop().is_none());
+//!
+//! producer.flush();
+//! assert_eq!(*consumer.pop().unwrap(), 1);
+//! assert_eq!(*consumer.pop().unwrap(), 2);
+//! ```
+//!
+//! [`BatchingProducer`]: crate::batch::BatchingProducer
+//! [`Chain`]: crate::Chain
+//! [`flush()`]: crate::batch::BatchingProducer::flush
+
+use crate::{Chain, Node, Producer};
+
+use core::mem;
+use core::mem::ManuallyDrop;
+use core::ptr;
+
+/// A [`Producer`] which publishes pushes in batches.
+///
+/// Any pending nodes are published when the `BatchingProducer` is dropped.
+///
+/// [`Producer`]: crate::Producer
+pub struct BatchingProducer<T> {
+    producer: Producer<T>,
+    pending: Chain<T>,
+    threshold: usize,
+}
+
+impl<T> BatchingProducer<T> {
+    /// Creates a new batching producer which publishes automatically once
+    /// `threshold` nodes are pending.
+    pub fn new(producer: Producer<T>, threshold: usize) -> BatchingProducer<T> {
+        BatchingProducer { producer, pending: Chain::new(), threshold }
+    }
+
+    /// Adds a node to the current batch, publishing the batch if it has
+    /// reached the threshold.
+    pub fn push(&mut self, node: Node<T>) {
+        self.pending.push_back(node);
+        if self.pending.len() >= self.threshold {
+            self.flush();
+        }
+    }
+
+    /// Returns the number of nodes waiting to be published.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Publishes all pending nodes with a single atomic store.
+    pub fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            self.producer.push_chain(mem::take(&mut self.pending));
+        }
+    }
+
+    /// Publishes any pending nodes and returns the inner producer.
+    pub fn into_inner(mut self) -> Producer<T> {
+        self.flush();
+
+        // The pending chain is now empty, so it owns nothing that needs to be
+        // dropped.
+        let this = ManuallyDrop::new(self);
+        unsafe { ptr::read(&this.producer) }
+    }
+}
+
+impl<T> Drop for BatchingProducer<T> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn threshold() {
+        let (producer, mut consumer) = Queue::new().split();
+        let mut producer = BatchingProducer::new(producer, 2);
+
+        producer.push(Node::new(0));
+        assert_eq!(producer.pending(), 1);
+        assert!(consumer.pop().is_none());
+        producer.push(Node::new(1));
+        assert_eq!(producer.pending(), 0);
+        assert_eq!(consumer.pop_chain().len(), 2);
+
+        producer.push(Node::new(2));
+        drop(producer);
+        assert_eq!(*consumer.pop().unwrap(), 2);
+    }
+}
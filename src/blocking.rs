@@ -0,0 +1,237 @@
+//! Queue halves whose consumer can block until elements are available.
+//!
+//! [`Queue::split_blocking()`] returns a [`BlockingProducer`] which wakes the
+//! consumer's thread when it pushes an element, and a [`BlockingConsumer`]
+//! which can wait for elements with [`BlockingConsumer::pop_wait()`]. The
+//! producer only takes a lock to wake the consumer when the consumer has
+//! found the queue empty.
+//!
+//! By default the consumer is parked with the standard library's `Mutex` and
+//! `Condvar`. With the `parking_lot` feature enabled, `parking_lot`'s versions
+//! are used instead.
+//!
+//! ```rust
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, mut consumer) = Queue::<u32>::new().split_blocking();
+//!
+//! let thread = std::thread::spawn(move || {
+//!     producer.push(Node::new(1));
+//! });
+//!
+//! assert_eq!(*consumer.pop_wait().unwrap(), 1);
+//! assert!(consumer.pop_wait().is_none());
+//! thread.join().unwrap();
+//! ```
+//!
+//! [`Queue::split_blocking()`]: crate::Queue::split_blocking
+//! [`BlockingProducer`]: crate::blocking::BlockingProducer
+//! [`BlockingConsumer`]: crate::blocking::BlockingConsumer
+//! [`BlockingConsumer::pop_wait()`]: crate::blocking::BlockingConsumer::pop_wait
+
+use crate::sync::{AtomicBool, ACQUIRE, RELEASE};
+use crate::waiting::Waiting;
+use crate::{Consumer, Node, Producer, Queue};
+
+use alloc::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "parking_lot")]
+use parking_lot::{Condvar, Mutex};
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::{Condvar, Mutex};
+
+/// Parks the consumer's thread until it is unparked or a deadline passes.
+struct Parker {
+    notified: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Parker {
+    fn new() -> Parker {
+        Parker { notified: Mutex::new(false), condvar: Condvar::new() }
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    fn park(&self, deadline: Option<Instant>) {
+        let mut notified = self.notified.lock().unwrap();
+        while !*notified {
+            notified = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    self.condvar.wait_timeout(notified, deadline - now).unwrap().0
+                }
+                None => self.condvar.wait(notified).unwrap(),
+            };
+        }
+        *notified = false;
+    }
+
+    #[cfg(feature = "parking_lot")]
+    fn park(&self, deadline: Option<Instant>) {
+        let mut notified = self.notified.lock();
+        while !*notified {
+            match deadline {
+                Some(deadline) => {
+                    if self.condvar.wait_until(&mut notified, deadline).timed_out() {
+                        break;
+                    }
+                }
+                None => self.condvar.wait(&mut notified),
+            }
+        }
+        *notified = false;
+    }
+
+    fn unpark(&self) {
+        #[cfg(not(feature = "parking_lot"))]
+        let mut notified = self.notified.lock().unwrap();
+        #[cfg(feature = "parking_lot")]
+        let mut notified = self.notified.lock();
+
+        *notified = true;
+        self.condvar.notify_one();
+    }
+}
+
+struct Signal {
+    parker: Parker,
+    waiting: Waiting,
+    closed: AtomicBool,
+}
+
+impl<T> Queue<T> {
+    /// Splits a queue into producer and consumer halves whose consumer can
+    /// block until elements are available.
+    pub fn split_blocking(self) -> (BlockingProducer<T>, BlockingConsumer<T>) {
+        let signal = Arc::new(Signal {
+            parker: Parker::new(),
+            waiting: Waiting::new(false),
+            closed: AtomicBool::new(false),
+        });
+
+        let (producer, consumer) = self.split();
+
+        (
+            BlockingProducer { producer, signal: signal.clone() },
+            BlockingConsumer { consumer, signal },
+        )
+    }
+}
+
+/// The consumer half of a [`Queue`] split with [`Queue::split_blocking()`].
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_blocking()`]: crate::Queue::split_blocking
+pub struct BlockingConsumer<T> {
+    consumer: Consumer<T>,
+    signal: Arc<Signal>,
+}
+
+impl<T> BlockingConsumer<T> {
+    /// Attempts to remove and return an element from the queue without
+    /// waiting. Returns `None` if the queue is empty.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        self.consumer.pop()
+    }
+
+    /// Removes and returns an element from the queue, blocking until one is
+    /// available. Returns `None` if the queue is empty and the producer has
+    /// been dropped.
+    pub fn pop_wait(&mut self) -> Option<Node<T>> {
+        self.pop_until(None)
+    }
+
+    /// Removes and returns an element from the queue, blocking until one is
+    /// available or `timeout` has elapsed. Returns `None` on timeout, or if
+    /// the queue is empty and the producer has been dropped.
+    pub fn pop_timeout(&mut self, timeout: Duration) -> Option<Node<T>> {
+        self.pop_until(Some(Instant::now() + timeout))
+    }
+
+    fn pop_until(&mut self, deadline: Option<Instant>) -> Option<Node<T>> {
+        loop {
+            if let Some(node) = self.consumer.pop() {
+                return Some(node);
+            }
+
+            self.signal.waiting.set();
+
+            // Check again in case an element was pushed before the consumer
+            // was marked as waiting.
+            if let Some(node) = self.consumer.pop() {
+                self.signal.waiting.clear();
+                return Some(node);
+            }
+
+            if self.signal.closed.load(ACQUIRE) {
+                // All pushes happen before the producer is closed.
+                return self.consumer.pop();
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.signal.waiting.clear();
+                return None;
+            }
+
+            self.signal.parker.park(deadline);
+        }
+    }
+}
+
+/// The producer half of a [`Queue`] split with [`Queue::split_blocking()`].
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_blocking()`]: crate::Queue::split_blocking
+pub struct BlockingProducer<T> {
+    producer: Producer<T>,
+    signal: Arc<Signal>,
+}
+
+impl<T> BlockingProducer<T> {
+    /// Adds an element to the queue and wakes the consumer's thread if it is
+    /// waiting.
+    pub fn push(&mut self, node: Node<T>) {
+        self.producer.push(node);
+
+        if self.signal.waiting.take() {
+            self.signal.parker.unpark();
+        }
+    }
+}
+
+impl<T> Drop for BlockingProducer<T> {
+    fn drop(&mut self) {
+        self.signal.closed.store(true, RELEASE);
+        self.signal.parker.unpark();
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn wait_and_timeout() {
+        let (mut producer, mut consumer) = Queue::new().split_blocking();
+        assert!(consumer.pop_timeout(Duration::from_millis(1)).is_none());
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..1000 {
+                producer.push(Node::new(i));
+            }
+        });
+
+        for i in 0..1000 {
+            assert_eq!(*consumer.pop_wait().unwrap(), i);
+        }
+        assert!(consumer.pop_wait().is_none());
+
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+//! Forwarding a queue over a byte stream.
+//!
+//! A [`BridgeSender`] serializes the elements of a queue with [postcard] and
+//! writes them to an [`io::Write`], and a [`BridgeReceiver`] on the far side
+//! reads them back and pushes them onto a local queue, preserving their order.
+//! Each element is framed with a 32-bit little-endian length prefix.
+//!
+//! The sender only removes an element from its queue once it has been
+//! written, so a slow or blocked stream leaves elements queued rather than
+//! dropping them. A frame which was only partly written, for example because
+//! a non-blocking stream returned [`io::ErrorKind::WouldBlock`], is finished
+//! by the next send before anything else is written.
+//!
+//! With the `async` feature enabled, [`send_async()`] and [`receive_async()`]
+//! do the same over [`AsyncWrite`] and [`AsyncRead`].
+//!
+//! ```rust
+//! use llq::bridge::{BridgeReceiver, BridgeSender};
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, consumer) = Queue::<(u32, String)>::new().split();
+//! let mut sender = BridgeSender::new(consumer, Vec::new());
+//!
+//! producer.push(Node::new((1, "play".to_string())));
+//! producer.push(Node::new((2, "stop".to_string())));
+//! assert_eq!(sender.send_all().unwrap(), 2);
+//!
+//! let (_, stream) = sender.into_inner();
+//! let (producer, mut consumer) = Queue::<(u32, String)>::new().split();
+//! let mut receiver = BridgeReceiver::new(producer, &stream[..]);
+//! assert_eq!(receiver.receive_all().unwrap(), 2);
+//!
+//! assert_eq!(*consumer.pop().unwrap(), (1, "play".to_string()));
+//! assert_eq!(*consumer.pop().unwrap(), (2, "stop".to_string()));
+//! ```
+//!
+//! [postcard]: https://docs.rs/postcard/1
+//! [`BridgeSender`]: crate::bridge::BridgeSender
+//! [`BridgeReceiver`]: crate::bridge::BridgeReceiver
+//! [`io::Write`]: std::io::Write
+//! [`send_async()`]: crate::bridge::send_async
+//! [`receive_async()`]: crate::bridge::receive_async
+//! [`AsyncWrite`]: futures_io::AsyncWrite
+//! [`AsyncRead`]: futures_io::AsyncRead
+
+use crate::{Consumer, Node, Producer};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+/// The largest frame a receiver will accept, in bytes. Longer frames are
+/// rejected as invalid data rather than allocated.
+pub const MAX_FRAME_LEN: usize = 1 << 24;
+
+/// Writes the elements of a queue to a byte stream.
+pub struct BridgeSender<T, W> {
+    consumer: Consumer<T>,
+    writer: W,
+    buf: Vec<u8>,
+    written: usize,
+}
+
+impl<T, W> BridgeSender<T, W>
+where
+    T: Serialize,
+    W: Write,
+{
+    /// Creates a new sender which writes the elements of `consumer`'s queue
+    /// to `writer`.
+    pub fn new(consumer: Consumer<T>, writer: W) -> BridgeSender<T, W> {
+        BridgeSender { consumer, writer, buf: Vec::new(), written: 0 }
+    }
+
+    /// Writes the element at the front of the queue, then removes it and
+    /// returns its node. Returns `Ok(None)` if the queue is empty.
+    ///
+    /// If serializing or writing fails, the element is left in the queue. The
+    /// part of its frame which was not written is kept, and the next call
+    /// writes it rather than serializing the element again.
+    pub fn send(&mut self) -> io::Result<Option<Node<T>>> {
+        // An empty buffer means that no frame is pending, since every frame
+        // starts with its length.
+        if self.buf.is_empty() {
+            let value = match self.consumer.peek() {
+                Some(value) => value,
+                None => return Ok(None),
+            };
+
+            encode(value, &mut self.buf)?;
+            self.written = 0;
+        }
+
+        while self.written < self.buf.len() {
+            match self.writer.write(&self.buf[self.written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.written += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.buf.clear();
+        Ok(self.consumer.pop())
+    }
+
+    /// Writes every element currently in the queue and flushes the stream,
+    /// returning the number of elements sent.
+    pub fn send_all(&mut self) -> io::Result<usize> {
+        let mut count = 0;
+        while self.send()?.is_some() {
+            count += 1;
+        }
+        self.writer.flush()?;
+        Ok(count)
+    }
+
+    /// Returns the inner consumer and writer.
+    ///
+    /// If a frame was only partly written, the rest of it is discarded and the
+    /// stream is left cut off partway through the frame. Its element is still
+    /// at the front of the queue.
+    pub fn into_inner(self) -> (Consumer<T>, W) {
+        (self.consumer, self.writer)
+    }
+}
+
+/// Reads elements from a byte stream and pushes them onto a queue.
+pub struct BridgeReceiver<T, R> {
+    producer: Producer<T>,
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<T, R> BridgeReceiver<T, R>
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    /// Creates a new receiver which reads from `reader` and pushes onto
+    /// `producer`'s queue.
+    pub fn new(producer: Producer<T>, reader: R) -> BridgeReceiver<T, R> {
+        BridgeReceiver { producer, reader, buf: Vec::new() }
+    }
+
+    /// Reads one element and pushes it onto the queue. Returns `Ok(false)` if
+    /// the stream ended cleanly before the next element.
+    ///
+    /// A stream which ends partway through an element results in an error of
+    /// kind [`io::ErrorKind::UnexpectedEof`].
+    pub fn receive(&mut self) -> io::Result<bool> {
+        let mut len = [0; 4];
+        let mut read = 0;
+        while read < len.len() {
+            match self.reader.read(&mut len[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.buf.resize(frame_len(len)?, 0);
+        self.reader.read_exact(&mut self.buf)?;
+
+        self.producer.push(Node::new(decode(&self.buf)?));
+        Ok(true)
+    }
+
+    /// Reads and pushes elements until the stream ends, returning the number
+    /// of elements received.
+    pub fn receive_all(&mut self) -> io::Result<usize> {
+        let mut count = 0;
+        while self.receive()? {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Returns the inner producer and reader.
+    pub fn into_inner(self) -> (Producer<T>, R) {
+        (self.producer, self.reader)
+    }
+}
+
+/// Writes each element received by `consumer` to `writer` until the producer
+/// is dropped and the queue has been drained.
+///
+/// An element whose write fails is lost.
+#[cfg(feature = "async")]
+pub async fn send_async<T, W>(
+    consumer: &mut crate::futures::AsyncConsumer<T>,
+    writer: &mut W,
+) -> io::Result<()>
+where
+    T: Serialize,
+    W: futures_io::AsyncWrite + Unpin,
+{
+    use core::future::poll_fn;
+    use core::pin::Pin;
+
+    let mut buf = Vec::new();
+    while let Some(node) = poll_fn(|cx| consumer.poll_pop(cx)).await {
+        encode(&*node, &mut buf)?;
+
+        let mut written = 0;
+        while written < buf.len() {
+            let n = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, &buf[written..])).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            written += n;
+        }
+        poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads elements from `reader` and pushes them with `producer` until the
+/// stream ends, returning the number of elements received.
+#[cfg(feature = "async")]
+pub async fn receive_async<T, R>(
+    reader: &mut R,
+    producer: &mut crate::futures::AsyncProducer<T>,
+) -> io::Result<usize>
+where
+    T: DeserializeOwned,
+    R: futures_io::AsyncRead + Unpin,
+{
+    use core::future::poll_fn;
+    use core::pin::Pin;
+
+    async fn read_exact<R: futures_io::AsyncRead + Unpin>(
+        reader: &mut R,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            let n = poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf[read..])).await?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+        Ok(read)
+    }
+
+    let mut buf = Vec::new();
+    let mut count = 0;
+    loop {
+        let mut len = [0; 4];
+        match read_exact(reader, &mut len).await? {
+            0 => return Ok(count),
+            4 => {}
+            _ => return Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+
+        buf.resize(frame_len(len)?, 0);
+        if read_exact(reader, &mut buf).await? < buf.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        producer.push(Node::new(decode(&buf)?));
+        count += 1;
+    }
+}
+
+/// Replaces the contents of `buf` with the framed serialization of `value`.
+fn encode<T: Serialize>(value: &T, buf: &mut Vec<u8>) -> io::Result<()> {
+    buf.clear();
+    buf.extend_from_slice(&[0; 4]);
+    let mut frame = postcard::to_extend(value, core::mem::take(buf))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let len = u32::try_from(frame.len() - 4)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "frame too large"))?;
+    frame[..4].copy_from_slice(&len.to_le_bytes());
+
+    *buf = frame;
+    Ok(())
+}
+
+fn frame_len(len: [u8; 4]) -> io::Result<usize> {
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+    Ok(len)
+}
+
+fn decode<T: DeserializeOwned>(frame: &[u8]) -> io::Result<T> {
+    postcard::from_bytes(frame).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Command {
+        Gain(f32),
+        Name(std::string::String),
+    }
+
+    #[test]
+    fn truncated_stream() {
+        let (mut producer, consumer) = Queue::new().split();
+        let mut sender = BridgeSender::new(consumer, Vec::new());
+        producer.push(Node::new(Command::Gain(0.5)));
+        producer.push(Node::new(Command::Name("bus".into())));
+        assert_eq!(sender.send_all().unwrap(), 2);
+
+        let (_, mut stream) = sender.into_inner();
+        stream.pop();
+
+        let (producer, mut consumer) = Queue::<Command>::new().split();
+        let mut receiver = BridgeReceiver::new(producer, &stream[..]);
+        assert!(receiver.receive().unwrap());
+        assert_eq!(receiver.receive().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(*consumer.pop().unwrap(), Command::Gain(0.5));
+        assert!(consumer.pop().is_none());
+    }
+
+    /// Accepts at most three bytes per write, and fails every other write with
+    /// `WouldBlock`.
+    struct Choppy {
+        stream: Vec<u8>,
+        block: bool,
+    }
+
+    impl Write for Choppy {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.block = !self.block;
+            if self.block {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            let n = buf.len().min(3);
+            self.stream.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn resume_partial_frame() {
+        let (mut producer, consumer) = Queue::new().split();
+        let writer = Choppy { stream: Vec::new(), block: false };
+        let mut sender = BridgeSender::new(consumer, writer);
+        producer.push(Node::new(Command::Name("master".into())));
+        producer.push(Node::new(Command::Gain(0.25)));
+
+        let mut sent = 0;
+        let mut blocked = 0;
+        while sent < 2 {
+            match sender.send() {
+                Ok(node) => {
+                    node.unwrap();
+                    sent += 1;
+                }
+                Err(err) => {
+                    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+                    blocked += 1;
+                }
+            }
+        }
+        assert!(blocked > 2);
+        assert!(sender.send().unwrap().is_none());
+
+        let (_, writer) = sender.into_inner();
+        let (producer, mut consumer) = Queue::<Command>::new().split();
+        let mut receiver = BridgeReceiver::new(producer, &writer.stream[..]);
+        assert_eq!(receiver.receive_all().unwrap(), 2);
+        assert_eq!(*consumer.pop().unwrap(), Command::Name("master".into()));
+        assert_eq!(*consumer.pop().unwrap(), Command::Gain(0.25));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_bridge() {
+        use crate::futures::byte_pipe;
+        use ::futures::executor::block_on;
+
+        let (mut producer, mut consumer) = Queue::new().split_async();
+        let (mut writer, mut reader) = byte_pipe(16);
+
+        for i in 0..10 {
+            producer.push(Node::new(Command::Gain(i as f32)));
+        }
+        drop(producer);
+
+        block_on(send_async(&mut consumer, &mut writer)).unwrap();
+        drop(writer);
+
+        let (mut producer, mut consumer) = Queue::<Command>::new().split_async();
+        assert_eq!(block_on(receive_async(&mut reader, &mut producer)).unwrap(), 10);
+        assert_eq!(*consumer.pop().unwrap(), Command::Gain(0.0));
+    }
+}
@@ -0,0 +1,140 @@
+//! Double-buffering over a pair of queues.
+//!
+//! A [`BufferChannel`] sends filled buffers from a producer to a consumer and
+//! returns emptied buffers to the producer for reuse. All buffers are
+//! allocated up front, so once the channel has been created, neither side
+//! allocates as long as buffers are filled within their capacity.
+//!
+//! ```rust
+//! use llq::buffer::BufferChannel;
+//!
+//! let (mut producer, mut consumer) = BufferChannel::<f32>::new(2, 64).split();
+//!
+//! let mut buffer = producer.acquire().unwrap();
+//! buffer.extend_from_slice(&[0.0, 0.5, 1.0]);
+//! producer.send_buffer(buffer);
+//!
+//! let buffer = consumer.recv_buffer().unwrap();
+//! assert_eq!(&buffer[..], &[0.0, 0.5, 1.0]);
+//! consumer.recycle(buffer);
+//!
+//! assert!(producer.acquire().unwrap().is_empty());
+//! ```
+//!
+//! [`BufferChannel`]: crate::buffer::BufferChannel
+
+use crate::{Consumer, Node, Producer, Queue};
+
+use alloc::vec::Vec;
+
+/// A channel which sends filled buffers in one direction and returns emptied
+/// buffers in the other.
+pub struct BufferChannel<T> {
+    full: (Producer<Vec<T>>, Consumer<Vec<T>>),
+    empty: (Producer<Vec<T>>, Consumer<Vec<T>>),
+}
+
+impl<T> BufferChannel<T> {
+    /// Creates a new channel with `count` empty buffers, each with room for
+    /// `capacity` elements.
+    pub fn new(count: usize, capacity: usize) -> BufferChannel<T> {
+        let full = Queue::new().split();
+        let mut empty = Queue::new().split();
+
+        for _ in 0..count {
+            empty.0.push(Node::new(Vec::with_capacity(capacity)));
+        }
+
+        BufferChannel { full, empty }
+    }
+
+    /// Splits a channel into its producer and consumer halves.
+    pub fn split(self) -> (BufferProducer<T>, BufferConsumer<T>) {
+        let (full_producer, full_consumer) = self.full;
+        let (empty_producer, empty_consumer) = self.empty;
+
+        (
+            BufferProducer { full: full_producer, empty: empty_consumer },
+            BufferConsumer { full: full_consumer, empty: empty_producer },
+        )
+    }
+}
+
+/// The producer half of a [`BufferChannel`].
+///
+/// [`BufferChannel`]: crate::buffer::BufferChannel
+pub struct BufferProducer<T> {
+    full: Producer<Vec<T>>,
+    empty: Consumer<Vec<T>>,
+}
+
+impl<T> BufferProducer<T> {
+    /// Attempts to take an empty buffer for filling. Returns `None` if all
+    /// buffers are currently in use.
+    pub fn acquire(&mut self) -> Option<Node<Vec<T>>> {
+        self.empty.pop()
+    }
+
+    /// Sends a filled buffer to the consumer.
+    pub fn send_buffer(&mut self, buffer: Node<Vec<T>>) {
+        self.full.push(buffer);
+    }
+}
+
+/// The consumer half of a [`BufferChannel`].
+///
+/// [`BufferChannel`]: crate::buffer::BufferChannel
+pub struct BufferConsumer<T> {
+    full: Consumer<Vec<T>>,
+    empty: Producer<Vec<T>>,
+}
+
+impl<T> BufferConsumer<T> {
+    /// Attempts to receive a filled buffer. Returns `None` if no buffers have
+    /// been sent.
+    pub fn recv_buffer(&mut self) -> Option<Node<Vec<T>>> {
+        self.full.pop()
+    }
+
+    /// Clears a buffer and returns it to the producer for reuse.
+    pub fn recycle(&mut self, mut buffer: Node<Vec<T>>) {
+        buffer.clear();
+        self.empty.push(buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn round_trip() {
+        let (mut producer, mut consumer) = BufferChannel::new(2, 4).split();
+
+        let thread = std::thread::spawn(move || {
+            let mut sent = 0;
+            while sent < 100 {
+                if let Some(mut buffer) = producer.acquire() {
+                    assert!(buffer.is_empty());
+                    assert!(buffer.capacity() >= 4);
+                    buffer.extend_from_slice(&[sent; 4]);
+                    producer.send_buffer(buffer);
+                    sent += 1;
+                }
+            }
+        });
+
+        let mut received = 0;
+        while received < 100 {
+            if let Some(buffer) = consumer.recv_buffer() {
+                assert_eq!(&buffer[..], &[received; 4]);
+                consumer.recycle(buffer);
+                received += 1;
+            }
+        }
+
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+use crate::pool::SharedPool;
+use crate::{Node, Overflow, Queue};
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+
+/// A builder for a [`Queue`] with a combination of optional behaviors.
+///
+/// Each option corresponds to one of the queue's constructors or setters, so
+/// that different queues in one program can be configured independently.
+/// Only behaviors chosen at runtime are covered; anything that would change
+/// the layout of [`Queue`] itself, such as cache-line padding between the
+/// producer's and consumer's fields, can't differ between queues of the same
+/// type and so has no builder option.
+///
+/// ```rust
+/// use llq::{Node, Overflow, Queue};
+///
+/// let queue = Queue::builder().capacity(2).overflow(Overflow::Reject).credits(4).build();
+/// let (mut producer, _consumer) = queue.split();
+///
+/// assert!(producer.try_push(Node::new(0)).is_ok());
+/// assert!(producer.try_push(Node::new(1)).is_ok());
+/// assert!(producer.try_push(Node::new(2)).is_err());
+/// assert_eq!(producer.lag(), Some(2));
+/// assert_eq!(producer.credits(), 4);
+/// ```
+///
+/// [`Queue`]: crate::Queue
+pub struct QueueBuilder<T> {
+    counter: bool,
+    capacity: Option<usize>,
+    overflow: Overflow,
+    discard: Option<Box<dyn FnMut(T) + Send>>,
+    #[cfg(feature = "observer")]
+    observer: Option<Box<dyn crate::observer::Observer<T>>>,
+    reclaim: Option<Arc<SharedPool<T>>>,
+    retraction: bool,
+    credits: usize,
+}
+
+impl<T> Queue<T> {
+    /// Returns a [`QueueBuilder`] for configuring a new queue.
+    ///
+    /// [`QueueBuilder`]: crate::QueueBuilder
+    pub fn builder() -> QueueBuilder<T> {
+        QueueBuilder::new()
+    }
+}
+
+impl<T> QueueBuilder<T> {
+    /// Creates a builder for a queue with no optional behaviors enabled.
+    pub fn new() -> QueueBuilder<T> {
+        QueueBuilder {
+            counter: false,
+            capacity: None,
+            overflow: Overflow::Reject,
+            discard: None,
+            #[cfg(feature = "observer")]
+            observer: None,
+            reclaim: None,
+            retraction: false,
+            credits: 0,
+        }
+    }
+
+    /// Sets whether the queue keeps count of its elements; see
+    /// [`Queue::with_counter()`].
+    ///
+    /// [`Queue::with_counter()`]: crate::Queue::with_counter
+    pub fn counter(mut self, counter: bool) -> QueueBuilder<T> {
+        self.counter = counter;
+        self
+    }
+
+    /// Sets a soft limit on the number of elements; see
+    /// [`Queue::with_capacity()`]. Implies [`counter(true)`].
+    ///
+    /// [`Queue::with_capacity()`]: crate::Queue::with_capacity
+    /// [`counter(true)`]: crate::QueueBuilder::counter
+    pub fn capacity(mut self, capacity: usize) -> QueueBuilder<T> {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Sets the policy applied when the queue is full. Only has an effect
+    /// together with [`capacity()`]. Defaults to [`Overflow::Reject`].
+    ///
+    /// [`capacity()`]: crate::QueueBuilder::capacity
+    /// [`Overflow::Reject`]: crate::Overflow::Reject
+    pub fn overflow(mut self, overflow: Overflow) -> QueueBuilder<T> {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets a handler for elements still in the queue when it is dropped;
+    /// see [`Queue::set_discard_handler()`].
+    ///
+    /// [`Queue::set_discard_handler()`]: crate::Queue::set_discard_handler
+    #[cfg(feature = "alloc-constructors")]
+    pub fn discard_handler<F>(mut self, handler: F) -> QueueBuilder<T>
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        self.discard = Some(Box::new(handler));
+        self
+    }
+
+    /// Installs an observer; see [`Queue::set_observer()`].
+    ///
+    /// [`Queue::set_observer()`]: crate::Queue::set_observer
+    #[cfg(feature = "observer")]
+    pub fn observer<O: crate::observer::Observer<T> + 'static>(
+        mut self,
+        observer: O,
+    ) -> QueueBuilder<T> {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Sets a pool which receives the queue's node allocations when it is
+    /// dropped; see [`Queue::set_reclaim_pool()`].
+    ///
+    /// [`Queue::set_reclaim_pool()`]: crate::Queue::set_reclaim_pool
+    pub fn reclaim_pool(mut self, pool: Arc<SharedPool<T>>) -> QueueBuilder<T> {
+        self.reclaim = Some(pool);
+        self
+    }
+
+    /// Sets whether the producer can retract elements; see
+    /// [`Queue::enable_retraction()`].
+    ///
+    /// [`Queue::enable_retraction()`]: crate::Queue::enable_retraction
+    pub fn retraction(mut self, retraction: bool) -> QueueBuilder<T> {
+        self.retraction = retraction;
+        self
+    }
+
+    /// Sets the initial number of credits; see [`Queue::set_credits()`].
+    ///
+    /// [`Queue::set_credits()`]: crate::Queue::set_credits
+    pub fn credits(mut self, credits: usize) -> QueueBuilder<T> {
+        self.credits = credits;
+        self
+    }
+
+    /// Creates the queue, allocating its sentinel node.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn build(self) -> Queue<T> {
+        let mut queue = Queue::new();
+        self.apply(&mut queue);
+        queue
+    }
+
+    /// Creates the queue using `node` as its sentinel, returning the value
+    /// moved out of it; see [`Queue::from_node()`].
+    ///
+    /// [`Queue::from_node()`]: crate::Queue::from_node
+    pub fn build_from_node(self, node: Node<T>) -> (Queue<T>, T) {
+        let (mut queue, value) = Queue::from_node(node);
+        self.apply(&mut queue);
+        (queue, value)
+    }
+
+    fn apply(self, queue: &mut Queue<T>) {
+        queue.counted = self.counter || self.capacity.is_some();
+        queue.capacity = self.capacity;
+        queue.overflow = self.overflow;
+        queue.discard = self.discard;
+        #[cfg(feature = "observer")]
+        {
+            queue.observer = self.observer;
+        }
+        queue.reclaim = self.reclaim;
+        queue.retractable = self.retraction;
+        *queue.credits.get_mut() = self.credits;
+    }
+}
+
+impl<T> Default for QueueBuilder<T> {
+    fn default() -> QueueBuilder<T> {
+        QueueBuilder::new()
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_from_node() {
+        let pool = Arc::new(SharedPool::new());
+        let (queue, _) = QueueBuilder::new()
+            .counter(true)
+            .retraction(true)
+            .reclaim_pool(pool.clone())
+            .build_from_node(Node::new(0));
+        let (mut producer, mut consumer) = queue.split();
+
+        producer.push(Node::new(1));
+        producer.push(Node::new(2));
+        assert_eq!(producer.lag(), Some(2));
+        assert_eq!(*producer.try_retract().unwrap(), 2);
+        assert_eq!(*consumer.pop().unwrap(), 1);
+
+        drop(producer);
+        drop(consumer);
+        assert_eq!(pool.len(), 1);
+    }
+}
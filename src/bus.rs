@@ -0,0 +1,255 @@
+//! Publishing to named topics with a queue per subscriber.
+//!
+//! A [`Bus`] is owned by the publishing thread and delivers every element
+//! published to a [`Topic`] to each of the topic's subscribers, each of which
+//! has its own queue. The publisher pushes the original node to one
+//! subscriber and clones into nodes taken from a [`SharedPool`] for the
+//! others, so once the pool has been preloaded and the subscribers recycle
+//! into it, publishing is wait-free and does not allocate.
+//!
+//! Subscriptions are managed through the matching [`BusControl`], away from
+//! the publishing thread. [`BusControl::subscribe()`] creates the new
+//! subscriber's queue and passes its producer half to the bus, which adopts
+//! it the next time it publishes. A subscriber unsubscribes by dropping its
+//! consumer; the bus then passes the producer back to the control side, where
+//! [`BusControl::collect()`] drops it.
+//!
+//! A bus carries elements of a single type, and each bus has a single
+//! publisher. The topics of a bus are fixed when it is created.
+//!
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use llq::bus::Bus;
+//! use llq::pool::SharedPool;
+//! use llq::Node;
+//!
+//! let pool = Arc::new(SharedPool::with_capacity(16));
+//! let (mut control, mut bus) = Bus::new(&["transport", "meters"], pool.clone());
+//!
+//! let transport = control.topic("transport").unwrap();
+//! let mut ui = control.subscribe(transport);
+//! let mut sequencer = control.subscribe(transport);
+//!
+//! std::thread::spawn(move || {
+//!     assert_eq!(bus.publish(transport, Node::new("play")).unwrap(), 2);
+//! })
+//! .join()
+//! .unwrap();
+//!
+//! assert_eq!(*ui.pop().unwrap(), "play");
+//! assert_eq!(pool.recycle(sequencer.pop().unwrap()), "play");
+//! ```
+//!
+//! [`Bus`]: crate::bus::Bus
+//! [`Topic`]: crate::bus::Topic
+//! [`SharedPool`]: crate::pool::SharedPool
+//! [`BusControl`]: crate::bus::BusControl
+//! [`BusControl::subscribe()`]: crate::bus::BusControl::subscribe
+//! [`BusControl::collect()`]: crate::bus::BusControl::collect
+
+use crate::error::PushError;
+use crate::pool::SharedPool;
+use crate::{Chain, Consumer, Node, Producer, Queue};
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// A topic of a [`Bus`], as returned by [`BusControl::topic()`].
+///
+/// [`Bus`]: crate::bus::Bus
+/// [`BusControl::topic()`]: crate::bus::BusControl::topic
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Topic(usize);
+
+struct Subscriber<T> {
+    topic: usize,
+    producer: Producer<T>,
+}
+
+/// The publishing side of a bus; see the [module documentation].
+///
+/// [module documentation]: crate::bus
+pub struct Bus<T> {
+    topics: Vec<Chain<Subscriber<T>>>,
+    subscriptions: Consumer<Subscriber<T>>,
+    retired: Producer<Subscriber<T>>,
+    pool: Arc<SharedPool<T>>,
+}
+
+impl<T> Bus<T> {
+    /// Creates a bus with the given topics, taking the nodes for clones from
+    /// `pool`. Returns the control side and the publishing side.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a topic name appears more than once.
+    pub fn new(topics: &[&str], pool: Arc<SharedPool<T>>) -> (BusControl<T>, Bus<T>) {
+        let names: Vec<String> = topics.iter().map(|&name| String::from(name)).collect();
+        for (i, name) in names.iter().enumerate() {
+            assert!(!names[..i].contains(name), "duplicate topic name: {}", name);
+        }
+
+        let (subscribe, subscriptions) = Queue::new().split();
+        let (retired, unsubscribed) = Queue::new().split();
+        let topics = names.iter().map(|_| Chain::new()).collect();
+
+        (
+            BusControl { names, subscribe, unsubscribed },
+            Bus { topics, subscriptions, retired, pool },
+        )
+    }
+
+    /// Publishes an element to every subscriber of `topic`, returning the
+    /// number of subscribers which received it.
+    ///
+    /// Never allocates. If the pool does not hold enough allocations for the
+    /// clones, returns the node in an error without delivering it to anyone.
+    /// An element published to a topic without subscribers is dropped, and
+    /// its node is added to the pool.
+    pub fn publish(&mut self, topic: Topic, node: Node<T>) -> Result<usize, PushError<Node<T>>>
+    where
+        T: Clone,
+    {
+        self.adopt();
+
+        let subscribers = &mut self.topics[topic.0];
+        let mut copies = Chain::new();
+        while copies.len() + 1 < subscribers.len() {
+            match self.pool.try_alloc(T::clone(&node)) {
+                Ok(copy) => copies.push_back(copy),
+                Err(_) => {
+                    while let Some(copy) = copies.pop_front() {
+                        self.pool.recycle(copy);
+                    }
+                    return Err(PushError::Full(node));
+                }
+            }
+        }
+
+        let mut node = Some(node);
+        let mut delivered = 0;
+        for _ in 0..subscribers.len() {
+            let mut subscriber = subscribers.pop_front().unwrap();
+            let value = copies.pop_front().or_else(|| node.take()).unwrap();
+
+            if subscriber.producer.consumer_dropped() {
+                self.pool.recycle(value);
+                self.retired.push(subscriber);
+            } else {
+                subscriber.producer.push(value);
+                subscribers.push_back(subscriber);
+                delivered += 1;
+            }
+        }
+
+        if let Some(node) = node {
+            self.pool.recycle(node);
+        }
+
+        Ok(delivered)
+    }
+
+    /// Returns the number of subscribers of `topic`, including new
+    /// subscriptions made through the control side and excluding
+    /// subscribers which have been found to have unsubscribed.
+    pub fn subscribers(&mut self, topic: Topic) -> usize {
+        self.adopt();
+        self.topics[topic.0].len()
+    }
+
+    /// Returns the pool from which nodes for clones are taken.
+    pub fn pool(&self) -> &Arc<SharedPool<T>> {
+        &self.pool
+    }
+
+    /// Adds the subscribers which have subscribed since the last call.
+    fn adopt(&mut self) {
+        while let Some(subscriber) = self.subscriptions.pop() {
+            self.topics[subscriber.topic].push_back(subscriber);
+        }
+    }
+}
+
+/// The control side of a bus, which manages subscriptions; see the
+/// [module documentation].
+///
+/// [module documentation]: crate::bus
+pub struct BusControl<T> {
+    names: Vec<String>,
+    subscribe: Producer<Subscriber<T>>,
+    unsubscribed: Consumer<Subscriber<T>>,
+}
+
+impl<T> BusControl<T> {
+    /// Returns the topic called `name`, or `None` if the bus has no such
+    /// topic.
+    pub fn topic(&self, name: &str) -> Option<Topic> {
+        self.names.iter().position(|topic| topic == name).map(Topic)
+    }
+
+    /// Returns the name of `topic`.
+    pub fn name(&self, topic: Topic) -> &str {
+        &self.names[topic.0]
+    }
+
+    /// Subscribes to `topic`, returning the consumer half of the new
+    /// subscriber's queue. Dropping the consumer unsubscribes.
+    ///
+    /// Allocates the queue. The subscriber receives the elements published
+    /// after the bus next checks for new subscriptions, which it does each
+    /// time it publishes. Also drops any subscribers which have
+    /// unsubscribed.
+    pub fn subscribe(&mut self, topic: Topic) -> Consumer<T> {
+        self.collect();
+
+        let (producer, consumer) = Queue::new().split();
+        self.subscribe.push(Node::new(Subscriber { topic: topic.0, producer }));
+        consumer
+    }
+
+    /// Drops the subscribers which the bus has found to have unsubscribed,
+    /// returning how many there were.
+    pub fn collect(&mut self) -> usize {
+        let mut count = 0;
+        while self.unsubscribed.pop().is_some() {
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribe_and_unsubscribe() {
+        let pool = Arc::new(SharedPool::with_capacity(2));
+        let (mut control, mut bus) = Bus::new(&["a", "b"], pool.clone());
+        let a = control.topic("a").unwrap();
+        let b = control.topic("b").unwrap();
+        assert_eq!(control.name(b), "b");
+        assert!(control.topic("c").is_none());
+
+        assert_eq!(bus.publish(a, Node::new(0)).unwrap(), 0);
+        assert_eq!(pool.len(), 3);
+
+        let mut first = control.subscribe(a);
+        let second = control.subscribe(a);
+        let mut third = control.subscribe(b);
+        assert_eq!(bus.subscribers(a), 2);
+
+        assert_eq!(bus.publish(a, Node::new(1)).unwrap(), 2);
+        assert_eq!(bus.publish(b, Node::new(2)).unwrap(), 1);
+        assert_eq!(*first.pop().unwrap(), 1);
+        assert_eq!(*third.pop().unwrap(), 2);
+
+        drop(second);
+        assert_eq!(bus.publish(a, Node::new(3)).unwrap(), 1);
+        assert_eq!(bus.subscribers(a), 1);
+        assert_eq!(control.collect(), 1);
+        assert_eq!(pool.recycle(first.pop().unwrap()), 3);
+    }
+}
@@ -0,0 +1,206 @@
+//! Elements which can be revoked after they have been pushed.
+//!
+//! An element pushed as a [`Cancellable`] with
+//! [`Producer::push_cancellable()`] returns a [`CancelToken`]. Cancelling the
+//! token before the consumer reaches the element makes
+//! [`Consumer::pop_uncancelled()`] skip it, so revoking a command which has
+//! not been processed yet, e.g. a note-on whose key has already been
+//! released, needs neither a second message nor bookkeeping on the consumer
+//! side.
+//!
+//! The token and the element share a flag, which is allocated by
+//! [`Cancellable::new()`].
+//!
+//! ```rust
+//! use llq::cancel::Cancellable;
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, mut consumer) = Queue::new().split();
+//!
+//! let _first = producer.push_cancellable(Node::new(Cancellable::new("note on 60")));
+//! let second = producer.push_cancellable(Node::new(Cancellable::new("note on 64")));
+//! producer.push_cancellable(Node::new(Cancellable::new("note on 67")));
+//!
+//! assert!(second.cancel());
+//!
+//! assert_eq!(**consumer.pop_uncancelled().unwrap(), "note on 60");
+//! assert_eq!(**consumer.pop_uncancelled().unwrap(), "note on 67");
+//! assert!(consumer.pop_uncancelled().is_none());
+//! ```
+//!
+//! [`Cancellable`]: crate::cancel::Cancellable
+//! [`Cancellable::new()`]: crate::cancel::Cancellable::new
+//! [`CancelToken`]: crate::cancel::CancelToken
+//! [`Producer::push_cancellable()`]: crate::Producer::push_cancellable
+//! [`Consumer::pop_uncancelled()`]: crate::Consumer::pop_uncancelled
+
+use crate::sync::{AtomicU8, RELAXED};
+use crate::{Consumer, Node, Producer};
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+const PENDING: u8 = 0;
+const CANCELLED: u8 = 1;
+const TAKEN: u8 = 2;
+
+/// An element which can be cancelled through a [`CancelToken`] until it is
+/// popped.
+///
+/// [`CancelToken`]: crate::cancel::CancelToken
+pub struct Cancellable<T> {
+    value: T,
+    state: Arc<AtomicU8>,
+}
+
+impl<T> Cancellable<T> {
+    /// Wraps `value` in a new, uncancelled element.
+    pub fn new(value: T) -> Cancellable<T> {
+        Cancellable { value, state: Arc::new(AtomicU8::new(PENDING)) }
+    }
+
+    /// Returns a token which can cancel this element.
+    pub fn token(&self) -> CancelToken {
+        CancelToken { state: self.state.clone() }
+    }
+
+    /// Returns `true` if the element has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(RELAXED) == CANCELLED
+    }
+
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Marks the element as taken, so that it can no longer be cancelled.
+    /// Returns `false` if it has already been cancelled.
+    fn take(&self) -> bool {
+        match self.state.compare_exchange(PENDING, TAKEN, RELAXED, RELAXED) {
+            Ok(_) => true,
+            Err(state) => state == TAKEN,
+        }
+    }
+}
+
+impl<T> Deref for Cancellable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Cancellable<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Cancellable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cancellable")
+            .field("value", &self.value)
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// A handle which cancels a [`Cancellable`] element.
+///
+/// Returned by [`Producer::push_cancellable()`] and [`Cancellable::token()`].
+///
+/// [`Cancellable`]: crate::cancel::Cancellable
+/// [`Producer::push_cancellable()`]: crate::Producer::push_cancellable
+/// [`Cancellable::token()`]: crate::cancel::Cancellable::token
+#[derive(Clone)]
+pub struct CancelToken {
+    state: Arc<AtomicU8>,
+}
+
+impl CancelToken {
+    /// Cancels the element. Returns `true` if it will not be returned by
+    /// [`Consumer::pop_uncancelled()`], and `false` if it has already been
+    /// popped.
+    ///
+    /// [`Consumer::pop_uncancelled()`]: crate::Consumer::pop_uncancelled
+    pub fn cancel(&self) -> bool {
+        match self.state.compare_exchange(PENDING, CANCELLED, RELAXED, RELAXED) {
+            Ok(_) => true,
+            Err(state) => state == CANCELLED,
+        }
+    }
+
+    /// Returns `true` if the element has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(RELAXED) == CANCELLED
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelToken").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+impl<T> Producer<Cancellable<T>> {
+    /// Adds an element to the queue, returning a token which can cancel it.
+    pub fn push_cancellable(&mut self, node: Node<Cancellable<T>>) -> CancelToken {
+        let token = node.token();
+        self.push(node);
+        token
+    }
+}
+
+impl<T> Consumer<Cancellable<T>> {
+    /// Removes and returns the next element which has not been cancelled.
+    /// Returns `None` if the queue holds no such element.
+    ///
+    /// Once returned, an element can no longer be cancelled. Cancelled
+    /// elements are dropped, and their nodes are added to the queue's reclaim
+    /// pool, if it has one, or deallocated otherwise.
+    pub fn pop_uncancelled(&mut self) -> Option<Node<Cancellable<T>>> {
+        loop {
+            let node = self.pop()?;
+            if node.take() {
+                return Some(node);
+            }
+
+            self.discard(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::SharedPool;
+    use crate::Queue;
+
+    #[test]
+    fn cancel() {
+        let pool = Arc::new(SharedPool::new());
+        let mut queue = Queue::new();
+        queue.set_reclaim_pool(pool.clone());
+        let (mut producer, mut consumer) = queue.split();
+
+        let tokens: alloc::vec::Vec<_> =
+            (0..4).map(|i| producer.push_cancellable(Node::new(Cancellable::new(i)))).collect();
+
+        assert!(tokens[0].cancel());
+        assert!(tokens[2].cancel());
+        assert!(tokens[2].cancel());
+
+        let node = consumer.pop_uncancelled().unwrap();
+        assert_eq!(**node, 1);
+        assert!(!tokens[1].cancel());
+        assert!(!node.is_cancelled());
+        assert_eq!(pool.len(), 1);
+
+        assert_eq!(Node::into_inner(consumer.pop_uncancelled().unwrap()).into_inner(), 3);
+        assert_eq!(pool.len(), 2);
+        assert!(consumer.pop_uncancelled().is_none());
+    }
+}
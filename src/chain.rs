@@ -0,0 +1,510 @@
+use crate::sync::{RELAXED, RELEASE};
+use crate::{Consumer, Node, NodeInner, Producer, Queue};
+
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::ptr::NonNull;
+
+/// An owned sequence of [`Node`]s, linked together through the nodes
+/// themselves.
+///
+/// Building or consuming a `Chain` never allocates. A chain can be pushed onto
+/// a queue all at once with [`Producer::push_chain()`], in which case the
+/// consumer observes either none or all of its nodes.
+///
+/// [`Node`]: crate::Node
+/// [`Producer::push_chain()`]: crate::Producer::push_chain
+pub struct Chain<T> {
+    head: *mut NodeInner<T>,
+    tail: *mut NodeInner<T>,
+    len: usize,
+    phantom: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Chain<T> {}
+unsafe impl<T: Sync> Sync for Chain<T> {}
+
+impl<T> Unpin for Chain<T> {}
+
+impl<T> Chain<T> {
+    /// Creates a new, empty chain.
+    pub fn new() -> Chain<T> {
+        Chain { head: ptr::null_mut(), tail: ptr::null_mut(), len: 0, phantom: PhantomData }
+    }
+
+    /// Returns the number of nodes in the chain.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the chain contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends a node to the back of the chain.
+    pub fn push_back(&mut self, node: Node<T>) {
+        unsafe {
+            let node_ptr = node.inner.as_ptr();
+            mem::forget(node);
+
+            (*node_ptr).next.store(ptr::null_mut(), RELAXED);
+
+            if self.tail.is_null() {
+                self.head = node_ptr;
+            } else {
+                (*self.tail).next.store(node_ptr, RELAXED);
+            }
+            self.tail = node_ptr;
+            self.len += 1;
+        }
+    }
+
+    /// Removes and returns the node at the front of the chain. Returns `None`
+    /// if the chain is empty.
+    pub fn pop_front(&mut self) -> Option<Node<T>> {
+        unsafe {
+            if self.head.is_null() {
+                return None;
+            }
+
+            let head = self.head;
+            self.head = (*head).next.load(RELAXED);
+            (*head).next.store(ptr::null_mut(), RELAXED);
+            if self.head.is_null() {
+                self.tail = ptr::null_mut();
+            }
+            self.len -= 1;
+
+            Some(Node { inner: NonNull::new_unchecked(head), phantom: PhantomData })
+        }
+    }
+
+    /// Returns an iterator over references to the values in the chain.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { current: self.head, phantom: PhantomData }
+    }
+
+    /// Detaches the nodes of the chain, returning its head and tail pointers.
+    /// Both are null if the chain is empty.
+    pub(crate) fn into_raw(self) -> (*mut NodeInner<T>, *mut NodeInner<T>) {
+        let raw = (self.head, self.tail);
+        mem::forget(self);
+        raw
+    }
+}
+
+impl<T> Default for Chain<T> {
+    fn default() -> Chain<T> {
+        Chain::new()
+    }
+}
+
+impl<T> Drop for Chain<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T> Extend<Node<T>> for Chain<T> {
+    fn extend<I: IntoIterator<Item = Node<T>>>(&mut self, iter: I) {
+        for node in iter {
+            self.push_back(node);
+        }
+    }
+}
+
+impl<T> IntoIterator for Chain<T> {
+    type Item = Node<T>;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { chain: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Chain<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// An owning iterator over the nodes of a [`Chain`].
+///
+/// [`Chain`]: crate::Chain
+pub struct IntoIter<T> {
+    chain: Chain<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Node<T>> {
+        self.chain.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.chain.len, Some(self.chain.len))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+/// An iterator over references to the values in a [`Chain`].
+///
+/// [`Chain`]: crate::Chain
+pub struct Iter<'a, T> {
+    current: *mut NodeInner<T>,
+    phantom: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        unsafe {
+            if self.current.is_null() {
+                return None;
+            }
+
+            let value = &*(*self.current).data.as_ptr();
+            self.current = (*self.current).next.load(RELAXED);
+            Some(value)
+        }
+    }
+}
+
+/// An owned last-in, first-out stack of [`Node`]s, linked together through
+/// the nodes themselves.
+///
+/// Like a [`Chain`], a `FreeList` never allocates, so it can hold spare nodes
+/// between uses without a `Vec` which would have to grow. Since the most
+/// recently pushed node is popped first, a node taken from the list is likely
+/// to still be in cache.
+///
+/// ```rust
+/// use llq::{FreeList, Node};
+///
+/// let mut spare = FreeList::new();
+/// spare.push(Node::new([0u8; 64]));
+/// spare.push(Node::new([1u8; 64]));
+///
+/// let mut node = spare.pop().unwrap();
+/// assert_eq!(node[0], 1);
+/// node[0] = 2;
+/// spare.push(node);
+/// assert_eq!(spare.len(), 2);
+/// ```
+///
+/// [`Node`]: crate::Node
+/// [`Chain`]: crate::Chain
+pub struct FreeList<T> {
+    head: *mut NodeInner<T>,
+    len: usize,
+    phantom: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for FreeList<T> {}
+unsafe impl<T: Sync> Sync for FreeList<T> {}
+
+impl<T> Unpin for FreeList<T> {}
+
+impl<T> FreeList<T> {
+    /// Creates a new, empty list.
+    pub fn new() -> FreeList<T> {
+        FreeList { head: ptr::null_mut(), len: 0, phantom: PhantomData }
+    }
+
+    /// Returns the number of nodes in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the list contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a node to the top of the list.
+    pub fn push(&mut self, node: Node<T>) {
+        unsafe {
+            let node_ptr = node.inner.as_ptr();
+            mem::forget(node);
+
+            (*node_ptr).next.store(self.head, RELAXED);
+            self.head = node_ptr;
+            self.len += 1;
+        }
+    }
+
+    /// Removes and returns the node at the top of the list. Returns `None` if
+    /// the list is empty.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        unsafe {
+            if self.head.is_null() {
+                return None;
+            }
+
+            let head = self.head;
+            self.head = (*head).next.load(RELAXED);
+            (*head).next.store(ptr::null_mut(), RELAXED);
+            self.len -= 1;
+
+            Some(Node { inner: NonNull::new_unchecked(head), phantom: PhantomData })
+        }
+    }
+
+    /// Returns a reference to the value in the node at the top of the list.
+    pub fn peek(&self) -> Option<&T> {
+        unsafe { self.head.as_ref().map(|head| &*head.data.as_ptr()) }
+    }
+
+    /// Returns an iterator over references to the values in the list, from
+    /// the top down.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { current: self.head, phantom: PhantomData }
+    }
+}
+
+impl<T> Default for FreeList<T> {
+    fn default() -> FreeList<T> {
+        FreeList::new()
+    }
+}
+
+impl<T> Drop for FreeList<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> Extend<Node<T>> for FreeList<T> {
+    fn extend<I: IntoIterator<Item = Node<T>>>(&mut self, iter: I) {
+        for node in iter {
+            self.push(node);
+        }
+    }
+}
+
+impl<T> Queue<T> {
+    /// Removes all elements from the queue and returns them as a [`Chain`],
+    /// so that they can be moved to another queue rather than dropped.
+    ///
+    /// [`Chain`]: crate::Chain
+    pub fn into_chain(self) -> Chain<T> {
+        let mut chain = Chain::new();
+        chain.extend(self);
+        chain
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Recovers every unconsumed element as a [`Chain`] if the producer half
+    /// has been dropped. Returns `None` otherwise.
+    ///
+    /// The chain starts with any nodes which the consumer evicted under the
+    /// [`Overflow::DropOldest`] policy and which have not been collected with
+    /// [`take_evicted()`], followed by the elements still
+    /// in the queue, so it can be pushed onto a replacement queue with
+    /// [`Producer::push_chain()`] without losing in-flight elements.
+    ///
+    /// [`Chain`]: crate::Chain
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    /// [`take_evicted()`]: crate::Consumer::take_evicted
+    /// [`Producer::push_chain()`]: crate::Producer::push_chain
+    pub fn into_chain(mut self) -> Option<Chain<T>> {
+        let mut chain = self.take_evicted();
+        chain.extend(self.into_queue()?.into_chain());
+        Some(chain)
+    }
+
+    /// Returns an iterator over every unconsumed element once the producer
+    /// has finished, or returns the consumer unchanged in an error if the
+    /// producer half still exists.
+    ///
+    /// As with [`into_chain()`], the iterator starts with any uncollected
+    /// evicted nodes. This gives code which uses a queue as a staging buffer
+    /// a definite end to its input:
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let (mut producer, consumer) = Queue::new().split();
+    /// let worker = std::thread::spawn(move || {
+    ///     for i in 0..4 {
+    ///         producer.push(Node::new(i));
+    ///     }
+    ///     producer.finish()
+    /// });
+    ///
+    /// assert_eq!(worker.join().unwrap(), 4);
+    /// let remaining = consumer.into_remaining().ok().unwrap();
+    /// assert_eq!(remaining.map(Node::into_inner).collect::<Vec<_>>(), [0, 1, 2, 3]);
+    /// ```
+    ///
+    /// [`into_chain()`]: crate::Consumer::into_chain
+    pub fn into_remaining(self) -> Result<IntoIter<T>, Consumer<T>> {
+        if !self.producer_dropped() {
+            return Err(self);
+        }
+
+        Ok(self.into_chain().unwrap_or_default().into_iter())
+    }
+
+    /// Removes all elements currently in the queue and returns them as a
+    /// [`Chain`]. The chain is empty if the queue is empty.
+    ///
+    /// [`Chain`]: crate::Chain
+    pub fn pop_chain(&mut self) -> Chain<T> {
+        let mut chain = Chain::new();
+        while let Some(node) = self.pop() {
+            chain.push_back(node);
+        }
+        chain
+    }
+}
+
+impl<T> Producer<T> {
+    /// Adds all nodes in a [`Chain`] to the queue with a single atomic store.
+    ///
+    /// The consumer will observe either none or all of the chain's elements.
+    ///
+    /// [`Chain`]: crate::Chain
+    pub fn push_chain(&mut self, chain: Chain<T>) {
+        let len = chain.len();
+        let (head, tail) = chain.into_raw();
+        if head.is_null() {
+            return;
+        }
+
+        if self.queue.is_counted() {
+            self.queue.len.fetch_add(len, RELAXED);
+        }
+
+        #[cfg(feature = "timestamps")]
+        unsafe {
+            let now = std::time::Instant::now();
+            let mut current = head;
+            while !current.is_null() {
+                (*current).pushed = Some(now);
+                current = (*current).next.load(RELAXED);
+            }
+        }
+
+        #[cfg(feature = "observer")]
+        if let Some(observer) = &self.queue.observer {
+            let mut current = head;
+            while !current.is_null() {
+                unsafe {
+                    observer.on_push(&*(*current).data.as_ptr());
+                    current = (*current).next.load(RELAXED);
+                }
+            }
+        }
+
+        unsafe {
+            // Nodes pushed as part of a chain cannot be retracted.
+            if self.queue.retractable {
+                self.queue.retract.store(ptr::null_mut(), RELEASE);
+            }
+
+            let old_tail = &*self.tail;
+            old_tail.next.store(head, RELEASE);
+
+            self.prev = ptr::null_mut();
+            self.tail = tail;
+            self.pushed = self.pushed.wrapping_add(len);
+            self.total_pushed = self.total_pushed.wrapping_add(len as u64);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    use alloc::vec::Vec;
+
+    #[test]
+    fn chain() {
+        let mut chain = Chain::new();
+        chain.extend((0..4).map(Node::new));
+        assert_eq!(chain.len(), 4);
+        assert_eq!(chain.iter().copied().collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+        assert_eq!(*chain.pop_front().unwrap(), 0);
+        chain.push_back(Node::new(4));
+
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push(Node::new(-1));
+        producer.push_chain(chain);
+        producer.push_chain(Chain::new());
+        producer.push(Node::new(5));
+
+        let chain = consumer.pop_chain();
+        assert_eq!(chain.len(), 6);
+        assert_eq!(chain.into_iter().map(|node| *node).collect::<Vec<_>>(), [-1, 1, 2, 3, 4, 5]);
+        assert!(consumer.pop_chain().is_empty());
+    }
+
+    #[test]
+    fn free_list() {
+        let mut list = FreeList::new();
+        assert!(list.pop().is_none());
+        list.extend((0..3).map(Node::new));
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.peek(), Some(&2));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [2, 1, 0]);
+
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push(list.pop().unwrap());
+        let node = consumer.pop().unwrap();
+        assert_eq!(*node, 2);
+        list.push(node);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [2, 1, 0]);
+    }
+
+    #[test]
+    fn into_chain() {
+        let (_producer, consumer) = Queue::<i32>::new().split();
+        assert!(consumer.into_chain().is_none());
+
+        let (mut old_producer, old_consumer) = Queue::new().split();
+        old_producer.push(Node::new(0));
+        old_producer.push(Node::new(1));
+        drop(old_producer);
+
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push_chain(old_consumer.into_chain().unwrap());
+        producer.push(Node::new(2));
+        assert_eq!(
+            consumer.pop_chain().into_iter().map(|node| *node).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+
+        producer.push(Node::new(3));
+        drop(consumer);
+        let chain = producer.into_queue().unwrap().into_chain();
+        assert_eq!(chain.iter().copied().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn into_remaining() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push(Node::new(0));
+        producer.push(Node::new(1));
+        assert_eq!(*consumer.pop().unwrap(), 0);
+
+        let consumer = consumer.into_remaining().err().unwrap();
+        producer.push(Node::new(2));
+        assert_eq!(producer.finish(), 3);
+
+        let remaining = consumer.into_remaining().ok().unwrap();
+        assert_eq!(remaining.map(Node::into_inner).collect::<Vec<_>>(), [1, 2]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Routing of failed elements to a dead-letter queue.
+//!
+//! [`DeadLetterConsumer`] wraps a [`Consumer`] together with the [`Producer`]
+//! of a second queue, the dead-letter queue. An element which the consumer
+//! fails to process can be handed to [`reject()`], which pushes its node,
+//! payload intact, onto the dead-letter queue, where another thread can log,
+//! retry or persist it. Failures are therefore observable rather than
+//! silently dropped.
+//!
+//! ```rust
+//! use llq::dead_letter::DeadLetterConsumer;
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, consumer) = Queue::new().split();
+//! let (dead_producer, mut dead_consumer) = Queue::new().split();
+//! let mut consumer = DeadLetterConsumer::new(consumer, dead_producer);
+//!
+//! producer.push(Node::new("not a number"));
+//! let node = consumer.pop().unwrap();
+//! if node.parse::<u32>().is_err() {
+//!     consumer.reject(node);
+//! }
+//!
+//! assert_eq!(consumer.rejected(), 1);
+//! assert_eq!(*dead_consumer.pop().unwrap(), "not a number");
+//! ```
+//!
+//! [`DeadLetterConsumer`]: crate::dead_letter::DeadLetterConsumer
+//! [`Consumer`]: crate::Consumer
+//! [`Producer`]: crate::Producer
+//! [`reject()`]: crate::dead_letter::DeadLetterConsumer::reject
+
+use crate::{Consumer, Node, Producer};
+
+/// A [`Consumer`] which routes rejected elements to a dead-letter queue.
+///
+/// [`Consumer`]: crate::Consumer
+pub struct DeadLetterConsumer<T> {
+    consumer: Consumer<T>,
+    dead_letters: Producer<T>,
+    rejected: usize,
+}
+
+impl<T> DeadLetterConsumer<T> {
+    /// Creates a new consumer which routes rejected elements to the queue
+    /// fed by `dead_letters`.
+    pub fn new(consumer: Consumer<T>, dead_letters: Producer<T>) -> DeadLetterConsumer<T> {
+        DeadLetterConsumer { consumer, dead_letters, rejected: 0 }
+    }
+
+    /// Attempts to remove and return an element from the queue. Returns `None`
+    /// if the queue is empty.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        self.consumer.pop()
+    }
+
+    /// Routes an element which could not be processed to the dead-letter
+    /// queue.
+    pub fn reject(&mut self, node: Node<T>) {
+        self.dead_letters.push(node);
+        self.rejected += 1;
+    }
+
+    /// Returns the number of elements rejected so far.
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    /// Returns a reference to the inner consumer.
+    pub fn consumer(&self) -> &Consumer<T> {
+        &self.consumer
+    }
+
+    /// Returns a mutable reference to the inner consumer.
+    pub fn consumer_mut(&mut self) -> &mut Consumer<T> {
+        &mut self.consumer
+    }
+
+    /// Returns the inner consumer and the dead-letter queue's producer.
+    pub fn into_inner(self) -> (Consumer<T>, Producer<T>) {
+        (self.consumer, self.dead_letters)
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn routes_rejected() {
+        let (mut producer, consumer) = Queue::new().split();
+        let (dead_producer, mut dead_consumer) = Queue::new().split();
+        let mut consumer = DeadLetterConsumer::new(consumer, dead_producer);
+
+        for i in 0..4 {
+            producer.push(Node::new(i));
+        }
+        while let Some(node) = consumer.pop() {
+            if *node % 2 == 1 {
+                consumer.reject(node);
+            }
+        }
+
+        assert_eq!(consumer.rejected(), 2);
+        let (_, dead_producer) = consumer.into_inner();
+        drop(dead_producer);
+        assert_eq!(*dead_consumer.pop().unwrap(), 1);
+        assert_eq!(*dead_consumer.pop().unwrap(), 3);
+        assert!(dead_consumer.pop().is_none() && dead_consumer.is_closed());
+    }
+}
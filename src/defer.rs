@@ -0,0 +1,122 @@
+//! Dropping values on a background thread.
+//!
+//! Dropping a queue frees every node still in it, which can take a long time
+//! for a queue with many pending elements. [`Queue::drop_deferred()`], the
+//! [`drop_deferred()`] function and the [`DeferredDrop`] wrapper instead hand
+//! the value over to a shared reaper thread, which is started on first use and
+//! drops values in the background.
+//!
+//! Handing a value over allocates a small box and sends it over a channel, so
+//! it is cheap but not wait-free.
+//!
+//! [`Queue::drop_deferred()`]: crate::Queue::drop_deferred
+//! [`drop_deferred()`]: crate::defer::drop_deferred
+//! [`DeferredDrop`]: crate::defer::DeferredDrop
+
+use crate::Queue;
+
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use std::boxed::Box;
+use std::sync::mpsc::{self, Sender};
+use std::sync::OnceLock;
+use std::thread;
+
+type Garbage = Box<dyn Send>;
+
+fn reaper() -> &'static Sender<Garbage> {
+    static REAPER: OnceLock<Sender<Garbage>> = OnceLock::new();
+    REAPER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Garbage>();
+        thread::Builder::new()
+            .name("llq-reaper".into())
+            .spawn(move || {
+                for garbage in receiver {
+                    drop(garbage);
+                }
+            })
+            .expect("failed to spawn reaper thread");
+        sender
+    })
+}
+
+/// Drops `value` on the reaper thread.
+pub fn drop_deferred<T: Send + 'static>(value: T) {
+    // The reaper thread never exits, so sending cannot fail.
+    let _ = reaper().send(Box::new(value));
+}
+
+impl<T: Send + 'static> Queue<T> {
+    /// Drops the queue and any elements remaining in it on the reaper thread.
+    pub fn drop_deferred(self) {
+        drop_deferred(self);
+    }
+}
+
+/// A wrapper which drops its value on the reaper thread.
+pub struct DeferredDrop<T: Send + 'static> {
+    value: ManuallyDrop<T>,
+}
+
+impl<T: Send + 'static> DeferredDrop<T> {
+    /// Wraps a value so that it is dropped on the reaper thread.
+    pub fn new(value: T) -> DeferredDrop<T> {
+        DeferredDrop { value: ManuallyDrop::new(value) }
+    }
+
+    /// Returns the inner value, which will then be dropped normally.
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::take(&mut this.value) }
+    }
+}
+
+impl<T: Send + 'static> Deref for DeferredDrop<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Send + 'static> DerefMut for DeferredDrop<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Send + 'static> Drop for DeferredDrop<T> {
+    fn drop(&mut self) {
+        drop_deferred(unsafe { ManuallyDrop::take(&mut self.value) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    use core::time::Duration;
+    use std::sync::Arc;
+
+    #[test]
+    fn reaper_drops() {
+        let counter = Arc::new(());
+
+        let (mut producer, consumer) = Queue::new().split();
+        for _ in 0..1000 {
+            producer.push(Node::new(counter.clone()));
+        }
+        drop(producer);
+        consumer.into_queue().unwrap().drop_deferred();
+        drop(DeferredDrop::new(counter.clone()));
+
+        for _ in 0..1000 {
+            if Arc::strong_count(&counter) == 1 {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("values were not dropped");
+    }
+}
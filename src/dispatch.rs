@@ -0,0 +1,157 @@
+//! Distribution of work from one producer across several queues.
+//!
+//! A [`Dispatcher`] owns the producer halves of a set of queues, typically
+//! one per worker thread, and routes each pushed element to one of them
+//! according to a [`Policy`]. Every queue keeps a single producer and a
+//! single consumer, so each push and pop remains wait-free, while the
+//! workers together share the load as they would with a multi-consumer
+//! queue.
+//!
+//! ```rust
+//! use llq::dispatch::{Dispatcher, Policy};
+//! use llq::{Node, Queue};
+//!
+//! let (producers, mut consumers): (Vec<_>, Vec<_>) =
+//!     (0..3).map(|_| Queue::with_counter().split()).unzip();
+//! let mut dispatcher = Dispatcher::new(producers, Policy::LeastLoaded);
+//!
+//! for job in 0..6 {
+//!     dispatcher.push(Node::new(job));
+//!     // The second worker keeps up with its queue.
+//!     consumers[1].pop();
+//! }
+//!
+//! let lens: Vec<_> = dispatcher.producers().iter().map(|producer| producer.lag()).collect();
+//! assert_eq!(lens, [Some(1), Some(0), Some(1)]);
+//! ```
+//!
+//! [`Dispatcher`]: crate::dispatch::Dispatcher
+//! [`Policy`]: crate::dispatch::Policy
+
+use crate::{Node, Producer};
+
+use alloc::vec::Vec;
+
+/// A rule for choosing the queue which receives an element.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Each queue receives an element in turn.
+    RoundRobin,
+    /// The queue holding the fewest elements receives the element, with ties
+    /// broken in turn.
+    ///
+    /// The lengths are read from the queues' counters, so the queues should
+    /// be created with [`Queue::with_counter()`] or
+    /// [`Queue::with_capacity()`]. A queue which does not keep count is
+    /// treated as empty.
+    ///
+    /// [`Queue::with_counter()`]: crate::Queue::with_counter
+    /// [`Queue::with_capacity()`]: crate::Queue::with_capacity
+    LeastLoaded,
+}
+
+/// A set of producers which routes each element to one of them.
+pub struct Dispatcher<T> {
+    producers: Vec<Producer<T>>,
+    policy: Policy,
+    next: usize,
+}
+
+impl<T> Dispatcher<T> {
+    /// Creates a dispatcher which routes elements to `producers` according
+    /// to `policy`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `producers` is empty.
+    pub fn new(producers: Vec<Producer<T>>, policy: Policy) -> Dispatcher<T> {
+        assert!(!producers.is_empty(), "a dispatcher needs at least one producer");
+        Dispatcher { producers, policy, next: 0 }
+    }
+
+    /// Adds an element to one of the queues, returning the index of the
+    /// queue which received it.
+    pub fn push(&mut self, node: Node<T>) -> usize {
+        let index = self.choose();
+        self.producers[index].push(node);
+        index
+    }
+
+    /// Returns the index of the queue which will receive the next element.
+    fn choose(&mut self) -> usize {
+        let count = self.producers.len();
+        let index = match self.policy {
+            Policy::RoundRobin => self.next,
+            Policy::LeastLoaded => {
+                let mut best = self.next;
+                let mut best_len = self.producers[best].lag().unwrap_or(0);
+                for offset in 1..count {
+                    let index = (self.next + offset) % count;
+                    let len = self.producers[index].lag().unwrap_or(0);
+                    if len < best_len {
+                        best = index;
+                        best_len = len;
+                    }
+                }
+                best
+            }
+        };
+
+        self.next = (index + 1) % count;
+        index
+    }
+
+    /// Returns the policy used to route elements.
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Sets the policy used to route elements.
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    /// Returns the producers, in the order in which they were given.
+    pub fn producers(&self) -> &[Producer<T>] {
+        &self.producers
+    }
+
+    /// Returns the producers mutably, so that elements can be pushed to a
+    /// particular queue.
+    pub fn producers_mut(&mut self) -> &mut [Producer<T>] {
+        &mut self.producers
+    }
+
+    /// Returns the producers.
+    pub fn into_inner(self) -> Vec<Producer<T>> {
+        self.producers
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn policies() {
+        let (producers, mut consumers): (Vec<_>, Vec<_>) =
+            (0..3).map(|_| Queue::with_counter().split()).unzip();
+        let mut dispatcher = Dispatcher::new(producers, Policy::RoundRobin);
+
+        let indices: Vec<_> = (0..4).map(|i| dispatcher.push(Node::new(i))).collect();
+        assert_eq!(indices, [0, 1, 2, 0]);
+
+        // Queue 0 holds two elements and queue 1 holds one.
+        consumers[2].pop();
+        dispatcher.set_policy(Policy::LeastLoaded);
+        assert_eq!(dispatcher.push(Node::new(4)), 2);
+        assert_eq!(dispatcher.push(Node::new(5)), 1);
+        assert_eq!(dispatcher.push(Node::new(6)), 2);
+
+        let lens: Vec<_> =
+            dispatcher.producers().iter().map(|producer| producer.lag().unwrap()).collect();
+        assert_eq!(lens, [2, 2, 2]);
+        assert_eq!(*consumers[1].pop().unwrap(), 1);
+    }
+}
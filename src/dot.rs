@@ -0,0 +1,112 @@
+use crate::sync::ACQUIRE;
+use crate::{is_end, Consumer, NodeInner, Queue};
+
+use core::ptr;
+use std::io::{self, Write};
+
+/// Rendering of a queue's nodes as a Graphviz DOT graph.
+///
+/// [`Queue::dump_dot()`] and [`Consumer::dump_dot()`] write a graph with a
+/// vertex for each node, labeled with its position and address, and an edge
+/// for each link, starting from the sentinel at the head of the queue. This
+/// shows exactly which allocations a queue holds at a given moment, e.g.
+/// when chasing a node which is reused while still linked.
+///
+/// ```rust
+/// use llq::{Node, Queue};
+///
+/// let (mut producer, consumer) = Queue::new().split();
+/// producer.push(Node::new(1));
+///
+/// let mut dot = Vec::new();
+/// consumer.dump_dot(&mut dot).unwrap();
+/// let dot = String::from_utf8(dot).unwrap();
+/// assert!(dot.starts_with("digraph llq {"));
+/// assert!(dot.contains("n0 -> n1;"));
+/// ```
+///
+/// [`Queue::dump_dot()`]: crate::Queue::dump_dot
+/// [`Consumer::dump_dot()`]: crate::Consumer::dump_dot
+impl<T> Queue<T> {
+    /// Writes the queue's nodes as a DOT graph, from the sentinel to the last
+    /// node.
+    pub fn dump_dot<W: Write>(&self, w: W) -> io::Result<()> {
+        // Without a producer, the links can be followed directly.
+        write_dot(w, self.head.get(), |node| unsafe {
+            let next = (*node).next.load(ACQUIRE);
+            if is_end(next) {
+                ptr::null_mut()
+            } else {
+                next
+            }
+        })
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Writes the nodes currently visible to the consumer as a DOT graph,
+    /// from the sentinel to the last node.
+    ///
+    /// Nodes pushed while the graph is being written may or may not be
+    /// included.
+    pub fn dump_dot<W: Write>(&self, w: W) -> io::Result<()> {
+        write_dot(w, self.queue.head.get(), |node| unsafe { self.queue.load_next(node) })
+    }
+}
+
+fn write_dot<T, W, F>(mut w: W, head: *mut NodeInner<T>, mut next: F) -> io::Result<()>
+where
+    W: Write,
+    F: FnMut(*mut NodeInner<T>) -> *mut NodeInner<T>,
+{
+    writeln!(w, "digraph llq {{")?;
+    writeln!(w, "    rankdir=LR;")?;
+    writeln!(w, "    node [shape=box];")?;
+    writeln!(w, "    head [shape=plaintext];")?;
+    writeln!(w, "    n0 [label=\"sentinel\\n{:p}\"];", head)?;
+    writeln!(w, "    head -> n0;")?;
+
+    let mut index = 0;
+    let mut current = next(head);
+    while !current.is_null() {
+        index += 1;
+        writeln!(w, "    n{} [label=\"{}\\n{:p}\"];", index, index, current)?;
+        writeln!(w, "    n{} -> n{};", index - 1, index)?;
+        current = next(current);
+    }
+
+    writeln!(w, "    tail [shape=plaintext];")?;
+    writeln!(w, "    n{} -> tail [dir=back];", index)?;
+    writeln!(w, "}}")
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn dot<F: FnOnce(&mut Vec<u8>) -> io::Result<()>>(f: F) -> String {
+        let mut out = Vec::new();
+        f(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn chain() {
+        let queue = Queue::with_initial((0..2).map(Node::new));
+        let graph = dot(|out| queue.dump_dot(out));
+        assert!(graph.contains("head -> n0;"));
+        assert!(graph.contains("n1 -> n2;"));
+        assert!(graph.contains("n2 -> tail"));
+        assert!(!graph.contains("n3"));
+
+        let (_producer, mut consumer) = queue.split();
+        consumer.pop();
+        let graph = dot(|out| consumer.dump_dot(out));
+        assert!(graph.contains("n0 -> n1;"));
+        assert!(!graph.contains("n2"));
+    }
+}
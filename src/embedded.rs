@@ -0,0 +1,284 @@
+#[cfg(feature = "embedded-io-async")]
+use crate::futures::{AsyncRingConsumer, AsyncRingProducer};
+use crate::ring::{RingConsumer, RingProducer};
+
+#[cfg(feature = "embedded-io-async")]
+use core::future;
+use core::hint;
+#[cfg(feature = "embedded-io-async")]
+use core::task::Poll;
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write, WriteReady};
+
+/// Blocking byte I/O over a ring buffer of bytes with [`embedded_io`].
+///
+/// A `RingProducer<u8>` implements [`Write`] and a `RingConsumer<u8>`
+/// implements [`Read`], e.g. so that a UART or USB interrupt handler can feed
+/// received bytes to application code written against `embedded-io`. Both
+/// also implement the readiness traits, which never block, so that an
+/// interrupt handler can check for room or data before calling into them.
+///
+/// A blocking call spins until it can make progress. Reading returns `Ok(0)`,
+/// i.e. end of file, once the producer has been dropped and the buffer is
+/// empty, and writing fails with [`ErrorKind::BrokenPipe`] once the consumer
+/// has been dropped.
+///
+/// ```rust
+/// use embedded_io::{Read, Write};
+/// use llq::ring::Ring;
+///
+/// let (mut tx, mut rx) = Ring::<u8>::with_capacity(64).split();
+///
+/// tx.write_all(b"AT+OK\r\n").unwrap();
+/// drop(tx);
+///
+/// let mut line = [0; 16];
+/// let len = rx.read(&mut line).unwrap();
+/// assert_eq!(&line[..len], b"AT+OK\r\n");
+/// assert_eq!(rx.read(&mut line).unwrap(), 0);
+/// ```
+///
+/// [`embedded_io`]: https://docs.rs/embedded-io/0.6
+/// [`Write`]: https://docs.rs/embedded-io/0.6/embedded_io/trait.Write.html
+/// [`Read`]: https://docs.rs/embedded-io/0.6/embedded_io/trait.Read.html
+/// [`ErrorKind::BrokenPipe`]: https://docs.rs/embedded-io/0.6/embedded_io/enum.ErrorKind.html#variant.BrokenPipe
+impl ErrorType for RingProducer<u8> {
+    type Error = ErrorKind;
+}
+
+impl Write for RingProducer<u8> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, ErrorKind> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.consumer_dropped() {
+                return Err(ErrorKind::BrokenPipe);
+            }
+
+            let written = buf.iter().take_while(|&&byte| self.push(byte).is_ok()).count();
+            if written > 0 {
+                return Ok(written);
+            }
+
+            hint::spin_loop();
+        }
+    }
+
+    /// Blocks until the consumer has read every byte written so far.
+    fn flush(&mut self) -> Result<(), ErrorKind> {
+        while !self.is_drained() {
+            if self.consumer_dropped() {
+                return Err(ErrorKind::BrokenPipe);
+            }
+
+            hint::spin_loop();
+        }
+
+        Ok(())
+    }
+}
+
+impl WriteReady for RingProducer<u8> {
+    fn write_ready(&mut self) -> Result<bool, ErrorKind> {
+        Ok(!self.is_full() || self.consumer_dropped())
+    }
+}
+
+impl ErrorType for RingConsumer<u8> {
+    type Error = ErrorKind;
+}
+
+impl Read for RingConsumer<u8> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            // Check for the producer first, so that bytes pushed just before
+            // it was dropped are still read.
+            let closed = self.producer_dropped();
+
+            let mut read = 0;
+            while read < buf.len() {
+                match self.pop() {
+                    Some(byte) => buf[read] = byte,
+                    None => break,
+                }
+                read += 1;
+            }
+
+            if read > 0 || closed {
+                return Ok(read);
+            }
+
+            hint::spin_loop();
+        }
+    }
+}
+
+impl ReadReady for RingConsumer<u8> {
+    fn read_ready(&mut self) -> Result<bool, ErrorKind> {
+        Ok(!self.is_empty() || self.producer_dropped())
+    }
+}
+
+/// Async byte I/O over a ring buffer of bytes with [`embedded_io_async`].
+///
+/// An `AsyncRingProducer<u8>` implements [`Write`] and an
+/// `AsyncRingConsumer<u8>` implements [`Read`], waiting for room or data by
+/// registering a waker rather than spinning. An interrupt handler holding the
+/// other half wakes the waiting task whenever it pushes or pops, e.g. for
+/// firmware built on Embassy.
+///
+/// End of file and a dropped consumer are reported as for the blocking
+/// implementations on [`RingProducer`] and [`RingConsumer`].
+///
+/// ```rust
+/// use embedded_io_async::{Read, Write};
+/// use llq::ring::Ring;
+///
+/// let (mut tx, mut rx) = Ring::<u8>::with_capacity(4).split_async();
+///
+/// let isr = std::thread::spawn(move || {
+///     futures::executor::block_on(tx.write_all(b"received")).unwrap();
+/// });
+///
+/// let mut buf = [0; 8];
+/// futures::executor::block_on(rx.read_exact(&mut buf)).unwrap();
+/// assert_eq!(&buf, b"received");
+/// isr.join().unwrap();
+/// ```
+///
+/// [`embedded_io_async`]: https://docs.rs/embedded-io-async/0.6
+/// [`Write`]: https://docs.rs/embedded-io-async/0.6/embedded_io_async/trait.Write.html
+/// [`Read`]: https://docs.rs/embedded-io-async/0.6/embedded_io_async/trait.Read.html
+/// [`RingProducer`]: crate::ring::RingProducer
+/// [`RingConsumer`]: crate::ring::RingConsumer
+#[cfg(feature = "embedded-io-async")]
+impl ErrorType for AsyncRingProducer<u8> {
+    type Error = ErrorKind;
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::Write for AsyncRingProducer<u8> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, ErrorKind> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        future::poll_fn(|cx| match self.poll_ready(cx) {
+            Poll::Ready(true) => {
+                let written = buf.iter().take_while(|&&byte| self.push(byte).is_ok()).count();
+                Poll::Ready(Ok(written))
+            }
+            Poll::Ready(false) => Poll::Ready(Err(ErrorKind::BrokenPipe)),
+            Poll::Pending => Poll::Pending,
+        })
+        .await
+    }
+
+    /// Waits until the consumer has read every byte written so far.
+    async fn flush(&mut self) -> Result<(), ErrorKind> {
+        match future::poll_fn(|cx| self.poll_drained(cx)).await {
+            true => Ok(()),
+            false => Err(ErrorKind::BrokenPipe),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl ErrorType for AsyncRingConsumer<u8> {
+    type Error = ErrorKind;
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl embedded_io_async::Read for AsyncRingConsumer<u8> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let first = match future::poll_fn(|cx| self.poll_pop(cx)).await {
+            Some(byte) => byte,
+            None => return Ok(0),
+        };
+
+        buf[0] = first;
+        let mut read = 1;
+        while read < buf.len() {
+            match self.pop() {
+                Some(byte) => buf[read] = byte,
+                None => break,
+            }
+            read += 1;
+        }
+
+        Ok(read)
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::ring::Ring;
+
+    #[test]
+    fn byte_pipe() {
+        let (mut tx, mut rx) = Ring::<u8>::with_capacity(4).split();
+        assert!(!rx.read_ready().unwrap());
+
+        assert_eq!(tx.write(b"hello").unwrap(), 4);
+        assert!(!tx.write_ready().unwrap());
+        assert!(rx.read_ready().unwrap());
+
+        let mut buf = [0; 3];
+        assert_eq!(rx.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(tx.write(b"o!").unwrap(), 2);
+
+        let mut buf = [0; 8];
+        assert_eq!(rx.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"lo!");
+        tx.flush().unwrap();
+
+        drop(rx);
+        assert!(tx.write_ready().unwrap());
+        assert_eq!(tx.write(b"x"), Err(ErrorKind::BrokenPipe));
+    }
+
+    #[cfg(feature = "embedded-io-async")]
+    #[test]
+    fn async_byte_pipe() {
+        use ::futures::executor::block_on;
+
+        extern crate std;
+
+        let (mut tx, mut rx) = Ring::<u8>::with_capacity(8).split_async();
+
+        let thread = std::thread::spawn(move || {
+            block_on(async {
+                for i in 0..100u8 {
+                    embedded_io_async::Write::write_all(&mut tx, &[i; 3]).await.unwrap();
+                }
+                embedded_io_async::Write::flush(&mut tx).await.unwrap();
+            });
+        });
+
+        block_on(async {
+            let mut received = alloc::vec::Vec::new();
+            let mut buf = [0; 16];
+            loop {
+                match embedded_io_async::Read::read(&mut rx, &mut buf).await.unwrap() {
+                    0 => break,
+                    len => received.extend_from_slice(&buf[..len]),
+                }
+            }
+            assert_eq!(received.len(), 300);
+            assert_eq!(received[..4], [0, 0, 0, 1]);
+        });
+
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! Error types returned by fallible operations.
+//!
+//! Each error hands back the value which could not be pushed or allocated,
+//! which can be recovered with `into_inner()`. The errors implement
+//! [`core::error::Error`] and [`Display`] without requiring the `std`
+//! feature, and implement [`Debug`] without requiring `T: Debug`, so they
+//! work with `?` and with error handling libraries.
+//!
+//! Operations which fail only because a queue is empty, such as
+//! [`Consumer::pop()`], return `None` rather than an error.
+//!
+//! [`Display`]: core::fmt::Display
+//! [`Debug`]: core::fmt::Debug
+//! [`Consumer::pop()`]: crate::Consumer::pop
+
+use core::fmt;
+
+/// An error returned when an element could not be pushed.
+#[non_exhaustive]
+#[derive(PartialEq, Eq)]
+pub enum PushError<T> {
+    /// The queue, or the supply of nodes backing it, is full.
+    Full(T),
+    /// No credits were available to [`Producer::push_with_credit()`].
+    ///
+    /// [`Producer::push_with_credit()`]: crate::Producer::push_with_credit
+    NoCredits(T),
+    /// The push was rejected by a rate limit.
+    RateLimited(T),
+    /// Another push on the same producer was in progress.
+    Busy(T),
+    /// The push needed retraction, which was not enabled with
+    /// [`Queue::enable_retraction()`].
+    ///
+    /// [`Queue::enable_retraction()`]: crate::Queue::enable_retraction
+    NotRetractable(T),
+}
+
+impl<T> PushError<T> {
+    /// Returns the element which could not be pushed.
+    pub fn into_inner(self) -> T {
+        match self {
+            PushError::Full(value)
+            | PushError::NoCredits(value)
+            | PushError::RateLimited(value)
+            | PushError::Busy(value)
+            | PushError::NotRetractable(value) => value,
+        }
+    }
+
+    /// Applies `f` to the element which could not be pushed.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> PushError<U> {
+        match self {
+            PushError::Full(value) => PushError::Full(f(value)),
+            PushError::NoCredits(value) => PushError::NoCredits(f(value)),
+            PushError::RateLimited(value) => PushError::RateLimited(f(value)),
+            PushError::Busy(value) => PushError::Busy(f(value)),
+            PushError::NotRetractable(value) => PushError::NotRetractable(f(value)),
+        }
+    }
+}
+
+impl<T> fmt::Debug for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Full(_) => f.write_str("Full(..)"),
+            PushError::NoCredits(_) => f.write_str("NoCredits(..)"),
+            PushError::RateLimited(_) => f.write_str("RateLimited(..)"),
+            PushError::Busy(_) => f.write_str("Busy(..)"),
+            PushError::NotRetractable(_) => f.write_str("NotRetractable(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Full(_) => f.write_str("queue is full"),
+            PushError::NoCredits(_) => f.write_str("no credits available"),
+            PushError::RateLimited(_) => f.write_str("rate limit exceeded"),
+            PushError::Busy(_) => f.write_str("producer is busy"),
+            PushError::NotRetractable(_) => f.write_str("retraction is not enabled"),
+        }
+    }
+}
+
+impl<T> core::error::Error for PushError<T> {}
+
+/// An error returned when a node could not be allocated for a value.
+#[non_exhaustive]
+#[derive(PartialEq, Eq)]
+pub enum AllocError<T> {
+    /// The pool or arena had no free allocations.
+    Exhausted(T),
+    /// The global allocator failed.
+    OutOfMemory(T),
+}
+
+impl<T> AllocError<T> {
+    /// Returns the value for which no node could be allocated.
+    pub fn into_inner(self) -> T {
+        match self {
+            AllocError::Exhausted(value) | AllocError::OutOfMemory(value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Debug for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocError::Exhausted(_) => f.write_str("Exhausted(..)"),
+            AllocError::OutOfMemory(_) => f.write_str("OutOfMemory(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocError::Exhausted(_) => f.write_str("no free allocations"),
+            AllocError::OutOfMemory(_) => f.write_str("out of memory"),
+        }
+    }
+}
+
+impl<T> core::error::Error for AllocError<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_and_into_inner() {
+        fn push() -> Result<(), alloc::boxed::Box<dyn core::error::Error>> {
+            Err(PushError::Full(1))?
+        }
+
+        let err = push().unwrap_err();
+        assert_eq!(alloc::format!("{}", err), "queue is full");
+
+        let err = PushError::RateLimited(alloc::string::String::from("x"));
+        assert_eq!(alloc::format!("{:?}", err), "RateLimited(..)");
+        assert_eq!(err.map(|s| s.len()), PushError::RateLimited(1));
+
+        let err = AllocError::Exhausted(2);
+        assert_eq!(alloc::format!("{}", err), "no free allocations");
+        assert_eq!(err.into_inner(), 2);
+    }
+}
@@ -0,0 +1,136 @@
+//! Injection of failures for testing code built on the crate.
+//!
+//! The functions in this module make the crate's operations fail on demand,
+//! so that a test suite can exercise its error paths deterministically:
+//!
+//! - [`fail_allocations()`] makes node and arena allocations fail, as if the
+//!   global allocator were out of memory. Fallible constructors such as
+//!   [`Node::try_new()`] then return an error, while the others abort the
+//!   process, as they would on a real allocation failure.
+//! - [`exhaust_pools()`] makes the pools' free lists appear empty, so that
+//!   [`Pool::try_alloc()`] and [`SharedPool::try_alloc()`] fail and
+//!   [`Pool::alloc()`] falls back to allocating.
+//! - [`delay_visibility()`] makes [`Consumer::pop()`] find the queue empty,
+//!   as if the elements pushed by the producer had not become visible yet.
+//!
+//! Faults are injected per thread and only affect operations performed on
+//! the thread which injected them, so tests running in parallel do not
+//! interfere with each other. [`reset()`] clears all faults on the current
+//! thread.
+//!
+//! ```rust
+//! use llq::{fault, Node, Queue};
+//!
+//! fault::fail_allocations(1);
+//! assert!(Node::try_new(1).is_err());
+//! assert!(Node::try_new(2).is_ok());
+//!
+//! let (mut producer, mut consumer) = Queue::new().split();
+//! producer.push(Node::new(3));
+//! fault::delay_visibility(2);
+//! assert!(consumer.pop().is_none());
+//! assert!(consumer.pop().is_none());
+//! assert_eq!(*consumer.pop().unwrap(), 3);
+//! ```
+//!
+//! [`fail_allocations()`]: crate::fault::fail_allocations
+//! [`exhaust_pools()`]: crate::fault::exhaust_pools
+//! [`delay_visibility()`]: crate::fault::delay_visibility
+//! [`reset()`]: crate::fault::reset
+//! [`Node::try_new()`]: crate::Node::try_new
+//! [`Pool::try_alloc()`]: crate::pool::Pool::try_alloc
+//! [`SharedPool::try_alloc()`]: crate::pool::SharedPool::try_alloc
+//! [`Pool::alloc()`]: crate::pool::Pool::alloc
+//! [`Consumer::pop()`]: crate::Consumer::pop
+
+use core::cell::Cell;
+
+struct Faults {
+    allocations: Cell<usize>,
+    pools_exhausted: Cell<bool>,
+    pops: Cell<usize>,
+}
+
+std::thread_local! {
+    static FAULTS: Faults = const {
+        Faults { allocations: Cell::new(0), pools_exhausted: Cell::new(false), pops: Cell::new(0) }
+    };
+}
+
+/// Makes the next `count` node or arena allocations on the current thread
+/// fail.
+pub fn fail_allocations(count: usize) {
+    FAULTS.with(|faults| faults.allocations.set(count));
+}
+
+/// Makes every pool appear empty to the current thread while `exhausted` is
+/// `true`.
+pub fn exhaust_pools(exhausted: bool) {
+    FAULTS.with(|faults| faults.pools_exhausted.set(exhausted));
+}
+
+/// Makes the next `pops` calls to [`Consumer::pop()`] on the current thread
+/// return `None` without removing an element.
+///
+/// [`Consumer::pop()`]: crate::Consumer::pop
+pub fn delay_visibility(pops: usize) {
+    FAULTS.with(|faults| faults.pops.set(pops));
+}
+
+/// Clears all faults injected on the current thread.
+pub fn reset() {
+    FAULTS.with(|faults| {
+        faults.allocations.set(0);
+        faults.pools_exhausted.set(false);
+        faults.pops.set(0);
+    });
+}
+
+/// Returns `true`, counting it, if the current allocation should fail.
+pub(crate) fn fail_allocation() -> bool {
+    FAULTS.with(|faults| take(&faults.allocations))
+}
+
+/// Returns `true` if pools should appear empty.
+pub(crate) fn pools_exhausted() -> bool {
+    FAULTS.with(|faults| faults.pools_exhausted.get())
+}
+
+/// Returns `true`, counting it, if the current pop should find the queue
+/// empty.
+pub(crate) fn delay_pop() -> bool {
+    FAULTS.with(|faults| take(&faults.pops))
+}
+
+fn take(count: &Cell<usize>) -> bool {
+    match count.get() {
+        0 => false,
+        n => {
+            count.set(n - 1);
+            true
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::pool::Pool;
+    use crate::Node;
+
+    #[test]
+    fn inject() {
+        let mut pool = Pool::with_capacity(1);
+        exhaust_pools(true);
+        assert!(pool.try_alloc(0).is_err());
+        fail_allocations(1);
+        assert!(Node::try_new(0).is_err());
+        let node = pool.alloc(1);
+        assert_eq!(pool.recycle(node), 1);
+
+        reset();
+        let node = pool.try_alloc(2).unwrap();
+        assert_eq!(pool.recycle(node), 2);
+        assert_eq!(pool.len(), 2);
+    }
+}
@@ -0,0 +1,336 @@
+//! Queue halves which signal readiness through a file descriptor.
+//!
+//! [`Queue::split_fd()`] connects the producer and consumer halves of a queue
+//! with a nonblocking socket pair. A push writes a byte to the socket if the
+//! consumer has found the queue empty since the last notification, so the
+//! consumer's file descriptor becomes readable whenever elements may be
+//! available. This allows a queue to be waited on by a reactor such as `mio`
+//! alongside other I/O sources.
+//!
+//! [`FdConsumer`] implements [`AsFd`] and [`AsRawFd`], so it can be waited on
+//! with the `polling` crate or wrapped in a `calloop` `Generic` event source.
+//! With the `mio` feature enabled, it also implements `mio::event::Source`.
+//!
+//! ```rust
+//! use llq::{Node, Queue};
+//! use polling::{Event, Events, Poller};
+//!
+//! let (mut producer, mut consumer) = Queue::<u32>::new().split_fd().unwrap();
+//!
+//! let poller = Poller::new().unwrap();
+//! unsafe { poller.add(&consumer, Event::readable(0)).unwrap() };
+//!
+//! producer.push(Node::new(1));
+//!
+//! let mut events = Events::new();
+//! poller.wait(&mut events, None).unwrap();
+//! assert_eq!(*consumer.pop().unwrap(), 1);
+//!
+//! poller.delete(&consumer).unwrap();
+//! ```
+//!
+//! Unlike [`Producer::push()`], [`FdProducer::push()`] may perform a system
+//! call and is therefore not wait-free.
+//!
+//! Dropping the [`FdProducer`] closes the queue and signals the consumer's
+//! file descriptor, so that a consumer waiting on it can find out with
+//! [`FdConsumer::is_closed()`].
+//!
+//! [`Queue::split_fd()`]: crate::Queue::split_fd
+//! [`Producer::push()`]: crate::Producer::push
+//! [`FdProducer::push()`]: crate::fd::FdProducer::push
+//! [`FdConsumer`]: crate::fd::FdConsumer
+//! [`FdProducer`]: crate::fd::FdProducer
+//! [`FdConsumer::is_closed()`]: crate::fd::FdConsumer::is_closed
+//! [`AsFd`]: std::os::unix::io::AsFd
+//! [`AsRawFd`]: std::os::unix::io::AsRawFd
+
+use crate::waiting::Waiting;
+use crate::{Consumer, Node, Producer, Queue};
+
+use alloc::sync::Arc;
+use core::mem::ManuallyDrop;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+impl<T> Queue<T> {
+    /// Splits a queue into producer and consumer halves which signal
+    /// readiness through a file descriptor.
+    pub fn split_fd(self) -> io::Result<(FdProducer<T>, FdConsumer<T>)> {
+        let (notify, wait) = UnixStream::pair()?;
+        notify.set_nonblocking(true)?;
+        wait.set_nonblocking(true)?;
+
+        // The consumer starts out waiting, since it may wait on the file
+        // descriptor before its first pop.
+        let waiting = Arc::new(Waiting::new(true));
+
+        let (producer, consumer) = self.split();
+
+        Ok((
+            FdProducer { producer: ManuallyDrop::new(producer), notify, waiting: waiting.clone() },
+            FdConsumer { consumer, wait, waiting },
+        ))
+    }
+}
+
+/// The consumer half of a [`Queue`] split with [`Queue::split_fd()`].
+///
+/// The file descriptor returned by [`AsFd::as_fd()`] becomes readable
+/// when elements may be available. Once it has been signaled, [`pop()`]
+/// should be called until it returns `None` before waiting on it again.
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_fd()`]: crate::Queue::split_fd
+/// [`pop()`]: crate::fd::FdConsumer::pop
+/// [`AsFd::as_fd()`]: std::os::unix::io::AsFd::as_fd
+pub struct FdConsumer<T> {
+    consumer: Consumer<T>,
+    wait: UnixStream,
+    waiting: Arc<Waiting>,
+}
+
+impl<T> FdConsumer<T> {
+    /// Attempts to remove and return an element from the queue. Returns `None`
+    /// if the queue is empty.
+    ///
+    /// Before returning `None`, any pending readiness notifications are
+    /// cleared.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        if let Some(node) = self.consumer.pop() {
+            return Some(node);
+        }
+
+        // Notifications are cleared before the consumer is marked as waiting,
+        // so that a notification sent in response cannot be lost.
+        self.clear();
+        self.waiting.set();
+
+        // An element may have been pushed after the first pop but before the
+        // consumer was marked as waiting.
+        let node = self.consumer.pop();
+        if node.is_some() {
+            self.waiting.clear();
+        }
+        node
+    }
+
+    /// Returns `true` if the producer has been dropped and every element has
+    /// been popped; see [`Consumer::is_closed()`].
+    ///
+    /// [`Consumer::is_closed()`]: crate::Consumer::is_closed
+    pub fn is_closed(&self) -> bool {
+        self.consumer.is_closed()
+    }
+
+    fn clear(&mut self) {
+        let mut buf = [0; 64];
+        while let Ok(count) = (&self.wait).read(&mut buf) {
+            if count == 0 {
+                break;
+            }
+        }
+    }
+}
+
+impl<T> AsFd for FdConsumer<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.wait.as_fd()
+    }
+}
+
+impl<T> AsRawFd for FdConsumer<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.wait.as_raw_fd()
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<T> mio::event::Source for FdConsumer<T> {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.wait.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.wait.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        mio::unix::SourceFd(&self.wait.as_raw_fd()).deregister(registry)
+    }
+}
+
+/// The producer half of a [`Queue`] split with [`Queue::split_fd()`].
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_fd()`]: crate::Queue::split_fd
+pub struct FdProducer<T> {
+    producer: ManuallyDrop<Producer<T>>,
+    notify: UnixStream,
+    waiting: Arc<Waiting>,
+}
+
+impl<T> FdProducer<T> {
+    /// Adds an element to the queue and notifies the consumer.
+    ///
+    /// The consumer is only notified, with a system call, if it has found the
+    /// queue empty since it was last notified.
+    pub fn push(&mut self, node: Node<T>) {
+        self.producer.push(node);
+
+        if self.waiting.take() {
+            // If the socket buffer is full, the consumer already has a
+            // pending notification, so the error can be ignored.
+            let _ = (&self.notify).write(&[0]);
+        }
+    }
+}
+
+impl<T> Drop for FdProducer<T> {
+    fn drop(&mut self) {
+        // The queue is closed before the consumer is notified, so that the
+        // consumer finds it closed when it wakes.
+        unsafe { ManuallyDrop::drop(&mut self.producer) };
+        let _ = (&self.notify).write(&[0]);
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify() {
+        let (mut producer, mut consumer) = Queue::new().split_fd().unwrap();
+
+        assert!(consumer.pop().is_none());
+        assert!((&consumer.wait).read(&mut [0]).is_err());
+
+        producer.push(Node::new(1));
+        producer.push(Node::new(2));
+
+        assert_eq!(*consumer.pop().unwrap(), 1);
+        assert_eq!(*consumer.pop().unwrap(), 2);
+        assert!(consumer.pop().is_none());
+        assert!((&consumer.wait).read(&mut [0]).is_err());
+    }
+
+    #[test]
+    fn skip_wakeups() {
+        let (mut producer, mut consumer) = Queue::new().split_fd().unwrap();
+
+        producer.push(Node::new(1));
+        producer.push(Node::new(2));
+        assert_eq!((&consumer.wait).read(&mut [0; 2]).unwrap(), 1);
+
+        assert_eq!(*consumer.pop().unwrap(), 1);
+        producer.push(Node::new(3));
+        assert!((&consumer.wait).read(&mut [0]).is_err());
+    }
+
+    #[test]
+    fn producer_dropped() {
+        use polling::{Event, Events, Poller};
+        use std::time::Duration;
+
+        let (mut producer, mut consumer) = Queue::new().split_fd().unwrap();
+        producer.push(Node::new(1));
+        assert_eq!(*consumer.pop().unwrap(), 1);
+        assert!(consumer.pop().is_none());
+
+        let poller = Poller::new().unwrap();
+        unsafe { poller.add(&consumer, Event::readable(5)).unwrap() };
+        assert!(!consumer.is_closed());
+
+        drop(producer);
+
+        let mut events = Events::new();
+        poller.wait(&mut events, Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(events.iter().map(|event| event.key).collect::<std::vec::Vec<_>>(), [5]);
+        // The consumer is sent a notification rather than only the end of the
+        // stream.
+        assert_eq!((&consumer.wait).read(&mut [0; 2]).unwrap(), 1);
+        assert!(consumer.pop().is_none());
+        assert!(consumer.is_closed());
+
+        poller.delete(&consumer).unwrap();
+    }
+
+    #[test]
+    fn polling() {
+        use polling::{Event, Events, Poller};
+        use std::time::Duration;
+
+        let (mut producer, mut consumer) = Queue::new().split_fd().unwrap();
+
+        let poller = Poller::new().unwrap();
+        unsafe { poller.add(&consumer, Event::readable(3)).unwrap() };
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..100 {
+                producer.push(Node::new(i));
+            }
+        });
+
+        let mut events = Events::new();
+        let mut received = 0;
+        while received < 100 {
+            events.clear();
+            poller.wait(&mut events, Some(Duration::from_secs(5))).unwrap();
+            assert!(events.iter().all(|event| event.key == 3));
+
+            while let Some(node) = consumer.pop() {
+                assert_eq!(*node, received);
+                received += 1;
+            }
+
+            poller.modify(&consumer, Event::readable(3)).unwrap();
+        }
+
+        poller.delete(&consumer).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[cfg(feature = "mio")]
+    #[test]
+    fn mio_poll() {
+        use mio::{Events, Interest, Poll, Token};
+        use std::time::Duration;
+
+        let (mut producer, mut consumer) = Queue::new().split_fd().unwrap();
+
+        let mut poll = Poll::new().unwrap();
+        poll.registry().register(&mut consumer, Token(7), Interest::READABLE).unwrap();
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..100 {
+                producer.push(Node::new(i));
+            }
+        });
+
+        let mut events = Events::with_capacity(8);
+        let mut received = 0;
+        while received < 100 {
+            poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+            assert!(events.iter().all(|event| event.token() == Token(7)));
+
+            while let Some(node) = consumer.pop() {
+                assert_eq!(*node, received);
+                received += 1;
+            }
+        }
+
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+//! FFI-stable queue handles for plugin boundaries.
+//!
+//! [`FfiProducer`] and [`FfiConsumer`] wrap a [`Producer`] or [`Consumer`] in
+//! a `repr(C)` handle consisting of an opaque pointer and a versioned vtable of
+//! `extern "C"` functions. The handles can be passed between binaries built
+//! with different Rust compilers, such as a plugin host and a dynamically
+//! loaded plugin: each operation runs code from the binary which created the
+//! handle.
+//!
+//! Nodes cross the boundary as pointers, so both sides must agree on the
+//! layout of `T` (which should be `repr(C)`) and on the enabled features of
+//! this crate, and nodes must be allocated and freed by the same global
+//! allocator. Before using a handle received from another binary, check that
+//! [`is_compatible()`] returns `true`.
+//!
+//! ```rust
+//! use llq::ffi::{FfiConsumer, FfiProducer};
+//! use llq::{Node, Queue};
+//!
+//! let (producer, consumer) = Queue::<u32>::new().split();
+//! let mut producer = FfiProducer::new(producer);
+//! let mut consumer = FfiConsumer::new(consumer);
+//! assert!(producer.is_compatible() && consumer.is_compatible());
+//!
+//! producer.push(Node::new(1));
+//! assert_eq!(*consumer.pop().unwrap(), 1);
+//! ```
+//!
+//! [`FfiProducer`]: crate::ffi::FfiProducer
+//! [`FfiConsumer`]: crate::ffi::FfiConsumer
+//! [`Producer`]: crate::Producer
+//! [`Consumer`]: crate::Consumer
+//! [`is_compatible()`]: crate::ffi::FfiProducer::is_compatible
+
+use crate::{Consumer, Node, NodeInner, Producer};
+
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::NonNull;
+
+/// The version of the vtable layout. Future versions will only append
+/// functions to the vtables.
+pub const FFI_VERSION: u32 = 1;
+
+/// The functions backing an [`FfiProducer`].
+///
+/// [`FfiProducer`]: crate::ffi::FfiProducer
+#[repr(C)]
+pub struct ProducerVTable {
+    /// The vtable layout version, equal to [`FFI_VERSION`] for handles
+    /// created by this version of the crate.
+    ///
+    /// [`FFI_VERSION`]: crate::ffi::FFI_VERSION
+    pub version: u32,
+    /// Pushes a node, taking ownership of it.
+    pub push: unsafe extern "C" fn(state: *mut c_void, node: *mut c_void),
+    /// Drops the producer.
+    pub drop: unsafe extern "C" fn(state: *mut c_void),
+}
+
+/// The functions backing an [`FfiConsumer`].
+///
+/// [`FfiConsumer`]: crate::ffi::FfiConsumer
+#[repr(C)]
+pub struct ConsumerVTable {
+    /// The vtable layout version, equal to [`FFI_VERSION`] for handles
+    /// created by this version of the crate.
+    ///
+    /// [`FFI_VERSION`]: crate::ffi::FFI_VERSION
+    pub version: u32,
+    /// Pops a node, returning null if the queue is empty.
+    pub pop: unsafe extern "C" fn(state: *mut c_void) -> *mut c_void,
+    /// Drops the consumer.
+    pub drop: unsafe extern "C" fn(state: *mut c_void),
+}
+
+/// A `repr(C)` handle to the producer half of a queue.
+#[repr(C)]
+pub struct FfiProducer<T> {
+    state: *mut c_void,
+    vtable: *const ProducerVTable,
+    phantom: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for FfiProducer<T> {}
+
+impl<T> FfiProducer<T> {
+    /// Wraps a producer in an FFI-stable handle.
+    pub fn new(producer: Producer<T>) -> FfiProducer<T> {
+        FfiProducer {
+            state: Box::into_raw(Box::new(producer)) as *mut c_void,
+            vtable: &VTables::<T>::PRODUCER,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the handle's vtable is compatible with this version
+    /// of the crate.
+    pub fn is_compatible(&self) -> bool {
+        unsafe { (*self.vtable).version >= FFI_VERSION }
+    }
+
+    /// Adds an element to the queue.
+    pub fn push(&mut self, node: Node<T>) {
+        let node_ptr = node.inner.as_ptr() as *mut c_void;
+        mem::forget(node);
+
+        unsafe { ((*self.vtable).push)(self.state, node_ptr) };
+    }
+}
+
+impl<T> Drop for FfiProducer<T> {
+    fn drop(&mut self) {
+        unsafe { ((*self.vtable).drop)(self.state) };
+    }
+}
+
+/// A `repr(C)` handle to the consumer half of a queue.
+#[repr(C)]
+pub struct FfiConsumer<T> {
+    state: *mut c_void,
+    vtable: *const ConsumerVTable,
+    phantom: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for FfiConsumer<T> {}
+
+impl<T> FfiConsumer<T> {
+    /// Wraps a consumer in an FFI-stable handle.
+    pub fn new(consumer: Consumer<T>) -> FfiConsumer<T> {
+        FfiConsumer {
+            state: Box::into_raw(Box::new(consumer)) as *mut c_void,
+            vtable: &VTables::<T>::CONSUMER,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the handle's vtable is compatible with this version
+    /// of the crate.
+    pub fn is_compatible(&self) -> bool {
+        unsafe { (*self.vtable).version >= FFI_VERSION }
+    }
+
+    /// Attempts to remove and return an element from the queue. Returns `None`
+    /// if the queue is empty.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        let node = unsafe { ((*self.vtable).pop)(self.state) } as *mut NodeInner<T>;
+        NonNull::new(node).map(|inner| Node { inner, phantom: PhantomData })
+    }
+}
+
+impl<T> Drop for FfiConsumer<T> {
+    fn drop(&mut self) {
+        unsafe { ((*self.vtable).drop)(self.state) };
+    }
+}
+
+struct VTables<T>(PhantomData<T>);
+
+impl<T> VTables<T> {
+    const PRODUCER: ProducerVTable =
+        ProducerVTable { version: FFI_VERSION, push: push::<T>, drop: drop_producer::<T> };
+    const CONSUMER: ConsumerVTable =
+        ConsumerVTable { version: FFI_VERSION, pop: pop::<T>, drop: drop_consumer::<T> };
+}
+
+unsafe extern "C" fn push<T>(state: *mut c_void, node: *mut c_void) {
+    let producer = &mut *(state as *mut Producer<T>);
+    let inner = NonNull::new_unchecked(node as *mut NodeInner<T>);
+    producer.push(Node { inner, phantom: PhantomData });
+}
+
+unsafe extern "C" fn drop_producer<T>(state: *mut c_void) {
+    drop(Box::from_raw(state as *mut Producer<T>));
+}
+
+unsafe extern "C" fn pop<T>(state: *mut c_void) -> *mut c_void {
+    let consumer = &mut *(state as *mut Consumer<T>);
+    match consumer.pop() {
+        Some(node) => {
+            let node_ptr = node.inner.as_ptr() as *mut c_void;
+            mem::forget(node);
+            node_ptr
+        }
+        None => core::ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn drop_consumer<T>(state: *mut c_void) {
+    drop(Box::from_raw(state as *mut Consumer<T>));
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn drop_handles() {
+        let (producer, consumer) = Queue::new().split();
+        let mut producer = FfiProducer::new(producer);
+        let consumer = FfiConsumer::new(consumer);
+
+        producer.push(Node::new(alloc::string::String::from("a")));
+        drop(producer);
+        drop(consumer);
+    }
+}
@@ -0,0 +1,103 @@
+//! Frame-indexed command submission for game engines and renderers.
+//!
+//! Commands are pushed as [`Command`]s tagged with the frame they belong to.
+//! The consumer uses [`Consumer::pop_for_frame()`] to retrieve the commands
+//! for the frame it is currently processing while commands for later frames
+//! remain queued, and [`Consumer::discard_older_than()`] to throw away the
+//! commands for frames which were dropped.
+//!
+//! ```rust
+//! use llq::frame::Command;
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, mut consumer) = Queue::new().split();
+//!
+//! producer.push(Node::new(Command::new(1, "draw a")));
+//! producer.push(Node::new(Command::new(2, "draw b")));
+//! producer.push(Node::new(Command::new(3, "draw c")));
+//!
+//! // Frame 2 was dropped.
+//! assert_eq!(*consumer.pop_for_frame(1).unwrap(), Command::new(1, "draw a"));
+//! assert!(consumer.pop_for_frame(1).is_none());
+//! assert_eq!(consumer.discard_older_than(3), 1);
+//! assert_eq!(*consumer.pop_for_frame(3).unwrap(), Command::new(3, "draw c"));
+//! ```
+//!
+//! [`Command`]: crate::frame::Command
+//! [`Consumer::pop_for_frame()`]: crate::Consumer::pop_for_frame
+//! [`Consumer::discard_older_than()`]: crate::Consumer::discard_older_than
+
+use crate::{Consumer, Node};
+
+/// A command tagged with the frame it belongs to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Command<T> {
+    /// The frame the command belongs to.
+    pub frame: u64,
+    /// The command payload.
+    pub data: T,
+}
+
+impl<T> Command<T> {
+    /// Creates a new command for the given frame.
+    pub fn new(frame: u64, data: T) -> Command<T> {
+        Command { frame, data }
+    }
+}
+
+impl<T> Consumer<Command<T>> {
+    /// Removes and returns the next command if it belongs to frame `frame` or
+    /// an earlier frame. Returns `None` if the queue is empty or if the next
+    /// command belongs to a later frame.
+    ///
+    /// Commands are expected to be pushed in nondecreasing order of frame.
+    pub fn pop_for_frame(&mut self, frame: u64) -> Option<Node<Command<T>>> {
+        if self.peek()?.frame > frame {
+            return None;
+        }
+
+        self.pop()
+    }
+
+    /// Removes and drops all queued commands belonging to frames earlier than
+    /// `frame`, returning the number of commands discarded.
+    ///
+    /// Dropping a command deallocates its node. To recycle the nodes instead,
+    /// remove them with [`pop_for_frame()`] and discard their contents.
+    ///
+    /// [`pop_for_frame()`]: crate::Consumer::pop_for_frame
+    pub fn discard_older_than(&mut self, frame: u64) -> usize {
+        let mut count = 0;
+        while frame > 0 && self.pop_for_frame(frame - 1).is_some() {
+            count += 1;
+        }
+
+        count
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn frames() {
+        let (mut producer, mut consumer) = Queue::new().split();
+
+        for &frame in &[0, 0, 1, 2, 2, 4] {
+            producer.push(Node::new(Command::new(frame, frame)));
+        }
+
+        assert_eq!(consumer.discard_older_than(0), 0);
+        assert_eq!(consumer.discard_older_than(1), 2);
+
+        assert_eq!(consumer.pop_for_frame(2).unwrap().data, 1);
+        assert_eq!(consumer.pop_for_frame(2).unwrap().data, 2);
+        assert_eq!(consumer.pop_for_frame(2).unwrap().data, 2);
+        assert!(consumer.pop_for_frame(3).is_none());
+
+        assert_eq!(consumer.discard_older_than(5), 1);
+        assert!(consumer.pop().is_none());
+    }
+}
@@ -0,0 +1,716 @@
+//! Queue halves which can be awaited from async code.
+//!
+//! [`Queue::split_async()`] returns an [`AsyncProducer`] which wakes the task
+//! waiting on the corresponding [`AsyncConsumer`] whenever an element is
+//! pushed. [`AsyncConsumer`] implements [`Stream`], yielding one node per
+//! item, and [`AsyncConsumer::batches()`] returns a stream which yields
+//! everything available at each wakeup as a single [`Chain`].
+//!
+//! Both streams end once the producer has been dropped and the queue has been
+//! drained.
+//!
+//! [`Ring::split_async()`] similarly returns ring buffer halves which can be
+//! awaited, the producer for room and the consumer for elements. Pushing and
+//! popping without waiting remain wait-free, so either half can be driven
+//! from an interrupt handler while the other is awaited by a task.
+//!
+//! With the `std` feature enabled, [`byte_pipe()`] returns an
+//! [`AsyncByteWriter`] and an [`AsyncByteReader`], which pass byte chunks over
+//! a queue to implement [`AsyncWrite`] and [`AsyncRead`], allowing a queue to
+//! be used as an in-process pipe.
+//!
+//! ```rust
+//! use futures::StreamExt;
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, mut consumer) = Queue::<usize>::new().split_async();
+//!
+//! std::thread::spawn(move || {
+//!     for i in 0..100 {
+//!         producer.push(Node::new(i));
+//!     }
+//! });
+//!
+//! futures::executor::block_on(async {
+//!     let mut expected = 0;
+//!     let mut batches = consumer.batches();
+//!     while let Some(batch) = batches.next().await {
+//!         for node in batch {
+//!             assert_eq!(*node, expected);
+//!             expected += 1;
+//!         }
+//!     }
+//!     assert_eq!(expected, 100);
+//! });
+//! ```
+//!
+//! [`Queue::split_async()`]: crate::Queue::split_async
+//! [`AsyncProducer`]: crate::futures::AsyncProducer
+//! [`AsyncConsumer`]: crate::futures::AsyncConsumer
+//! [`AsyncConsumer::batches()`]: crate::futures::AsyncConsumer::batches
+//! [`Stream`]: futures_core::Stream
+//! [`Chain`]: crate::Chain
+//! [`Ring::split_async()`]: crate::ring::Ring::split_async
+//! [`byte_pipe()`]: crate::futures::byte_pipe
+//! [`AsyncByteWriter`]: crate::futures::AsyncByteWriter
+//! [`AsyncByteReader`]: crate::futures::AsyncByteReader
+//! [`AsyncWrite`]: futures_io::AsyncWrite
+//! [`AsyncRead`]: futures_io::AsyncRead
+
+use crate::error::PushError;
+use crate::ring::{Ring, RingConsumer, RingProducer};
+use crate::sync::{AtomicBool, ACQUIRE, RELEASE};
+use crate::waiting::Waiting;
+use crate::{Chain, Consumer, Node, Producer, Queue};
+
+use alloc::sync::Arc;
+use atomic_waker::AtomicWaker;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::Stream;
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+use futures_io::{AsyncRead, AsyncWrite};
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+use std::io;
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+use std::vec::Vec;
+
+struct Signal {
+    waker: AtomicWaker,
+    waiting: Waiting,
+    closed: AtomicBool,
+}
+
+impl Signal {
+    fn new() -> Signal {
+        Signal {
+            waker: AtomicWaker::new(),
+            waiting: Waiting::new(false),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    fn wake(&self) {
+        if self.waiting.take() {
+            self.waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        self.closed.store(true, RELEASE);
+        self.waker.wake();
+    }
+
+    /// Polls until `ready` returns `true`, returning `false` instead if the
+    /// signal has been closed first.
+    fn poll_until<F: FnMut() -> bool>(&self, cx: &mut Context, mut ready: F) -> Poll<bool> {
+        if ready() {
+            return Poll::Ready(true);
+        }
+
+        self.waker.register(cx.waker());
+        self.waiting.set();
+
+        if ready() {
+            self.waiting.clear();
+            return Poll::Ready(true);
+        }
+
+        if self.closed.load(ACQUIRE) {
+            return Poll::Ready(ready());
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> Queue<T> {
+    /// Splits a queue into producer and consumer halves which can be used
+    /// from async code.
+    pub fn split_async(self) -> (AsyncProducer<T>, AsyncConsumer<T>) {
+        let signal = Arc::new(Signal::new());
+
+        let (producer, consumer) = self.split();
+
+        (AsyncProducer { producer, signal: signal.clone() }, AsyncConsumer { consumer, signal })
+    }
+}
+
+/// The consumer half of a [`Queue`] split with [`Queue::split_async()`].
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_async()`]: crate::Queue::split_async
+pub struct AsyncConsumer<T> {
+    consumer: Consumer<T>,
+    signal: Arc<Signal>,
+}
+
+impl<T> AsyncConsumer<T> {
+    /// Attempts to remove and return an element from the queue without
+    /// waiting. Returns `None` if the queue is empty.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        self.consumer.pop()
+    }
+
+    /// Attempts to remove an element from the queue, registering the current
+    /// task to be woken if the queue is empty.
+    ///
+    /// Returns `Poll::Ready(None)` if the queue is empty and the producer has
+    /// been dropped.
+    pub fn poll_pop(&mut self, cx: &mut Context) -> Poll<Option<Node<T>>> {
+        self.poll_with(cx, Consumer::pop)
+    }
+
+    /// Attempts to remove all available elements from the queue as a
+    /// [`Chain`], registering the current task to be woken if the queue is
+    /// empty.
+    ///
+    /// Returns `Poll::Ready(None)` if the queue is empty and the producer has
+    /// been dropped.
+    ///
+    /// [`Chain`]: crate::Chain
+    pub fn poll_pop_chain(&mut self, cx: &mut Context) -> Poll<Option<Chain<T>>> {
+        self.poll_with(cx, |consumer| {
+            let chain = consumer.pop_chain();
+            if chain.is_empty() {
+                None
+            } else {
+                Some(chain)
+            }
+        })
+    }
+
+    /// Returns a stream which yields all elements available at each wakeup as
+    /// a single [`Chain`].
+    ///
+    /// [`Chain`]: crate::Chain
+    pub fn batches(&mut self) -> Batches<'_, T> {
+        Batches { consumer: self }
+    }
+
+    fn poll_with<R, F>(&mut self, cx: &mut Context, mut f: F) -> Poll<Option<R>>
+    where
+        F: FnMut(&mut Consumer<T>) -> Option<R>,
+    {
+        if let Some(result) = f(&mut self.consumer) {
+            return Poll::Ready(Some(result));
+        }
+
+        self.signal.waker.register(cx.waker());
+        self.signal.waiting.set();
+
+        // Check again in case an element was pushed before the consumer was
+        // marked as waiting.
+        if let Some(result) = f(&mut self.consumer) {
+            self.signal.waiting.clear();
+            return Poll::Ready(Some(result));
+        }
+
+        if self.signal.closed.load(ACQUIRE) {
+            // All pushes happen before the producer is closed.
+            return Poll::Ready(f(&mut self.consumer));
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> Stream for AsyncConsumer<T> {
+    type Item = Node<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Node<T>>> {
+        self.get_mut().poll_pop(cx)
+    }
+}
+
+/// A stream which yields all available elements at each wakeup as a single
+/// [`Chain`].
+///
+/// Returned by [`AsyncConsumer::batches()`].
+///
+/// [`Chain`]: crate::Chain
+/// [`AsyncConsumer::batches()`]: crate::futures::AsyncConsumer::batches
+pub struct Batches<'a, T> {
+    consumer: &'a mut AsyncConsumer<T>,
+}
+
+impl<'a, T> Stream for Batches<'a, T> {
+    type Item = Chain<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Chain<T>>> {
+        self.get_mut().consumer.poll_pop_chain(cx)
+    }
+}
+
+/// The producer half of a [`Queue`] split with [`Queue::split_async()`].
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_async()`]: crate::Queue::split_async
+pub struct AsyncProducer<T> {
+    producer: Producer<T>,
+    signal: Arc<Signal>,
+}
+
+impl<T> AsyncProducer<T> {
+    /// Adds an element to the queue and wakes the consumer's task if it is
+    /// waiting.
+    pub fn push(&mut self, node: Node<T>) {
+        self.producer.push(node);
+        self.wake();
+    }
+
+    /// Adds all nodes in a [`Chain`] to the queue and wakes the consumer's
+    /// task if it is waiting.
+    ///
+    /// [`Chain`]: crate::Chain
+    pub fn push_chain(&mut self, chain: Chain<T>) {
+        self.producer.push_chain(chain);
+        self.wake();
+    }
+
+    fn wake(&self) {
+        self.signal.wake();
+    }
+}
+
+impl<T> Drop for AsyncProducer<T> {
+    fn drop(&mut self) {
+        self.signal.close();
+    }
+}
+
+struct RingSignals {
+    /// Signals the consumer that elements have been pushed.
+    data: Signal,
+    /// Signals the producer that elements have been popped.
+    space: Signal,
+}
+
+impl<T> Ring<T> {
+    /// Splits a ring buffer into producer and consumer halves which can be
+    /// used from async code.
+    pub fn split_async(self) -> (AsyncRingProducer<T>, AsyncRingConsumer<T>) {
+        let signals = Arc::new(RingSignals { data: Signal::new(), space: Signal::new() });
+        let (producer, consumer) = self.split();
+
+        (
+            AsyncRingProducer { producer, signals: signals.clone() },
+            AsyncRingConsumer { consumer, signals },
+        )
+    }
+}
+
+/// The producer half of a [`Ring`] split with [`Ring::split_async()`].
+///
+/// [`Ring`]: crate::ring::Ring
+/// [`Ring::split_async()`]: crate::ring::Ring::split_async
+pub struct AsyncRingProducer<T> {
+    producer: RingProducer<T>,
+    signals: Arc<RingSignals>,
+}
+
+impl<T> AsyncRingProducer<T> {
+    /// Adds an element to the ring buffer and wakes the consumer's task if it
+    /// is waiting. Returns the element in an error if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), PushError<T>> {
+        self.producer.push(value)?;
+        self.signals.data.wake();
+        Ok(())
+    }
+
+    /// Checks whether the ring buffer has room for an element, registering
+    /// the current task to be woken if it is full.
+    ///
+    /// Returns `Poll::Ready(true)` once there is room, and `Poll::Ready(false)`
+    /// if the buffer is full and the consumer has been dropped.
+    pub fn poll_ready(&mut self, cx: &mut Context) -> Poll<bool> {
+        let producer = &mut self.producer;
+        self.signals.space.poll_until(cx, || !producer.is_full())
+    }
+
+    /// Checks whether the consumer has removed every element pushed so far,
+    /// registering the current task to be woken if it has not.
+    ///
+    /// Returns `Poll::Ready(false)` if elements remain and the consumer has
+    /// been dropped.
+    pub fn poll_drained(&mut self, cx: &mut Context) -> Poll<bool> {
+        let producer = &mut self.producer;
+        self.signals.space.poll_until(cx, || producer.is_drained())
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.producer.capacity()
+    }
+}
+
+impl<T> Drop for AsyncRingProducer<T> {
+    fn drop(&mut self) {
+        self.signals.data.close();
+    }
+}
+
+/// The consumer half of a [`Ring`] split with [`Ring::split_async()`].
+///
+/// [`Ring`]: crate::ring::Ring
+/// [`Ring::split_async()`]: crate::ring::Ring::split_async
+pub struct AsyncRingConsumer<T> {
+    consumer: RingConsumer<T>,
+    signals: Arc<RingSignals>,
+}
+
+impl<T> AsyncRingConsumer<T> {
+    /// Attempts to remove and return an element from the ring buffer without
+    /// waiting, waking the producer's task if it is waiting for room. Returns
+    /// `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let value = self.consumer.pop()?;
+        self.signals.space.wake();
+        Some(value)
+    }
+
+    /// Attempts to remove an element from the ring buffer, registering the
+    /// current task to be woken if it is empty.
+    ///
+    /// Returns `Poll::Ready(None)` if the buffer is empty and the producer
+    /// has been dropped.
+    pub fn poll_pop(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        let consumer = &mut self.consumer;
+        match self.signals.data.poll_until(cx, || !consumer.is_empty()) {
+            Poll::Ready(true) => Poll::Ready(self.pop()),
+            Poll::Ready(false) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.consumer.capacity()
+    }
+}
+
+impl<T> Stream for AsyncRingConsumer<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.get_mut().poll_pop(cx)
+    }
+}
+
+impl<T> Drop for AsyncRingConsumer<T> {
+    fn drop(&mut self) {
+        self.signals.space.close();
+    }
+}
+
+/// Creates an in-process byte pipe, returning its writing and reading ends.
+///
+/// Each write is passed to the reader as a byte chunk in a node. Once the
+/// reader has read a chunk, it sends the node back to the writer, which
+/// reuses it along with its buffer for a later write. At most `chunks` nodes
+/// are allocated, rounded up to one, and once they are all in flight, writes
+/// return `Poll::Pending` until the reader hands one back.
+///
+/// ```rust
+/// use futures::{AsyncReadExt, AsyncWriteExt};
+/// use llq::futures::byte_pipe;
+///
+/// let (mut writer, mut reader) = byte_pipe(4);
+///
+/// futures::executor::block_on(async {
+///     writer.write_all(b"hello").await.unwrap();
+///     writer.close().await.unwrap();
+///
+///     let mut buf = Vec::new();
+///     reader.read_to_end(&mut buf).await.unwrap();
+///     assert_eq!(buf, b"hello");
+/// });
+/// ```
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+pub fn byte_pipe(chunks: usize) -> (AsyncByteWriter, AsyncByteReader) {
+    let (producer, consumer) = Queue::new().split_async();
+    let (returned_producer, returned_consumer) = Queue::new().split_async();
+
+    (
+        AsyncByteWriter {
+            producer: Some(producer),
+            returned: returned_consumer,
+            unallocated: chunks.max(1),
+        },
+        AsyncByteReader { consumer, returned: returned_producer, current: None, offset: 0 },
+    )
+}
+
+/// The writing end of a pipe created with [`byte_pipe()`], which implements
+/// [`AsyncWrite`].
+///
+/// Closing the writer closes the pipe, after which the [`AsyncByteReader`]
+/// reaches end of file once it has read everything written. Writes fail with
+/// [`io::ErrorKind::BrokenPipe`] once the writer has been closed or the
+/// reader has been dropped.
+///
+/// [`byte_pipe()`]: crate::futures::byte_pipe
+/// [`AsyncWrite`]: futures_io::AsyncWrite
+/// [`AsyncByteReader`]: crate::futures::AsyncByteReader
+/// [`io::ErrorKind::BrokenPipe`]: std::io::ErrorKind::BrokenPipe
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+pub struct AsyncByteWriter {
+    producer: Option<AsyncProducer<Vec<u8>>>,
+    returned: AsyncConsumer<Vec<u8>>,
+    // The number of nodes which may still be allocated before writes wait for
+    // the reader to return one.
+    unallocated: usize,
+}
+
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+impl AsyncWrite for AsyncByteWriter {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let writer = self.get_mut();
+        let producer = match &mut writer.producer {
+            Some(producer) => producer,
+            None => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        };
+
+        // The reader holds the producer of the queue which returns nodes, so
+        // its half of that queue is gone once the reader has been dropped.
+        if writer.returned.consumer.producer_dropped() {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let mut node = match writer.returned.pop() {
+            Some(node) => node,
+            None if writer.unallocated > 0 => {
+                writer.unallocated -= 1;
+                Node::new(Vec::new())
+            }
+            None => match writer.returned.poll_pop(cx) {
+                Poll::Ready(Some(node)) => node,
+                Poll::Ready(None) => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+                Poll::Pending => return Poll::Pending,
+            },
+        };
+
+        node.clear();
+        node.extend_from_slice(buf);
+        producer.push(node);
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().producer = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The reading end of a pipe created with [`byte_pipe()`], which implements
+/// [`AsyncRead`].
+///
+/// Reads return end of file once the [`AsyncByteWriter`] has been closed or
+/// dropped and all chunks have been read.
+///
+/// [`byte_pipe()`]: crate::futures::byte_pipe
+/// [`AsyncRead`]: futures_io::AsyncRead
+/// [`AsyncByteWriter`]: crate::futures::AsyncByteWriter
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+pub struct AsyncByteReader {
+    consumer: AsyncConsumer<Vec<u8>>,
+    returned: AsyncProducer<Vec<u8>>,
+    current: Option<Node<Vec<u8>>>,
+    offset: usize,
+}
+
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+impl AsyncRead for AsyncByteReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let reader = self.get_mut();
+
+        loop {
+            if let Some(chunk) = &reader.current {
+                let remaining = &chunk[reader.offset..];
+                let len = remaining.len().min(buf.len());
+                buf[..len].copy_from_slice(&remaining[..len]);
+                reader.offset += len;
+                if reader.offset == chunk.len() {
+                    if let Some(chunk) = reader.current.take() {
+                        reader.returned.push(chunk);
+                    }
+                }
+                return Poll::Ready(Ok(len));
+            }
+
+            match reader.consumer.poll_pop(cx) {
+                Poll::Ready(Some(chunk)) => {
+                    reader.current = Some(chunk);
+                    reader.offset = 0;
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    use ::futures::executor::block_on;
+    use ::futures::StreamExt;
+
+    extern crate std;
+
+    #[test]
+    fn stream_ends() {
+        let (mut producer, mut consumer) = Queue::new().split_async();
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..1000 {
+                producer.push(Node::new(i));
+            }
+        });
+
+        block_on(async {
+            let mut expected = 0;
+            while let Some(node) = consumer.next().await {
+                assert_eq!(*node, expected);
+                expected += 1;
+            }
+            assert_eq!(expected, 1000);
+        });
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn batches() {
+        let (mut producer, mut consumer) = Queue::new().split_async();
+
+        for i in 0..10 {
+            producer.push(Node::new(i));
+        }
+        drop(producer);
+
+        block_on(async {
+            let mut batches = consumer.batches();
+            assert_eq!(batches.next().await.unwrap().len(), 10);
+            assert!(batches.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn skip_wakeups() {
+        use crate::sync::AtomicUsize;
+        use ::futures::task::{waker, ArcWake};
+
+        struct Counter(AtomicUsize);
+
+        impl ArcWake for Counter {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.fetch_add(1, RELEASE);
+            }
+        }
+
+        let (mut producer, mut consumer) = Queue::new().split_async();
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = waker(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        producer.push(Node::new(0));
+        assert_eq!(counter.0.load(ACQUIRE), 0);
+
+        assert!(consumer.poll_pop(&mut cx).is_ready());
+        assert!(consumer.poll_pop(&mut cx).is_pending());
+        producer.push(Node::new(1));
+        producer.push(Node::new(2));
+        assert_eq!(counter.0.load(ACQUIRE), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn byte_pipe() {
+        use ::futures::{AsyncReadExt, AsyncWriteExt};
+        use std::vec::Vec;
+
+        let (mut writer, mut reader) = super::byte_pipe(2);
+
+        let thread = std::thread::spawn(move || {
+            block_on(async {
+                for i in 0..100u8 {
+                    writer.write_all(&[i; 3]).await.unwrap();
+                }
+                writer.close().await.unwrap();
+                assert!(writer.write(&[0]).await.is_err());
+            });
+        });
+
+        block_on(async {
+            let mut buf = [0; 2];
+            reader.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, [0, 0]);
+
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).await.unwrap();
+            assert_eq!(rest.len(), 298);
+            assert_eq!(rest[..4], [0, 1, 1, 1]);
+        });
+
+        thread.join().unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn byte_pipe_backpressure() {
+        use ::futures::task::noop_waker;
+
+        let (mut writer, mut reader) = super::byte_pipe(1);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0; 8];
+
+        let mut writer = Pin::new(&mut writer);
+        assert!(matches!(writer.as_mut().poll_write(&mut cx, b"abc"), Poll::Ready(Ok(3))));
+        assert!(writer.as_mut().poll_write(&mut cx, b"de").is_pending());
+
+        let mut reader = Pin::new(&mut reader);
+        assert!(matches!(reader.as_mut().poll_read(&mut cx, &mut buf), Poll::Ready(Ok(3))));
+
+        // The node of the first write is handed back and reused.
+        assert!(matches!(writer.as_mut().poll_write(&mut cx, b"de"), Poll::Ready(Ok(2))));
+        assert_eq!(writer.unallocated, 0);
+        assert!(matches!(reader.poll_read(&mut cx, &mut buf), Poll::Ready(Ok(2))));
+        assert_eq!(&buf[..2], b"de");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn byte_pipe_reader_dropped() {
+        use ::futures::task::noop_waker;
+
+        let (mut writer, reader) = super::byte_pipe(4);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut writer = Pin::new(&mut writer);
+        assert!(matches!(writer.as_mut().poll_write(&mut cx, b"abc"), Poll::Ready(Ok(3))));
+        drop(reader);
+
+        // Nodes are still unallocated, but nothing would read the bytes.
+        match writer.as_mut().poll_write(&mut cx, b"de") {
+            Poll::Ready(Err(err)) => assert_eq!(err.kind(), io::ErrorKind::BrokenPipe),
+            _ => panic!("expected BrokenPipe"),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Creation of many queues at once.
+//!
+//! A [`ChannelGroup`] creates a fixed number of queues in one step, e.g. the
+//! channels of an engine at startup, and hands out their halves as pairs.
+//! [`ChannelGroup::try_new()`] is all or nothing: the sentinel nodes of every
+//! queue are allocated before any queue is created, and if one of the
+//! allocations fails, those already made are released and an error is
+//! returned.
+//!
+//! The control blocks of all of the queues live in a single allocation,
+//! which is shared by every half handed out and freed once the last of them
+//! has been dropped. Each queue still has its own sentinel node, since a
+//! sentinel does not stay with its queue: it carries the first element popped
+//! and is handed to the consumer as an ordinary [`Node`], which may be
+//! recycled into a pool, pushed onto another queue or deallocated on its own,
+//! so sentinels cannot share a single allocation.
+//!
+//! ```rust
+//! use llq::group::ChannelGroup;
+//! use llq::Node;
+//!
+//! let mut pairs = ChannelGroup::<u32>::new(200).into_pairs();
+//! assert_eq!(pairs.len(), 200);
+//!
+//! let (producer, consumer) = &mut pairs[17];
+//! producer.push(Node::new(17));
+//! assert_eq!(*consumer.pop().unwrap(), 17);
+//! ```
+//!
+//! [`ChannelGroup`]: crate::group::ChannelGroup
+//! [`ChannelGroup::try_new()`]: crate::group::ChannelGroup::try_new
+//! [`Node`]: crate::Node
+
+use crate::error::AllocError;
+use crate::{Consumer, NodeInner, Producer, Queue};
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+/// A set of queues created together; see the [module documentation].
+///
+/// [module documentation]: crate::group
+pub struct ChannelGroup<T> {
+    pairs: Vec<(Producer<T>, Consumer<T>)>,
+}
+
+impl<T> ChannelGroup<T> {
+    /// Creates `count` queues and splits each of them.
+    pub fn new(count: usize) -> ChannelGroup<T> {
+        let sentinels: Vec<_> = (0..count).map(|_| NodeInner::<T>::alloc_uninit()).collect();
+        ChannelGroup::from_sentinels(sentinels)
+    }
+
+    /// Creates `count` queues and splits each of them. Returns an error
+    /// without creating any queue if a sentinel node cannot be allocated.
+    pub fn try_new(count: usize) -> Result<ChannelGroup<T>, AllocError<()>> {
+        let mut sentinels = Vec::with_capacity(count);
+        for _ in 0..count {
+            match NodeInner::<T>::try_alloc_uninit() {
+                Some(node) => sentinels.push(node),
+                None => {
+                    for node in sentinels {
+                        unsafe { NodeInner::free(node.as_ptr()) };
+                    }
+                    return Err(AllocError::OutOfMemory(()));
+                }
+            }
+        }
+
+        Ok(ChannelGroup::from_sentinels(sentinels))
+    }
+
+    fn from_sentinels(sentinels: Vec<NonNull<NodeInner<T>>>) -> ChannelGroup<T> {
+        let queues: Arc<[Queue<T>]> =
+            sentinels.iter().map(|node| Queue::from_sentinel(node.as_ptr())).collect();
+        let pairs = (0..queues.len()).map(|index| Queue::split_grouped(&queues, index)).collect();
+        ChannelGroup { pairs }
+    }
+
+    /// Returns the number of queues in the group.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Returns `true` if the group has no queues.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Returns the producer and consumer halves of the queue at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn get_mut(&mut self, index: usize) -> (&mut Producer<T>, &mut Consumer<T>) {
+        let (producer, consumer) = &mut self.pairs[index];
+        (producer, consumer)
+    }
+
+    /// Returns the halves of every queue, in order of creation.
+    pub fn into_pairs(self) -> Vec<(Producer<T>, Consumer<T>)> {
+        self.pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    #[test]
+    fn independent_queues() {
+        let mut group = ChannelGroup::try_new(3).unwrap();
+        assert_eq!(group.len(), 3);
+
+        for i in 0..3 {
+            group.get_mut(i).0.push(Node::new(i));
+        }
+        for i in (0..3).rev() {
+            let (_, consumer) = group.get_mut(i);
+            assert_eq!(*consumer.pop().unwrap(), i);
+            assert!(consumer.pop().is_none());
+        }
+
+        assert!(ChannelGroup::<u8>::new(0).into_pairs().is_empty());
+    }
+
+    #[test]
+    fn recover_queue() {
+        let mut pairs = ChannelGroup::new(2).into_pairs();
+        let (mut producer, consumer) = pairs.pop().unwrap();
+        let (mut other_producer, mut other_consumer) = pairs.pop().unwrap();
+
+        producer.push(Node::new(1));
+        producer.push(Node::new(2));
+        other_producer.push(Node::new(3));
+        assert!(producer.into_queue().is_none());
+
+        // The recovered queue outlives the rest of the group.
+        let queue = consumer.into_queue().unwrap();
+        assert_eq!(*other_consumer.pop().unwrap(), 3);
+        drop((other_producer, other_consumer));
+
+        let (_, mut consumer) = queue.split();
+        assert_eq!(*consumer.pop().unwrap(), 1);
+        assert_eq!(*consumer.pop().unwrap(), 2);
+        assert!(consumer.pop().is_none());
+    }
+}
@@ -0,0 +1,241 @@
+//! Coordinated state transitions for a real-time thread.
+//!
+//! Changing the sample rate, swapping a processing graph or reallocating
+//! buffers all need the same handshake: a new state is prepared off the
+//! real-time thread, the real-time thread switches to it at a point where
+//! that is safe, and the state it replaces goes back to be disposed of
+//! elsewhere. A [`HandoffControl`] and a [`Handoff`] implement this over two
+//! queues.
+//!
+//! Each state posted with [`HandoffControl::post()`] is numbered with an
+//! epoch, counting up from the initial state's epoch of zero. At its safe
+//! point, the real-time thread calls [`Handoff::adopt()`], which switches to
+//! the most recently posted state and sends back every state it replaces,
+//! including any posted state which was superseded before it could be
+//! adopted. The control side learns which epoch has been adopted with
+//! [`HandoffControl::adopted()`], and receives the replaced states with
+//! [`HandoffControl::retire()`].
+//!
+//! [`Handoff::adopt()`] is wait-free, never allocates and never drops a
+//! state. Nodes are recycled between the two sides, so after the first few
+//! transitions [`HandoffControl::post()`] does not allocate either.
+//!
+//! ```rust
+//! use llq::handoff::Handoff;
+//!
+//! let (mut control, mut engine) = Handoff::new(vec![0.0f32; 64]);
+//!
+//! let audio = std::thread::spawn(move || {
+//!     // Typically called at the start of each audio callback.
+//!     while !engine.adopt() {}
+//!     engine
+//! });
+//!
+//! let epoch = control.post(vec![0.0; 128]);
+//! let engine = audio.join().unwrap();
+//! assert_eq!(engine.current().len(), 128);
+//! assert_eq!(control.adopted(), epoch);
+//!
+//! let retired = control.retire().unwrap();
+//! assert_eq!((retired.epoch, retired.state.len()), (0, 64));
+//! ```
+//!
+//! [`HandoffControl`]: crate::handoff::HandoffControl
+//! [`Handoff`]: crate::handoff::Handoff
+//! [`HandoffControl::post()`]: crate::handoff::HandoffControl::post
+//! [`Handoff::adopt()`]: crate::handoff::Handoff::adopt
+//! [`HandoffControl::adopted()`]: crate::handoff::HandoffControl::adopted
+//! [`HandoffControl::retire()`]: crate::handoff::HandoffControl::retire
+
+use crate::{Consumer, FreeList, Node, Producer, Queue};
+
+use core::mem;
+
+struct Slot<T> {
+    epoch: u64,
+    state: Option<T>,
+}
+
+/// A state which has been replaced on the real-time side, as returned by
+/// [`HandoffControl::retire()`].
+///
+/// [`HandoffControl::retire()`]: crate::handoff::HandoffControl::retire
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Retired<T> {
+    /// The epoch with which the state was posted.
+    pub epoch: u64,
+    /// The state.
+    pub state: T,
+}
+
+/// The real-time side of a handoff; see the [module documentation].
+///
+/// [module documentation]: crate::handoff
+pub struct Handoff<T> {
+    current: T,
+    epoch: u64,
+    posted: Consumer<Slot<T>>,
+    retired: Producer<Slot<T>>,
+}
+
+impl<T> Handoff<T> {
+    /// Creates a handoff starting out in `initial`, at epoch zero, returning
+    /// the control side and the real-time side.
+    pub fn new(initial: T) -> (HandoffControl<T>, Handoff<T>) {
+        let (posted_producer, posted_consumer) = Queue::new().split();
+        let (retired_producer, retired_consumer) = Queue::new().split();
+        (
+            HandoffControl {
+                posted: posted_producer,
+                retired: retired_consumer,
+                spare: FreeList::new(),
+                epoch: 0,
+            },
+            Handoff {
+                current: initial,
+                epoch: 0,
+                posted: posted_consumer,
+                retired: retired_producer,
+            },
+        )
+    }
+
+    /// Switches to the most recently posted state, sending the states it
+    /// replaces back to the control side. Returns `true` if the state
+    /// changed.
+    ///
+    /// Call this only at a point where the current state may be replaced.
+    pub fn adopt(&mut self) -> bool {
+        let mut adopted = false;
+        while let Some(mut node) = self.posted.pop() {
+            if let Some(state) = &mut node.state {
+                mem::swap(state, &mut self.current);
+                mem::swap(&mut node.epoch, &mut self.epoch);
+                adopted = true;
+            }
+            self.retired.push(node);
+        }
+
+        if adopted {
+            self.posted.ack();
+        }
+        adopted
+    }
+
+    /// Returns the current state.
+    pub fn current(&self) -> &T {
+        &self.current
+    }
+
+    /// Returns a mutable reference to the current state.
+    pub fn current_mut(&mut self) -> &mut T {
+        &mut self.current
+    }
+
+    /// Returns the epoch of the current state.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+}
+
+/// The control side of a handoff; see the [module documentation].
+///
+/// [module documentation]: crate::handoff
+pub struct HandoffControl<T> {
+    posted: Producer<Slot<T>>,
+    retired: Consumer<Slot<T>>,
+    spare: FreeList<Slot<T>>,
+    epoch: u64,
+}
+
+impl<T> HandoffControl<T> {
+    /// Posts a new state, which the real-time side will switch to at its
+    /// next call to [`Handoff::adopt()`]. Returns the state's epoch.
+    ///
+    /// Allocates a node only if none is left over from a previous
+    /// transition.
+    ///
+    /// [`Handoff::adopt()`]: crate::handoff::Handoff::adopt
+    pub fn post(&mut self, state: T) -> u64 {
+        self.epoch += 1;
+        let slot = Slot { epoch: self.epoch, state: Some(state) };
+        let node = match self.spare.pop() {
+            Some(mut node) => {
+                *node = slot;
+                node
+            }
+            None => Node::new(slot),
+        };
+
+        self.posted.push(node);
+        self.epoch
+    }
+
+    /// Returns the epoch of the most recently posted state.
+    pub fn posted(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the epoch of the state most recently adopted by the real-time
+    /// side.
+    pub fn adopted(&self) -> u64 {
+        let pending = (self.posted.total_pushed() as usize).wrapping_sub(self.posted.acked());
+        self.epoch - pending as u64
+    }
+
+    /// Returns `true` if the real-time side has adopted the most recently
+    /// posted state.
+    pub fn is_settled(&self) -> bool {
+        self.adopted() == self.epoch
+    }
+
+    /// Returns a state which the real-time side has replaced, so that it can
+    /// be disposed of. Returns `None` if there are none waiting.
+    ///
+    /// States are returned in the order in which they were replaced.
+    pub fn retire(&mut self) -> Option<Retired<T>> {
+        let mut node = self.retired.pop()?;
+        let state = node.state.take();
+        let epoch = node.epoch;
+        self.spare.push(node);
+
+        state.map(|state| Retired { epoch, state })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    #[test]
+    fn transitions() {
+        let (mut control, mut handoff) = Handoff::new("initial");
+        assert!(!handoff.adopt());
+        assert!(control.is_settled());
+
+        assert_eq!(control.post("superseded"), 1);
+        assert_eq!(control.post("second"), 2);
+        assert_eq!(control.adopted(), 0);
+        assert!(!control.is_settled());
+
+        assert!(handoff.adopt());
+        assert_eq!((handoff.epoch(), *handoff.current()), (2, "second"));
+        assert_eq!(control.adopted(), 2);
+
+        let retired: Vec<_> = core::iter::from_fn(|| control.retire()).collect();
+        assert_eq!(
+            retired,
+            [Retired { epoch: 0, state: "initial" }, Retired { epoch: 1, state: "superseded" }]
+        );
+
+        // Both nodes are reused.
+        control.post("third");
+        control.post("fourth");
+        assert!(control.spare.is_empty());
+        assert!(handoff.adopt());
+        assert_eq!(control.retire().map(|retired| retired.epoch), Some(2));
+        assert!(control.is_settled());
+    }
+}
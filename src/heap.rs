@@ -0,0 +1,232 @@
+//! Allocation of node memory.
+//!
+//! With the `std` feature enabled, each thread keeps a small cache of freed
+//! node allocations, grouped by layout, which is consulted before calling the
+//! global allocator. The cache has a fixed size and never allocates itself,
+//! and its contents are released when the thread exits.
+//!
+//! When the global allocator fails, the hook set with
+//! [`set_alloc_error_hook()`] is called before giving up.
+//!
+//! [`set_alloc_error_hook()`]: crate::set_alloc_error_hook
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// The hook set with [`set_alloc_error_hook()`], stored as a pointer, or null
+/// if there is none.
+///
+/// This is a plain atomic rather than one from [`crate::sync`], since it is a
+/// process-wide setting rather than part of a queue's protocol.
+static HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets a hook which is called with the layout of any node or arena
+/// allocation made by this crate which the global allocator fails to
+/// satisfy, or removes the hook if `hook` is `None`.
+///
+/// The hook can log the failure, release memory, e.g. by trimming pools with
+/// [`SharedPool::shrink_to()`], or escalate it. If it returns `true`, the
+/// allocation is retried, and the hook is called again if it fails again.
+/// Otherwise, fallible constructors such as [`Node::try_new()`] return an
+/// error, and the others abort the process as usual.
+///
+/// [`SharedPool::shrink_to()`]: crate::pool::SharedPool::shrink_to
+/// [`Node::try_new()`]: crate::Node::try_new
+pub fn set_alloc_error_hook(hook: Option<fn(Layout) -> bool>) {
+    let hook = match hook {
+        Some(hook) => hook as *mut (),
+        None => ptr::null_mut(),
+    };
+    HOOK.store(hook, Ordering::Release);
+}
+
+/// Allocates a block of memory with the given nonzero-sized layout.
+pub(crate) fn allocate(layout: Layout) -> *mut u8 {
+    let block = try_allocate(layout);
+    if block.is_null() {
+        handle_alloc_error(layout);
+    }
+    block
+}
+
+/// Allocates a block of memory with the given nonzero-sized layout. Returns
+/// null if the allocation fails.
+pub(crate) fn try_allocate(layout: Layout) -> *mut u8 {
+    #[cfg(feature = "fault-injection")]
+    {
+        if crate::fault::fail_allocation() {
+            return ptr::null_mut();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    {
+        if let Some(block) = cache::take(layout) {
+            #[cfg(feature = "accounting")]
+            crate::accounting::allocated(layout);
+            return block;
+        }
+    }
+
+    loop {
+        let block = unsafe { alloc(layout) };
+        if !block.is_null() {
+            #[cfg(feature = "accounting")]
+            crate::accounting::allocated(layout);
+            return block;
+        }
+
+        let hook = HOOK.load(Ordering::Acquire);
+        if hook.is_null() {
+            return block;
+        }
+
+        // Only pointers created from a `fn(Layout) -> bool` are stored.
+        let hook = unsafe { mem::transmute::<*mut (), fn(Layout) -> bool>(hook) };
+        if !hook(layout) {
+            return block;
+        }
+    }
+}
+
+/// Deallocates a block of memory returned by [`allocate()`] with the same
+/// layout.
+pub(crate) unsafe fn deallocate(block: *mut u8, layout: Layout) {
+    #[cfg(feature = "accounting")]
+    crate::accounting::deallocated(layout);
+
+    #[cfg(feature = "std")]
+    {
+        if cache::give(block, layout) {
+            return;
+        }
+    }
+
+    dealloc(block, layout);
+}
+
+#[cfg(feature = "std")]
+mod cache {
+    use super::*;
+
+    use core::cell::RefCell;
+    use core::ptr;
+
+    const CLASSES: usize = 8;
+    const BLOCKS_PER_CLASS: usize = 32;
+
+    struct Class {
+        layout: Option<Layout>,
+        len: usize,
+        blocks: [*mut u8; BLOCKS_PER_CLASS],
+    }
+
+    struct Cache {
+        classes: [Class; CLASSES],
+    }
+
+    impl Drop for Cache {
+        fn drop(&mut self) {
+            for class in &mut self.classes {
+                if let Some(layout) = class.layout {
+                    for &block in &class.blocks[..class.len] {
+                        unsafe { dealloc(block, layout) };
+                    }
+                }
+            }
+        }
+    }
+
+    const EMPTY: Class =
+        Class { layout: None, len: 0, blocks: [ptr::null_mut(); BLOCKS_PER_CLASS] };
+
+    std::thread_local! {
+        static CACHE: RefCell<Cache> = const { RefCell::new(Cache { classes: [EMPTY; CLASSES] }) };
+    }
+
+    /// Takes a cached block with the given layout, if there is one.
+    pub(super) fn take(layout: Layout) -> Option<*mut u8> {
+        CACHE
+            .try_with(|cache| {
+                let mut cache = cache.try_borrow_mut().ok()?;
+                let class = cache
+                    .classes
+                    .iter_mut()
+                    .find(|class| class.layout == Some(layout) && class.len > 0)?;
+                class.len -= 1;
+                Some(class.blocks[class.len])
+            })
+            .ok()
+            .flatten()
+    }
+
+    /// Adds a block to the cache. Returns `false` if the cache for its layout
+    /// is full or unavailable, in which case the caller must deallocate it.
+    pub(super) fn give(block: *mut u8, layout: Layout) -> bool {
+        CACHE
+            .try_with(|cache| {
+                let mut cache = match cache.try_borrow_mut() {
+                    Ok(cache) => cache,
+                    Err(_) => return false,
+                };
+
+                let index =
+                    match cache.classes.iter().position(|class| class.layout == Some(layout)) {
+                        Some(index) => index,
+                        None => match cache.classes.iter().position(|class| class.len == 0) {
+                            Some(index) => index,
+                            None => return false,
+                        },
+                    };
+
+                let class = &mut cache.classes[index];
+                if class.len == BLOCKS_PER_CLASS {
+                    return false;
+                }
+                class.layout = Some(layout);
+                class.blocks[class.len] = block;
+                class.len += 1;
+                true
+            })
+            .unwrap_or(false)
+    }
+
+    #[cfg(all(test, feature = "alloc-constructors"))]
+    mod tests {
+        use crate::Node;
+
+        #[test]
+        fn reuse() {
+            let node = Node::new(0u64);
+            let addr = &*node as *const u64;
+            drop(node);
+
+            let node = Node::new(1u64);
+            assert_eq!(&*node as *const u64, addr);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    use core::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn alloc_error_hook() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        set_alloc_error_hook(Some(|_| CALLS.fetch_add(1, Ordering::Relaxed) < 2));
+
+        let layout = Layout::from_size_align(isize::MAX as usize / 2, 8).unwrap();
+        assert!(try_allocate(layout).is_null());
+        assert_eq!(CALLS.load(Ordering::Relaxed), 3);
+
+        set_alloc_error_hook(None);
+        assert!(try_allocate(layout).is_null());
+        assert_eq!(CALLS.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,188 @@
+//! An unbounded SPSC channel with a ring buffer fast path.
+//!
+//! [`Hybrid`] stores elements in a fixed-size [`Ring`] while it has space, and
+//! spills them into individually allocated nodes on a linked-list [`Queue`]
+//! only when the ring is full. Steady-state traffic therefore never
+//! allocates, while bursts larger than the ring are still accepted. The
+//! consumer sees a single stream of elements in the order they were pushed.
+//!
+//! Once the producer has spilled, it keeps pushing onto the linked list until
+//! the consumer has drained it, so that elements are never reordered.
+//!
+//! ```rust
+//! use llq::hybrid::Hybrid;
+//!
+//! let (mut producer, mut consumer) = Hybrid::with_capacity(2).split();
+//! for i in 0..5 {
+//!     producer.push(i);
+//! }
+//! assert!(producer.is_spilling());
+//!
+//! let values: Vec<_> = std::iter::from_fn(|| consumer.pop()).collect();
+//! assert_eq!(values, [0, 1, 2, 3, 4]);
+//! assert!(!producer.is_spilling());
+//! ```
+//!
+//! [`Hybrid`]: crate::hybrid::Hybrid
+//! [`Ring`]: crate::ring::Ring
+//! [`Queue`]: crate::Queue
+
+use crate::ring::{Ring, RingConsumer, RingProducer};
+use crate::spsc::{SpscConsumer, SpscProducer};
+use crate::sync::{AtomicUsize, ACQUIRE, RELAXED, RELEASE};
+use crate::{Consumer, Node, Producer, Queue};
+
+use alloc::sync::Arc;
+
+/// An unbounded SPSC channel which stores elements in a ring buffer and
+/// spills to a linked list when the ring is full.
+pub struct Hybrid<T> {
+    ring: Ring<T>,
+    queue: Queue<T>,
+}
+
+impl<T> Hybrid<T> {
+    /// Creates a hybrid channel whose ring buffer can hold at least
+    /// `capacity` elements.
+    ///
+    /// The capacity is rounded up to a power of two.
+    pub fn with_capacity(capacity: usize) -> Hybrid<T> {
+        Hybrid { ring: Ring::with_capacity(capacity), queue: Queue::new() }
+    }
+
+    /// Splits a hybrid channel into producer and consumer halves.
+    pub fn split(self) -> (HybridProducer<T>, HybridConsumer<T>) {
+        let spilled = Arc::new(AtomicUsize::new(0));
+
+        let (ring_producer, ring_consumer) = self.ring.split();
+        let (producer, consumer) = self.queue.split();
+
+        (
+            HybridProducer { ring: ring_producer, overflow: producer, spilled: spilled.clone() },
+            HybridConsumer { ring: ring_consumer, overflow: consumer, spilled },
+        )
+    }
+}
+
+/// The producer half of a [`Hybrid`] channel.
+///
+/// [`Hybrid`]: crate::hybrid::Hybrid
+pub struct HybridProducer<T> {
+    ring: RingProducer<T>,
+    overflow: Producer<T>,
+    spilled: Arc<AtomicUsize>,
+}
+
+impl<T> HybridProducer<T> {
+    /// Adds an element to the channel, allocating a node for it if the ring
+    /// buffer is full or the consumer has not yet drained earlier spilled
+    /// elements.
+    pub fn push(&mut self, value: T) {
+        let value = if self.is_spilling() {
+            value
+        } else {
+            match self.ring.push(value) {
+                Ok(()) => return,
+                Err(err) => err.into_inner(),
+            }
+        };
+
+        // The count is incremented before the push, so the consumer's
+        // decrement after popping can never underflow it.
+        self.spilled.fetch_add(1, RELAXED);
+        self.overflow.push(Node::new(value));
+    }
+
+    /// Returns `true` if elements are currently being spilled to the linked
+    /// list, i.e. if spilled elements remain which the consumer has not yet
+    /// popped.
+    pub fn is_spilling(&self) -> bool {
+        // Acquire ordering ensures that once the consumer has drained the
+        // linked list, its pops happen before any later pushes to the ring.
+        self.spilled.load(ACQUIRE) != 0
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+}
+
+impl<T> SpscProducer<T> for HybridProducer<T> {
+    fn try_send(&mut self, value: T) -> Result<(), T> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+/// The consumer half of a [`Hybrid`] channel.
+///
+/// [`Hybrid`]: crate::hybrid::Hybrid
+pub struct HybridConsumer<T> {
+    ring: RingConsumer<T>,
+    overflow: Consumer<T>,
+    spilled: Arc<AtomicUsize>,
+}
+
+impl<T> HybridConsumer<T> {
+    /// Attempts to remove and return an element from the channel. Returns
+    /// `None` if the channel is empty.
+    ///
+    /// Elements which were spilled to the linked list have their nodes freed
+    /// here.
+    pub fn pop(&mut self) -> Option<T> {
+        if let Some(value) = self.ring.pop() {
+            return Some(value);
+        }
+
+        // The producer may have filled the ring and spilled since the ring
+        // was checked. Everything pushed to the ring before a spill is visible
+        // once the spilled node is, and nothing is pushed to the ring while
+        // spilled elements remain, so the ring is checked again.
+        self.overflow.peek()?;
+        if let Some(value) = self.ring.pop() {
+            return Some(value);
+        }
+
+        let node = self.overflow.pop()?;
+        self.spilled.fetch_sub(1, RELEASE);
+        Some(Node::into_inner(node))
+    }
+}
+
+impl<T> SpscConsumer<T> for HybridConsumer<T> {
+    fn try_recv(&mut self) -> Option<T> {
+        self.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn ordered_bursts() {
+        let (mut producer, mut consumer) = Hybrid::with_capacity(8).split();
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..10000 {
+                producer.push(i);
+                if i % 1000 == 0 {
+                    std::thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 10000 {
+            if let Some(value) = consumer.pop() {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+        }
+
+        thread.join().unwrap();
+    }
+}
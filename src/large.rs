@@ -0,0 +1,181 @@
+//! Queues for large elements, stored out of line.
+//!
+//! A [`Queue`] always owns one node more than it has elements: the sentinel
+//! at its front, which has room for a full element. Popping moves the first
+//! element into the sentinel and returns the sentinel's node. For element
+//! types of many kilobytes, such as audio or video buffers, that is a
+//! payload's worth of memory per queue which is never used, and a payload's
+//! worth of copying per pop.
+//!
+//! A [`Large`] queue instead stores each element in a separate allocation
+//! owned by its node, so that the sentinel and the copy made by each pop only
+//! cost a pointer. Nodes keep their payload allocations as they move between
+//! the two halves: the consumer sends nodes it is done with back with
+//! [`LargeConsumer::recycle()`], and [`LargeProducer::alloc()`] reuses them,
+//! writing the new element over the old one in place of allocating.
+//!
+//! ```rust
+//! use llq::large::Large;
+//!
+//! let (mut producer, mut consumer) = Large::<[f32; 16384]>::new().split();
+//!
+//! let node = producer.alloc([0.5; 16384]);
+//! producer.push(node);
+//!
+//! let node = consumer.pop().unwrap();
+//! assert_eq!(node[0], 0.5);
+//! consumer.recycle(node);
+//!
+//! // The node and its payload allocation are reused.
+//! let node = producer.alloc([0.25; 16384]);
+//! assert_eq!(producer.reclaimed(), 1);
+//! producer.push(node);
+//! ```
+//!
+//! [`Queue`]: crate::Queue
+//! [`Large`]: crate::large::Large
+//! [`LargeConsumer::recycle()`]: crate::large::LargeConsumer::recycle
+//! [`LargeProducer::alloc()`]: crate::large::LargeProducer::alloc
+
+use crate::{Consumer, Node, Producer, Queue};
+
+use alloc::boxed::Box;
+
+/// A node of a [`Large`] queue, which owns a separate allocation holding its
+/// element.
+///
+/// [`Large`]: crate::large::Large
+pub type LargeNode<T> = Node<Box<T>>;
+
+/// A queue which stores its elements out of line; see the [module
+/// documentation].
+///
+/// [module documentation]: crate::large
+pub struct Large<T> {
+    queue: Queue<Box<T>>,
+    returned: Queue<Box<T>>,
+}
+
+impl<T> Large<T> {
+    /// Creates a new, empty queue.
+    pub fn new() -> Large<T> {
+        Large { queue: Queue::new(), returned: Queue::new() }
+    }
+
+    /// Splits a queue into producer and consumer halves.
+    pub fn split(self) -> (LargeProducer<T>, LargeConsumer<T>) {
+        let (producer, consumer) = self.queue.split();
+        let (returned_producer, returned_consumer) = self.returned.split();
+        (
+            LargeProducer { producer, returned: returned_consumer, reclaimed: 0 },
+            LargeConsumer { consumer, returned: returned_producer },
+        )
+    }
+}
+
+impl<T> Default for Large<T> {
+    fn default() -> Large<T> {
+        Large::new()
+    }
+}
+
+/// The producer half of a [`Large`] queue.
+///
+/// [`Large`]: crate::large::Large
+pub struct LargeProducer<T> {
+    producer: Producer<Box<T>>,
+    returned: Consumer<Box<T>>,
+    reclaimed: usize,
+}
+
+impl<T> LargeProducer<T> {
+    /// Returns a node holding `value`.
+    ///
+    /// Reuses a node recycled by the consumer, dropping the element it held,
+    /// if there is one, and otherwise allocates a node and its payload.
+    pub fn alloc(&mut self, value: T) -> LargeNode<T> {
+        match self.reclaim() {
+            Some(mut node) => {
+                **node = value;
+                node
+            }
+            None => Node::new(Box::new(value)),
+        }
+    }
+
+    /// Returns a node recycled by the consumer, still holding the element it
+    /// was pushed with, so that it can be overwritten in place. Returns
+    /// `None` if there are none waiting.
+    pub fn reclaim(&mut self) -> Option<LargeNode<T>> {
+        let node = self.returned.pop()?;
+        self.reclaimed = self.reclaimed.wrapping_add(1);
+        Some(node)
+    }
+
+    /// Returns the number of nodes reused from the consumer so far.
+    pub fn reclaimed(&self) -> usize {
+        self.reclaimed
+    }
+
+    /// Pushes a node onto the queue.
+    pub fn push(&mut self, node: LargeNode<T>) {
+        self.producer.push(node);
+    }
+}
+
+/// The consumer half of a [`Large`] queue.
+///
+/// [`Large`]: crate::large::Large
+pub struct LargeConsumer<T> {
+    consumer: Consumer<Box<T>>,
+    returned: Producer<Box<T>>,
+}
+
+impl<T> LargeConsumer<T> {
+    /// Pops a node from the queue, returning `None` if it is empty.
+    ///
+    /// Only the pointer to the element is copied.
+    pub fn pop(&mut self) -> Option<LargeNode<T>> {
+        self.consumer.pop()
+    }
+
+    /// Sends a node back to the producer, which reuses it along with its
+    /// payload allocation. Never allocates, frees or drops an element.
+    pub fn recycle(&mut self, node: LargeNode<T>) {
+        self.returned.push(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::NodeInner;
+
+    use core::mem;
+
+    #[test]
+    fn sentinel_is_small() {
+        type Payload = [u8; 65536];
+        assert!(mem::size_of::<NodeInner<Box<Payload>>>() < 64);
+    }
+
+    #[test]
+    fn reuse_payload() {
+        let (mut producer, mut consumer) = Large::<[u64; 1024]>::new().split();
+
+        let node = producer.alloc([1; 1024]);
+        producer.push(node);
+        let node = consumer.pop().unwrap();
+        assert_eq!(node[1023], 1);
+        let payload = &**node as *const [u64; 1024];
+        consumer.recycle(node);
+
+        let node = producer.alloc([2; 1024]);
+        assert_eq!(&**node as *const [u64; 1024], payload);
+        assert_eq!(producer.reclaimed(), 1);
+        producer.push(node);
+        assert_eq!(consumer.pop().unwrap()[0], 2);
+        assert!(consumer.pop().is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::sync::{AtomicU64, RELAXED};
+use crate::{Consumer, Node};
+
+use std::time::{Duration, Instant};
+
+/// Summary statistics of the time elements spent in a queue between being
+/// pushed and being popped.
+///
+/// Returned by [`Consumer::latency()`].
+///
+/// [`Consumer::latency()`]: crate::Consumer::latency
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Latency {
+    /// The number of elements measured.
+    pub count: u64,
+    /// The shortest measured latency.
+    pub min: Duration,
+    /// The longest measured latency.
+    pub max: Duration,
+    /// The mean of the measured latencies.
+    pub mean: Duration,
+}
+
+/// Running latency statistics, in nanoseconds. Only written by the consumer.
+pub(crate) struct LatencyStats {
+    count: AtomicU64,
+    total: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl LatencyStats {
+    pub(crate) fn new() -> LatencyStats {
+        LatencyStats {
+            count: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, pushed: Instant) {
+        let nanos = pushed.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+
+        // There is only one writer, so read-modify-write operations are not
+        // necessary.
+        let count = self.count.load(RELAXED);
+        self.count.store(count.wrapping_add(1), RELAXED);
+        let total = self.total.load(RELAXED);
+        self.total.store(total.saturating_add(nanos), RELAXED);
+        if nanos < self.min.load(RELAXED) {
+            self.min.store(nanos, RELAXED);
+        }
+        if nanos > self.max.load(RELAXED) {
+            self.max.store(nanos, RELAXED);
+        }
+    }
+
+    fn get(&self) -> Latency {
+        let count = self.count.load(RELAXED);
+        if count == 0 {
+            return Latency::default();
+        }
+
+        Latency {
+            count,
+            min: Duration::from_nanos(self.min.load(RELAXED)),
+            max: Duration::from_nanos(self.max.load(RELAXED)),
+            mean: Duration::from_nanos(self.total.load(RELAXED) / count),
+        }
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Returns statistics of the time elements have spent in the queue between
+    /// being pushed and being popped.
+    ///
+    /// Elements evicted with [`evict_older_than()`] or under the
+    /// [`Overflow::DropOldest`] policy are not measured.
+    ///
+    /// [`evict_older_than()`]: crate::Consumer::evict_older_than
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    pub fn latency(&self) -> Latency {
+        self.queue.latency.get()
+    }
+
+    /// Removes every element at the front of the queue which was pushed more
+    /// than `age` ago, passing each one to `f`, and returns the number of
+    /// elements removed.
+    ///
+    /// Elements are removed in order and removal stops at the first element
+    /// which is recent enough, so stale elements behind it are left in place.
+    /// Elements without a timestamp, such as those put back with
+    /// [`push_front()`], are never considered stale.
+    ///
+    /// [`push_front()`]: crate::Consumer::push_front
+    pub fn evict_older_than<F: FnMut(Node<T>)>(&mut self, age: Duration, mut f: F) -> usize {
+        let cutoff = match Instant::now().checked_sub(age) {
+            Some(cutoff) => cutoff,
+            None => return 0,
+        };
+
+        self.evict();
+
+        let mut count = 0;
+        loop {
+            let pushed = unsafe {
+                let next = self.queue.load_next(self.queue.head.get());
+                if next.is_null() {
+                    break;
+                }
+                (*next).pushed
+            };
+            if pushed.is_none_or(|pushed| pushed >= cutoff) {
+                break;
+            }
+
+            f(self.pop_evicted().unwrap());
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use crate::{Node, Queue};
+
+    use std::time::Duration;
+
+    #[test]
+    fn latency() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        assert_eq!(consumer.latency().count, 0);
+
+        producer.push(Node::new(()));
+        std::thread::sleep(Duration::from_millis(10));
+        producer.push(Node::new(()));
+        consumer.pop().unwrap();
+        consumer.pop().unwrap();
+
+        let latency = consumer.latency();
+        assert_eq!(latency.count, 2);
+        assert!(latency.max >= Duration::from_millis(10));
+        assert!(latency.min <= latency.mean && latency.mean <= latency.max);
+    }
+
+    #[test]
+    fn evict_older_than() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push(Node::new(0));
+        producer.push(Node::new(1));
+        std::thread::sleep(Duration::from_millis(20));
+        producer.push(Node::new(2));
+
+        let mut evicted = std::vec::Vec::new();
+        let count = consumer.evict_older_than(Duration::from_millis(10), |node| {
+            evicted.push(Node::into_inner(node))
+        });
+        assert_eq!(count, 2);
+        assert_eq!(evicted, [0, 1]);
+        assert_eq!(consumer.latency().count, 0);
+        assert_eq!(*consumer.pop().unwrap(), 2);
+        assert_eq!(consumer.latency().count, 1);
+    }
+}
@@ -44,16 +44,315 @@
 //! assert_eq!(*node, 3);
 //! ```
 //!
+//! Elements may borrow data, e.g. to pass references into a buffer to worker
+//! threads spawned with [`std::thread::scope()`]. The queue halves then carry
+//! the borrow's lifetime, so they cannot outlive the data:
+//!
+//! ```rust
+//! use llq::{Node, Queue};
+//!
+//! let samples = vec![0.0f32; 1024];
+//! let (mut producer, mut consumer) = Queue::<&[f32]>::new().split();
+//!
+//! std::thread::scope(|scope| {
+//!     scope.spawn(|| {
+//!         for chunk in samples.chunks(256) {
+//!             producer.push(Node::new(chunk));
+//!         }
+//!     });
+//! });
+//!
+//! assert_eq!(consumer.pop().unwrap().len(), 256);
+//! ```
+//!
+//! # Memory ordering
+//!
+//! Pushing an element stores a pointer to its node with `Release` ordering,
+//! and popping loads it with `Acquire` ordering. Everything the producer did
+//! before pushing an element, including writing the element itself, therefore
+//! happens before everything the consumer does after popping it. Counters
+//! such as [`Producer::lag()`] and [`Consumer::rejected()`] are updated with
+//! `Relaxed` ordering and are only approximate while both halves are in use.
+//!
+//! Enabling the `seqcst` feature upgrades every atomic operation in the crate
+//! to `SeqCst`, for environments which require the conservative setting.
+//!
+//! # Pointer provenance
+//!
+//! The crate never casts integers to pointers. Tags stored in pointers are
+//! applied with the strict provenance APIs, so no configuration is needed to
+//! run code which uses it under Miri with `-Zmiri-strict-provenance`.
+//!
+//! # Large elements
+//!
+//! A queue always owns one node more than it has elements: the sentinel at
+//! its front. Popping moves the first element's value into the sentinel and
+//! returns the sentinel's node, while the first element's node, which the
+//! producer may still be linking onto, becomes the new sentinel. This is what
+//! allows [`Producer::push()`] to be a single atomic store, but it means that
+//! every node, including the sentinel, has room for a full `T`, and that each
+//! pop copies a `T`.
+//!
+//! For large element types, such as buffers of tens of kilobytes, the
+//! [`large`] module provides a queue which stores each element out of line,
+//! so that the sentinel and the pop copy only cost a pointer.
+//!
+//! # Panics
+//!
+//! [`Producer::push()`], [`Consumer::pop()`], their fallible variants such as
+//! [`Producer::try_push()`], and the pool operations [`Pool::try_alloc()`]
+//! and [`SharedPool::try_alloc()`] never panic or abort, and contain no
+//! arithmetic which can overflow, so they cannot take down a thread or a
+//! process built with `panic = "abort"`. The exceptions are user code which
+//! they call: the hooks of an [`observer`], and the `Drop` impl of the
+//! element type where an element is discarded.
+//!
+//! Functions which allocate abort the process if the allocator fails, like
+//! the standard library's collections. [`Node::try_new()`] and
+//! [`Queue::try_new()`] instead return an [`AllocError`], and preallocating
+//! nodes in a [`pool`] avoids allocating on the real-time thread altogether.
+//!
+//! # Cargo features
+//!
+//! - `alloc-constructors` (enabled by default): Enables [`Node::new()`],
+//!   [`Queue::new()`] and the other functions which allocate on their own,
+//!   such as [`Consumer::snapshot()`], along with [`SmallBytes`] and the
+//!   modules built on them. Without it, nodes can only be
+//!   allocated through a [`pool`] or as a [`RawNode`], and queues can only be
+//!   created with [`Queue::from_node()`], so that allocation outside of setup
+//!   code can be ruled out at compile time.
+//! - `std`: Enables functionality which depends on the standard library, such
+//!   as [`Queue::split_fd()`], [`Queue::dump_dot()`] and the [`blocking`],
+//!   [`wal`] and [`registry`] modules, and, along with
+//!   `alloc-constructors`, the [`defer`] module.
+//! - `parking_lot`: Parks the consumers in the [`blocking`] module with
+//!   `parking_lot`'s `Mutex` and `Condvar` instead of the standard library's.
+//!   Implies `std`.
+//! - `mio`: Implements [`mio::event::Source`] for [`FdConsumer`], allowing it
+//!   to be registered with a [`mio::Poll`].
+//! - `timestamps`: Records the time at which each element is pushed and
+//!   keeps statistics of push-to-pop latency, available through
+//!   [`Consumer::latency()`]. Implies `std`.
+//! - `async`: Enables [`Queue::split_async()`] and [`Ring::split_async()`],
+//!   which return queue halves that can be awaited from async code. Together
+//!   with `std`, also enables the byte pipe adapters in the [`futures`]
+//!   module.
+//! - `observer`: Enables [`Queue::set_observer()`], which installs hooks
+//!   called as elements pass through a queue; see the [`observer`] module.
+//!   Without it, the hooks are compiled out.
+//! - `seqcst`: Uses `SeqCst` ordering for all atomic operations; see
+//!   [Memory ordering](#memory-ordering).
+//! - `derive`: Enables [`Channels`], a derive macro which generates a bundle
+//!   of queues with one queue per variant of a message enum. Implies
+//!   `alloc-constructors`.
+//! - `bridge`: Enables the [`bridge`] module, which forwards a queue over a
+//!   byte stream using serde and postcard. Implies `std` and
+//!   `alloc-constructors`.
+//! - `accounting`: Counts the allocations made by the crate and their size,
+//!   globally and per pool; see the [`accounting`] module.
+//! - `leak-check`: Makes dropping a [`pool`] panic if allocations which it
+//!   made have not been returned to it, to catch nodes leaked into forgotten
+//!   containers during testing. Only allocations made by pools are tracked;
+//!   nodes created with `Node::new()` and queues are not.
+//! - `bytemuck`: Enables casting the payload of a `Node<[u8; N]>` to and
+//!   from slices of plain-old-data types with [`bytemuck`], e.g. to move
+//!   blocks of `f32` samples as bytes.
+//! - `embedded-io`: Implements the [`embedded-io`] `Read` and `Write` traits
+//!   for the halves of a [`Ring`] of bytes, so that it can carry a byte
+//!   stream between an interrupt handler and application code on `no_std`
+//!   targets.
+//! - `embedded-io-async`: Implements the [`embedded-io-async`] `Read` and
+//!   `Write` traits for the halves of a [`Ring`] of bytes split with
+//!   [`Ring::split_async()`]. Implies `async` and `embedded-io`.
+//! - `record`: Enables the [`record`] module, which records the elements
+//!   pushed onto a queue with their timing and replays them later. Implies
+//!   `std`, `observer` and `alloc-constructors`.
+//! - `fault-injection`: Enables the [`fault`] module, which makes
+//!   allocations, pools and pops fail on demand so that tests can exercise
+//!   error paths. Implies `std`.
+//! - `arbitrary`: Implements [`arbitrary::Arbitrary`] for nodes, chains and
+//!   queues, and enables the [`model`] module, which checks sequences of
+//!   operations against a reference model. Implies `alloc-constructors`.
+//! - `metrics`: Enables the [`stats`] module, which counts the elements
+//!   passing through queues and periodically publishes their depth,
+//!   throughput and drops through the [`metrics`][metrics-facade] facade.
+//!   Implies `std` and `observer`.
+//! - `shuttle`: Replaces the atomic types used internally with those from
+//!   [shuttle], so that the crate's interleavings can be explored by
+//!   shuttle's randomized schedulers, both in the crate's own tests and in
+//!   the shuttle tests of dependent crates. The queue may then only be used
+//!   inside a shuttle test, so this feature is for testing only; run the
+//!   crate's shuttle tests with `cargo test --features shuttle --lib
+//!   shuttle_`. Implies `std` and `seqcst`, since shuttle only models
+//!   `SeqCst` ordering.
+//!
 //! [`Producer::push()`]: crate::Producer::push
 //! [`Consumer::pop()`]: crate::Consumer::pop
+//! [`std::thread::scope()`]: https://doc.rust-lang.org/std/thread/fn.scope.html
+//! [`Producer::try_push()`]: crate::Producer::try_push
+//! [`Pool::try_alloc()`]: crate::pool::Pool::try_alloc
+//! [`SharedPool::try_alloc()`]: crate::pool::SharedPool::try_alloc
+//! [`Node::try_new()`]: crate::Node::try_new
+//! [`Queue::try_new()`]: crate::Queue::try_new
+//! [`AllocError`]: crate::error::AllocError
+//! [`Producer::lag()`]: crate::Producer::lag
+//! [`Consumer::rejected()`]: crate::Consumer::rejected
 //! [`Node`]: crate::Node
+//! [`Node::new()`]: crate::Node::new
+//! [`Queue::new()`]: crate::Queue::new
+//! [`Queue::from_node()`]: crate::Queue::from_node
+//! [`pool`]: crate::pool
+//! [`RawNode`]: crate::RawNode
+//! [`Queue::split_fd()`]: crate::Queue::split_fd
+//! [`wal`]: crate::wal
+//! [`registry`]: crate::registry
+//! [`defer`]: crate::defer
+//! [`blocking`]: crate::blocking
+//! [`Queue::set_observer()`]: crate::Queue::set_observer
+//! [`observer`]: crate::observer
+//! [`Queue::split_async()`]: crate::Queue::split_async
+//! [`futures`]: crate::futures
+//! [`bridge`]: crate::bridge
+//! [`Channels`]: https://docs.rs/llq-derive/0.1/llq_derive/derive.Channels.html
+//! [`Consumer::latency()`]: crate::Consumer::latency
+//! [`accounting`]: crate::accounting
+//! [`FdConsumer`]: crate::fd::FdConsumer
+//! [`embedded-io`]: https://docs.rs/embedded-io/0.6
+//! [`Ring`]: crate::ring::Ring
+//! [`Ring::split_async()`]: crate::ring::Ring::split_async
+//! [`embedded-io-async`]: https://docs.rs/embedded-io-async/0.6
+//! [`record`]: crate::record
+//! [`fault`]: crate::fault
+//! [`arbitrary::Arbitrary`]: https://docs.rs/arbitrary/1/arbitrary/trait.Arbitrary.html
+//! [`model`]: crate::model
+//! [`stats`]: crate::stats
+//! [metrics-facade]: https://docs.rs/metrics/0.24
+//! [`Queue::dump_dot()`]: crate::Queue::dump_dot
+//! [`mio::event::Source`]: https://docs.rs/mio/1/mio/event/trait.Source.html
+//! [`mio::Poll`]: https://docs.rs/mio/1/mio/struct.Poll.html
+//! [shuttle]: https://docs.rs/shuttle/0.9
+//! [`bytemuck`]: https://docs.rs/bytemuck/1
+//! [`large`]: crate::large
+//! [`Consumer::snapshot()`]: crate::Consumer::snapshot
+//! [`SmallBytes`]: crate::SmallBytes
 
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+// Allows the code generated by the derive macros to be used in this crate's
+// own tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as llq;
+
+#[cfg(feature = "accounting")]
+pub mod accounting;
+pub mod arena;
+pub mod audio;
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod blocking;
+#[cfg(feature = "bridge")]
+pub mod bridge;
+#[cfg(feature = "alloc-constructors")]
+pub mod buffer;
+mod builder;
+#[cfg(feature = "alloc-constructors")]
+pub mod bus;
+#[cfg(feature = "alloc-constructors")]
+pub mod cancel;
+pub mod chain;
+pub mod dead_letter;
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+pub mod defer;
+pub mod dispatch;
+#[cfg(feature = "std")]
+mod dot;
+#[cfg(feature = "embedded-io")]
+mod embedded;
+pub mod error;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+#[cfg(all(feature = "std", unix))]
+pub mod fd;
+pub mod ffi;
+pub mod frame;
+#[cfg(feature = "async")]
+pub mod futures;
+#[cfg(feature = "alloc-constructors")]
+pub mod group;
+#[cfg(feature = "alloc-constructors")]
+pub mod handoff;
+mod heap;
+#[cfg(feature = "alloc-constructors")]
+pub mod hybrid;
+#[cfg(feature = "alloc-constructors")]
+pub mod large;
+#[cfg(feature = "timestamps")]
+mod latency;
+pub mod micro;
+#[cfg(feature = "arbitrary")]
+pub mod model;
+mod node_str;
+#[cfg(feature = "observer")]
+pub mod observer;
+#[cfg(feature = "bytemuck")]
+mod pod;
+pub mod pool;
+pub mod rate;
+mod raw_node;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "std")]
+pub mod registry;
+pub mod ring;
+pub mod scatter;
+pub mod scoped;
+pub mod signal;
+#[cfg(feature = "alloc-constructors")]
+mod small_bytes;
+pub mod spsc;
+#[cfg(feature = "alloc-constructors")]
+pub mod stage;
+#[cfg(feature = "alloc-constructors")]
+mod static_queue;
+#[cfg(feature = "metrics")]
+pub mod stats;
+#[cfg(feature = "alloc-constructors")]
+pub mod swap;
+mod sync;
+mod sync_consumer;
+pub mod tee;
+#[cfg(feature = "alloc-constructors")]
+pub mod timer;
+#[cfg(any(feature = "std", feature = "async"))]
+mod waiting;
+#[cfg(all(feature = "std", feature = "alloc-constructors"))]
+pub mod wal;
+
+pub use builder::QueueBuilder;
+pub use chain::{Chain, FreeList};
+pub use heap::set_alloc_error_hook;
+#[cfg(feature = "timestamps")]
+pub use latency::Latency;
+#[cfg(feature = "derive")]
+pub use llq_derive::Channels;
+pub use node_str::{NodeStr, StrWriter};
+pub use raw_node::RawNode;
+#[cfg(feature = "alloc-constructors")]
+pub use small_bytes::SmallBytes;
+#[cfg(feature = "alloc-constructors")]
+pub use static_queue::StaticQueue;
+pub use sync_consumer::SyncConsumer;
 
+use alloc::alloc::{handle_alloc_error, Layout};
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+#[cfg(feature = "alloc-constructors")]
+use alloc::vec::Vec;
 use core::cell::Cell;
 use core::marker::PhantomData;
 use core::mem;
@@ -61,7 +360,9 @@ use core::mem::MaybeUninit;
 use core::ops::{Deref, DerefMut};
 use core::ptr;
 use core::ptr::NonNull;
-use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::error::PushError;
+use crate::sync::{AtomicPtr, AtomicUsize, ACQUIRE, ACQ_REL, RELAXED, RELEASE};
 
 /// An individual node which may be pushed onto and popped from a [`Queue`].
 ///
@@ -74,34 +375,279 @@ pub struct Node<T> {
 unsafe impl<T: Send> Send for Node<T> {}
 unsafe impl<T: Sync> Sync for Node<T> {}
 
+// Like a `Box`, a node owns its value through a pointer, so moving the node
+// never moves the value.
+impl<T> Unpin for Node<T> {}
+
+// The layout is fixed so that nodes can be passed between binaries by the
+// `ffi` module.
+#[repr(C)]
 struct NodeInner<T> {
     next: AtomicPtr<NodeInner<T>>,
+    #[cfg(feature = "timestamps")]
+    pushed: Option<std::time::Instant>,
     data: MaybeUninit<T>,
 }
 
+impl<T> NodeInner<T> {
+    /// Allocates a node containing the given data.
+    #[cfg(feature = "alloc-constructors")]
+    fn alloc(data: MaybeUninit<T>) -> NonNull<NodeInner<T>> {
+        let layout = Layout::new::<NodeInner<T>>();
+        match NodeInner::try_alloc(data) {
+            Ok(node) => node,
+            Err(_) => handle_alloc_error(layout),
+        }
+    }
+
+    /// Allocates a node containing the given data. Returns the data if the
+    /// allocation fails.
+    #[cfg(feature = "alloc-constructors")]
+    fn try_alloc(data: MaybeUninit<T>) -> Result<NonNull<NodeInner<T>>, MaybeUninit<T>> {
+        match NodeInner::try_alloc_uninit() {
+            Some(node) => {
+                unsafe { ptr::addr_of_mut!((*node.as_ptr()).data).write(data) };
+                Ok(node)
+            }
+            None => Err(data),
+        }
+    }
+
+    /// Allocates a node whose data is uninitialized. Unlike passing
+    /// `MaybeUninit::uninit()` to [`alloc()`], this never moves a `T`, so
+    /// large data does not pass through the stack.
+    ///
+    /// [`alloc()`]: NodeInner::alloc
+    fn alloc_uninit() -> NonNull<NodeInner<T>> {
+        match NodeInner::try_alloc_uninit() {
+            Some(node) => node,
+            None => handle_alloc_error(Layout::new::<NodeInner<T>>()),
+        }
+    }
+
+    /// Allocates a node whose data is uninitialized. Returns `None` if the
+    /// allocation fails.
+    fn try_alloc_uninit() -> Option<NonNull<NodeInner<T>>> {
+        let node = heap::try_allocate(Layout::new::<NodeInner<T>>()) as *mut NodeInner<T>;
+        if node.is_null() {
+            return None;
+        }
+
+        unsafe {
+            ptr::addr_of_mut!((*node).next).write(AtomicPtr::new(ptr::null_mut()));
+            #[cfg(feature = "timestamps")]
+            ptr::addr_of_mut!((*node).pushed).write(None);
+
+            Some(NonNull::new_unchecked(node))
+        }
+    }
+
+    /// Deallocates a node without dropping its data.
+    unsafe fn free(node: *mut NodeInner<T>) {
+        heap::deallocate(node as *mut u8, Layout::new::<NodeInner<T>>());
+    }
+}
+
 impl<T> Node<T> {
     /// Allocates a new node containing the given value.
+    #[cfg(feature = "alloc-constructors")]
     pub fn new(data: T) -> Node<T> {
-        Node {
-            inner: unsafe {
-                NonNull::new_unchecked(Box::into_raw(Box::new(NodeInner {
-                    next: AtomicPtr::new(ptr::null_mut()),
-                    data: MaybeUninit::new(data),
-                })))
-            },
-            phantom: PhantomData,
+        Node { inner: NodeInner::alloc(MaybeUninit::new(data)), phantom: PhantomData }
+    }
+
+    /// Allocates a new node containing the given value. Returns the value in
+    /// an error instead of aborting if the allocation fails.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn try_new(data: T) -> Result<Node<T>, error::AllocError<T>> {
+        match NodeInner::try_alloc(MaybeUninit::new(data)) {
+            Ok(inner) => Ok(Node { inner, phantom: PhantomData }),
+            Err(data) => Err(error::AllocError::OutOfMemory(unsafe { data.assume_init() })),
         }
     }
 
+    /// Allocates a new node and initializes its value with the result of
+    /// `f`, which is written directly into the allocation.
+    ///
+    /// Unlike `Node::new(f())`, this lets the compiler construct a large
+    /// value, such as a multi-kilobyte array, in place instead of on the
+    /// stack, although it does not guarantee that it does so.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn new_with<F: FnOnce() -> T>(f: F) -> Node<T> {
+        let mut node = Node::<T>::new_uninit();
+        node.write(f());
+        unsafe { Node::assume_init(node) }
+    }
+
+    /// Allocates a new node whose value is uninitialized. The value can be
+    /// written in place, after which [`Node::assume_init()`] converts the
+    /// node back.
+    ///
+    /// [`Node::assume_init()`]: crate::Node::assume_init
+    #[cfg(feature = "alloc-constructors")]
+    pub fn new_uninit() -> Node<MaybeUninit<T>> {
+        let inner = NodeInner::<T>::alloc_uninit().cast::<NodeInner<MaybeUninit<T>>>();
+        Node { inner, phantom: PhantomData }
+    }
+
+    /// Allocates a new node whose value is filled with zero bytes, without
+    /// constructing it on the stack.
+    ///
+    /// ```rust
+    /// use llq::Node;
+    ///
+    /// let node = Node::<[f32; 65536]>::new_zeroed();
+    /// // Zero bytes are a valid array of floats.
+    /// let node = unsafe { Node::assume_init(node) };
+    /// assert!(node.iter().all(|&sample| sample == 0.0));
+    /// ```
+    #[cfg(feature = "alloc-constructors")]
+    pub fn new_zeroed() -> Node<MaybeUninit<T>> {
+        let mut node = Node::<T>::new_uninit();
+        unsafe { Node::as_mut_ptr(&mut node).write_bytes(0, 1) };
+        node
+    }
+
     /// Deallocates a `Node` and returns the inner value.
     pub fn into_inner(this: Node<T>) -> T {
         unsafe {
             let data = ptr::read(this.inner.as_ref().data.as_ptr());
-            drop(Box::from_raw(this.inner.as_ptr()));
+            NodeInner::free(this.inner.as_ptr());
             mem::forget(this);
             data
         }
     }
+
+    /// Returns a pointer to the node's value.
+    pub fn as_ptr(this: &Node<T>) -> *const T {
+        unsafe { this.inner.as_ref().data.as_ptr() }
+    }
+
+    /// Returns a mutable pointer to the node's value.
+    pub fn as_mut_ptr(this: &mut Node<T>) -> *mut T {
+        unsafe { this.inner.as_mut().data.as_mut_ptr() }
+    }
+
+    /// Converts the node into a node whose value is uninitialized, without
+    /// dropping the value.
+    ///
+    /// This can be used to transfer ownership of the value elsewhere, e.g. by
+    /// reading it through [`Node::as_ptr()`] beforehand, while keeping the
+    /// allocation.
+    ///
+    /// [`Node::as_ptr()`]: crate::Node::as_ptr
+    pub fn forget_value(this: Node<T>) -> Node<MaybeUninit<T>> {
+        let inner = this.inner.cast::<NodeInner<MaybeUninit<T>>>();
+        mem::forget(this);
+        Node { inner, phantom: PhantomData }
+    }
+
+    /// Converts the node's value with `f`, reusing the node's allocation for
+    /// the result if a node of `U` has the same size and alignment as a node
+    /// of `T`, and allocating a new node otherwise.
+    ///
+    /// ```rust
+    /// use llq::Node;
+    ///
+    /// let node = Node::new(440u32);
+    /// let node = Node::map(node, |hz| hz as f32 / 2.0);
+    /// assert_eq!(*node, 220.0);
+    /// ```
+    #[cfg(feature = "alloc-constructors")]
+    pub fn map<U, F: FnOnce(T) -> U>(this: Node<T>, f: F) -> Node<U> {
+        let (value, empty) = Node::take(this);
+        match Node::recast::<U>(empty) {
+            Ok(mut node) => {
+                node.write(f(value));
+                unsafe { Node::assume_init(node) }
+            }
+            Err(empty) => {
+                drop(empty);
+                Node::new(f(value))
+            }
+        }
+    }
+
+    /// Moves the value out of the node, returning it along with the empty
+    /// node.
+    #[cfg(feature = "alloc-constructors")]
+    pub(crate) fn take(this: Node<T>) -> (T, Node<MaybeUninit<T>>) {
+        let value = unsafe { ptr::read(Node::as_ptr(&this)) };
+        (value, Node::forget_value(this))
+    }
+}
+
+impl<T> Node<MaybeUninit<T>> {
+    /// Converts a node with an uninitialized value into a node with an
+    /// initialized value.
+    ///
+    /// # Safety
+    ///
+    /// The node's value must have been initialized.
+    pub unsafe fn assume_init(this: Node<MaybeUninit<T>>) -> Node<T> {
+        let inner = this.inner.cast::<NodeInner<T>>();
+        mem::forget(this);
+        Node { inner, phantom: PhantomData }
+    }
+
+    /// Converts an empty node into an empty node for values of type `U`,
+    /// if their nodes have the same layout. Returns the node unchanged
+    /// otherwise.
+    #[cfg(feature = "alloc-constructors")]
+    pub(crate) fn recast<U>(
+        this: Node<MaybeUninit<T>>,
+    ) -> Result<Node<MaybeUninit<U>>, Node<MaybeUninit<T>>> {
+        if Layout::new::<NodeInner<U>>() != Layout::new::<NodeInner<T>>() {
+            return Err(this);
+        }
+
+        let inner = this.inner.cast::<NodeInner<MaybeUninit<U>>>();
+        mem::forget(this);
+        Ok(Node { inner, phantom: PhantomData })
+    }
+}
+
+impl Node<Box<[MaybeUninit<u8>]>> {
+    /// Allocates a new node holding a buffer of `len` bytes which are left
+    /// uninitialized, for payloads whose size is only known at runtime. The
+    /// bytes can be written in place, e.g. by reading from a socket, after
+    /// which [`Node::assume_init_slice()`] converts the node.
+    ///
+    /// Only the buffer's pointer and length are stored in the node.
+    ///
+    /// ```rust
+    /// use llq::Node;
+    ///
+    /// let mut node = Node::new_uninit_slice(5);
+    /// for (byte, &value) in node.iter_mut().zip(b"hello") {
+    ///     byte.write(value);
+    /// }
+    /// let node = unsafe { Node::assume_init_slice(node) };
+    /// assert_eq!(&node[..], b"hello");
+    /// ```
+    ///
+    /// [`Node::assume_init_slice()`]: crate::Node::assume_init_slice
+    #[cfg(feature = "alloc-constructors")]
+    pub fn new_uninit_slice(len: usize) -> Node<Box<[MaybeUninit<u8>]>> {
+        Node::new(Box::new_uninit_slice(len))
+    }
+
+    /// Converts a node holding an uninitialized buffer into a node holding
+    /// the initialized bytes. This is [`Node::assume_init()`] for nodes from
+    /// [`Node::new_uninit_slice()`], whose buffer is boxed.
+    ///
+    /// # Safety
+    ///
+    /// Every byte of the buffer must have been initialized.
+    ///
+    /// [`Node::assume_init()`]: crate::Node::assume_init
+    /// [`Node::new_uninit_slice()`]: crate::Node::new_uninit_slice
+    pub unsafe fn assume_init_slice(this: Node<Box<[MaybeUninit<u8>]>>) -> Node<Box<[u8]>> {
+        // `MaybeUninit<u8>` has the same layout as `u8`, so the boxes, and
+        // with them the nodes, have the same layout.
+        let inner = this.inner.cast::<NodeInner<Box<[u8]>>>();
+        mem::forget(this);
+        Node { inner, phantom: PhantomData }
+    }
 }
 
 impl<T> Deref for Node<T> {
@@ -122,14 +668,63 @@ impl<T> Drop for Node<T> {
     fn drop(&mut self) {
         unsafe {
             ptr::drop_in_place(self.inner.as_mut().data.as_mut_ptr());
-            drop(Box::from_raw(self.inner.as_ptr()));
+            NodeInner::free(self.inner.as_ptr());
         }
     }
 }
 
+/// The policy applied by [`Producer::try_push()`] when a queue created with
+/// [`Queue::with_capacity()`] is full.
+///
+/// [`Producer::try_push()`]: crate::Producer::try_push
+/// [`Queue::with_capacity()`]: crate::Queue::with_capacity
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Overflow {
+    /// The new element is rejected and its node is handed back to the
+    /// producer.
+    Reject,
+    /// The new element is added to the queue, and the oldest element is
+    /// discarded to make room for it.
+    ///
+    /// Since only the consumer may remove elements from the queue, discarded
+    /// elements are removed lazily by [`Consumer::pop()`]. Their nodes are not
+    /// deallocated, but are retained by the consumer so that they can be
+    /// reused; see [`Consumer::take_evicted()`].
+    ///
+    /// [`Consumer::pop()`]: crate::Consumer::pop
+    /// [`Consumer::take_evicted()`]: crate::Consumer::take_evicted
+    DropOldest,
+    /// The new element is rejected and its node is handed back to the
+    /// producer, and the rejection is recorded in a counter which can be read
+    /// with [`Consumer::rejected()`].
+    ///
+    /// [`Consumer::rejected()`]: crate::Consumer::rejected
+    DropNewest,
+}
+
 /// A wait-free SPSC linked-list queue.
 pub struct Queue<T> {
     head: Cell<*mut NodeInner<T>>,
+    len: AtomicUsize,
+    rejected: AtomicUsize,
+    counted: bool,
+    capacity: Option<usize>,
+    overflow: Overflow,
+    #[cfg(feature = "timestamps")]
+    latency: latency::LatencyStats,
+    discard: Option<Box<dyn FnMut(T) + Send>>,
+    #[cfg(feature = "observer")]
+    observer: Option<Box<dyn observer::Observer<T>>>,
+    reclaim: Option<Arc<pool::SharedPool<T>>>,
+    retractable: bool,
+    retract: AtomicPtr<NodeInner<T>>,
+    retractions: AtomicUsize,
+    acked: AtomicUsize,
+    credits: AtomicUsize,
+    // The number of live halves of a queue in a `ChannelGroup`; unused
+    // otherwise.
+    halves: AtomicUsize,
     phantom: PhantomData<T>,
 }
 
@@ -137,182 +732,2204 @@ unsafe impl<T: Send> Send for Queue<T> {}
 
 impl<T> Queue<T> {
     /// Creates a new queue.
+    #[cfg(feature = "alloc-constructors")]
     pub fn new() -> Queue<T> {
-        let node = Box::into_raw(Box::new(NodeInner {
-            next: AtomicPtr::new(ptr::null_mut()),
-            data: MaybeUninit::uninit(),
-        }));
+        Queue::from_sentinel(NodeInner::alloc_uninit().as_ptr())
+    }
 
-        Queue { head: Cell::new(node), phantom: PhantomData }
+    /// Creates a new queue. Returns an error instead of aborting if the
+    /// sentinel node cannot be allocated.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn try_new() -> Result<Queue<T>, error::AllocError<()>> {
+        match NodeInner::try_alloc_uninit() {
+            Some(node) => Ok(Queue::from_sentinel(node.as_ptr())),
+            None => Err(error::AllocError::OutOfMemory(())),
+        }
     }
 
-    /// Splits a queue into its producer and consumer halves.
-    pub fn split(self) -> (Producer<T>, Consumer<T>) {
-        let queue = Arc::new(self);
+    /// Creates a new queue which uses the allocation of `node` as its sentinel
+    /// node, returning the node's value.
+    ///
+    /// Unlike [`Queue::new()`], this does not allocate, so nodes from a
+    /// [`Pool`] can be used to create queues.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use llq::pool::SharedPool;
+    /// use llq::Queue;
+    ///
+    /// let pool = Arc::new(SharedPool::with_capacity(4));
+    /// let (mut queue, _) = Queue::from_node(pool.try_alloc(0).ok().unwrap());
+    /// // Return the sentinel to the pool when the queue is dropped.
+    /// queue.set_reclaim_pool(pool.clone());
+    /// let (mut producer, mut consumer) = queue.split();
+    ///
+    /// producer.push(pool.try_alloc(1).ok().unwrap());
+    /// let node = consumer.pop().unwrap();
+    /// assert_eq!(pool.recycle(node), 1);
+    /// ```
+    ///
+    /// [`Queue::new()`]: crate::Queue::new
+    /// [`Pool`]: crate::pool::Pool
+    pub fn from_node(node: Node<T>) -> (Queue<T>, T) {
+        let inner = node.inner.as_ptr();
+        mem::forget(node);
 
-        let producer = Producer { queue: queue.clone(), tail: queue.head.get() };
-        let consumer = Consumer { queue };
+        unsafe {
+            let value = ptr::read((*inner).data.as_ptr());
+            (*inner).next.store(ptr::null_mut(), RELAXED);
+            #[cfg(feature = "timestamps")]
+            {
+                (*inner).pushed = None;
+            }
 
-        (producer, consumer)
+            (Queue::from_sentinel(inner), value)
+        }
     }
-}
 
-impl<T> Drop for Queue<T> {
-    fn drop(&mut self) {
-        unsafe {
-            let head = self.head.get();
-            let mut current = (*head).next.load(Ordering::Relaxed);
+    fn from_sentinel(node: *mut NodeInner<T>) -> Queue<T> {
+        Queue {
+            head: Cell::new(node),
+            len: AtomicUsize::new(0),
+            rejected: AtomicUsize::new(0),
+            counted: false,
+            capacity: None,
+            overflow: Overflow::Reject,
+            #[cfg(feature = "timestamps")]
+            latency: latency::LatencyStats::new(),
+            discard: None,
+            #[cfg(feature = "observer")]
+            observer: None,
+            reclaim: None,
+            retractable: false,
+            retract: AtomicPtr::new(ptr::null_mut()),
+            retractions: AtomicUsize::new(0),
+            acked: AtomicUsize::new(0),
+            credits: AtomicUsize::new(0),
+            halves: AtomicUsize::new(0),
+            phantom: PhantomData,
+        }
+    }
 
-            drop(Box::from_raw(head));
+    /// Creates a new queue with a soft limit of `capacity` elements.
+    ///
+    /// When the queue is full, [`Producer::try_push()`] applies the given
+    /// [`Overflow`] policy. [`Producer::push()`] ignores the limit and always
+    /// adds the element to the queue.
+    ///
+    /// The number of elements in the queue is tracked with an atomic counter,
+    /// which is updated on every push and pop.
+    ///
+    /// [`Producer::try_push()`]: crate::Producer::try_push
+    /// [`Producer::push()`]: crate::Producer::push
+    /// [`Overflow`]: crate::Overflow
+    #[cfg(feature = "alloc-constructors")]
+    pub fn with_capacity(capacity: usize, overflow: Overflow) -> Queue<T> {
+        let mut queue = Queue::new();
+        queue.counted = true;
+        queue.capacity = Some(capacity);
+        queue.overflow = overflow;
+        queue
+    }
 
-            while !current.is_null() {
-                let next = (*current).next.load(Ordering::Relaxed);
-                ptr::drop_in_place((*current).data.as_mut_ptr());
-                drop(Box::from_raw(current));
-                current = next;
-            }
-        }
+    /// Creates a new queue which keeps track of the number of elements it
+    /// contains, so that the producer can query how far behind the consumer is
+    /// with [`Producer::lag()`].
+    ///
+    /// The count is maintained with an atomic counter, which is updated on
+    /// every push and pop.
+    ///
+    /// [`Producer::lag()`]: crate::Producer::lag
+    #[cfg(feature = "alloc-constructors")]
+    pub fn with_counter() -> Queue<T> {
+        let mut queue = Queue::new();
+        queue.counted = true;
+        queue
     }
-}
 
-/// The consumer half of a [`Queue`].
-///
-/// [`Queue`]: crate::Queue
-pub struct Consumer<T> {
-    queue: Arc<Queue<T>>,
-}
+    /// Sets a handler which is called with each element still in the queue
+    /// when it is dropped, instead of dropping the elements directly.
+    ///
+    /// This can be used to log or persist work which was never consumed.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn set_discard_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        self.discard = Some(Box::new(handler));
+    }
 
-unsafe impl<T: Send> Send for Consumer<T> {}
+    /// Installs an [`Observer`] which is called as elements are pushed,
+    /// popped, and dropped unconsumed, replacing any previous observer.
+    ///
+    /// [`Observer`]: crate::observer::Observer
+    #[cfg(feature = "observer")]
+    pub fn set_observer<O: observer::Observer<T> + 'static>(&mut self, observer: O) {
+        self.observer = Some(Box::new(observer));
+    }
 
-impl<T> Consumer<T> {
-    /// Attempts to remove and return an element from the queue. Returns `None`
-    /// if the queue is empty.
-    pub fn pop(&mut self) -> Option<Node<T>> {
-        unsafe {
-            let head = self.queue.head.get();
-            let next = (*head).next.load(Ordering::Acquire);
+    /// Sets a pool which receives the node allocations still owned by the
+    /// queue when it is dropped, instead of deallocating them.
+    ///
+    /// The values in any unconsumed nodes are dropped, or passed to the
+    /// handler set with [`set_discard_handler()`], before their allocations
+    /// are added to the pool.
+    ///
+    /// [`set_discard_handler()`]: crate::Queue::set_discard_handler
+    pub fn set_reclaim_pool(&mut self, pool: Arc<pool::SharedPool<T>>) {
+        self.reclaim = Some(pool);
+    }
 
-            if !next.is_null() {
-                ptr::copy_nonoverlapping((*next).data.as_ptr(), (*head).data.as_mut_ptr(), 1);
-                (*head).next.store(ptr::null_mut(), Ordering::Relaxed);
+    /// Allows the producer to take back the most recently pushed element with
+    /// [`Producer::try_retract()`].
+    ///
+    /// On a queue with retraction enabled, pushes perform an additional atomic
+    /// store, and the consumer must claim each element with a compare-and-swap
+    /// before it can pop or inspect it. Pops remain lock-free, but are no
+    /// longer wait-free.
+    ///
+    /// [`Producer::try_retract()`]: crate::Producer::try_retract
+    pub fn enable_retraction(&mut self) {
+        self.retractable = true;
+    }
+
+    /// Sets the number of credits available to [`Producer::push_with_credit()`]
+    /// before the consumer grants any with [`Consumer::grant()`].
+    ///
+    /// [`Producer::push_with_credit()`]: crate::Producer::push_with_credit
+    /// [`Consumer::grant()`]: crate::Consumer::grant
+    pub fn set_credits(&mut self, credits: usize) {
+        *self.credits.get_mut() = credits;
+    }
 
-                self.queue.head.set(next);
+    /// Loads the successor of `node`, a node owned by the consumer.
+    ///
+    /// On a queue with retraction enabled, the successor is claimed before it
+    /// is returned, after which the producer can no longer
+    /// retract it. Returns null if there is no successor, or if it is being
+    /// retracted.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the consumer.
+    unsafe fn load_next(&self, node: *mut NodeInner<T>) -> *mut NodeInner<T> {
+        if !self.retractable {
+            let next = (*node).next.load(ACQUIRE);
+            return if is_end(next) { ptr::null_mut() } else { next };
+        }
 
-                return Some(Node { inner: NonNull::new_unchecked(head), phantom: PhantomData });
+        loop {
+            // The count must be loaded before the successor, so that any
+            // retraction of the loaded successor is reflected in it.
+            let retractions = self.retractions.load(ACQUIRE);
+            let next = (*node).next.load(ACQUIRE);
+            if is_end(next) {
+                return ptr::null_mut();
             }
 
-            None
+            // The node must not be dereferenced before it has been claimed,
+            // since the producer may have retracted and freed it.
+            match self.retract.compare_exchange(next, ptr::null_mut(), ACQ_REL, ACQUIRE) {
+                Ok(_) => {
+                    // If a retracted node's allocation was reused for a node
+                    // which has been stored in the slot but not linked yet,
+                    // the pointer loaded above is stale. Claiming the slot
+                    // then only prevents that node from being retracted.
+                    if (*node).next.load(ACQUIRE) == next {
+                        return next;
+                    }
+                }
+                Err(current) if current == retracting(next) => return ptr::null_mut(),
+                Err(_) => {
+                    // Either a newer node has been pushed, or the slot was
+                    // cleared, and the node can no longer be retracted. This
+                    // only holds if it was not retracted (and its allocation
+                    // pushed again) since it was loaded, which is detected by
+                    // a change in the retraction count.
+                    if self.retractions.load(ACQUIRE) == retractions {
+                        return next;
+                    }
+                }
+            }
         }
     }
-}
 
-/// The producer half of a [`Queue`].
-///
-/// [`Queue`]: crate::Queue
-pub struct Producer<T> {
-    #[allow(unused)]
-    queue: Arc<Queue<T>>,
-    tail: *mut NodeInner<T>,
-}
+    /// Deallocates a node owned by the queue, or adds it to the reclaim pool.
+    unsafe fn free_node(&self, node: *mut NodeInner<T>) {
+        match &self.reclaim {
+            Some(pool) => pool.reclaim(node),
+            None => NodeInner::free(node),
+        }
+    }
 
-unsafe impl<T: Send> Send for Producer<T> {}
+    fn is_counted(&self) -> bool {
+        self.counted
+    }
 
-impl<T> Producer<T> {
-    /// Adds an element to the queue.
-    pub fn push(&mut self, node: Node<T>) {
-        unsafe {
-            let node_ptr = node.inner.as_ptr();
-            mem::forget(node);
+    /// Finds the last node and the number of elements before it, clearing the
+    /// tag left by a previous producer, if any.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called while the queue has a producer.
+    unsafe fn take_tail(&self) -> (*mut NodeInner<T>, usize) {
+        let mut tail = self.head.get();
+        let mut len = 0;
+        loop {
+            let next = (*tail).next.load(RELAXED);
+            if is_end(next) {
+                break;
+            }
+            tail = next;
+            len += 1;
+        }
+
+        (*tail).next.store(ptr::null_mut(), RELAXED);
+        (tail, len)
+    }
 
-            let tail = &*self.tail;
-            tail.next.store(node_ptr, Ordering::Release);
+    /// Links `node` after `tail`, the last node, and returns the new last
+    /// node.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the producer.
+    unsafe fn link(&self, tail: *mut NodeInner<T>, node: Node<T>) -> *mut NodeInner<T> {
+        let node_ptr = node.inner.as_ptr();
+        mem::forget(node);
 
-            self.tail = node_ptr;
+        if self.is_counted() {
+            self.len.fetch_add(1, RELAXED);
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        #[cfg(feature = "timestamps")]
+        {
+            (*node_ptr).pushed = Some(std::time::Instant::now());
+        }
 
-    extern crate std;
+        #[cfg(feature = "observer")]
+        if let Some(observer) = &self.observer {
+            observer.on_push(&*(*node_ptr).data.as_ptr());
+        }
 
-    #[test]
-    fn multithreaded() {
-        let (mut producer, mut consumer) = Queue::new().split();
+        if self.retractable {
+            self.retract.store(node_ptr, RELEASE);
+        }
 
-        let thread1 = std::thread::spawn(move || {
-            for _ in 0..10000 {
-                producer.push(Node::new(false));
+        (*tail).next.store(node_ptr, RELEASE);
+        node_ptr
+    }
+
+    /// Removes and returns the element at the front of the queue.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called concurrently with any other operation on the front
+    /// of the queue.
+    unsafe fn pop(&self) -> Option<Node<T>> {
+        let head = self.head.get();
+        let next = self.load_next(head);
+
+        if !next.is_null() {
+            #[cfg(feature = "timestamps")]
+            if let Some(pushed) = (*next).pushed {
+                self.latency.record(pushed);
             }
 
-            producer.push(Node::new(true));
-        });
+            ptr::copy_nonoverlapping((*next).data.as_ptr(), (*head).data.as_mut_ptr(), 1);
+            (*head).next.store(ptr::null_mut(), RELAXED);
 
-        let thread2 = std::thread::spawn(move || {
-            let mut counter = 0;
+            #[cfg(feature = "observer")]
+            if let Some(observer) = &self.observer {
+                observer.on_pop(&*(*head).data.as_ptr());
+            }
 
-            loop {
-                if let Some(node) = consumer.pop() {
-                    if *node {
-                        break;
-                    } else {
-                        counter += 1;
-                    }
-                }
+            self.head.set(next);
+
+            if self.is_counted() {
+                self.len.fetch_sub(1, RELAXED);
             }
 
-            assert_eq!(counter, 10000);
-        });
+            return Some(Node { inner: NonNull::new_unchecked(head), phantom: PhantomData });
+        }
 
-        thread1.join().unwrap();
-        thread2.join().unwrap();
+        None
     }
 
-    #[test]
-    fn multiple_queues() {
-        let (mut producer1, mut consumer1) = Queue::new().split();
-        let (mut producer2, mut consumer2) = Queue::new().split();
+    /// Creates a new queue containing the given nodes, in order.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn with_initial<I: IntoIterator<Item = Node<T>>>(nodes: I) -> Queue<T> {
+        let queue = Queue::new();
 
-        for _ in 0..10000 {
-            producer1.push(Node::new(()));
-        }
+        let mut tail = queue.head.get();
+        for node in nodes {
+            let node_ptr = node.inner.as_ptr();
+            mem::forget(node);
 
-        let mut counter = 0;
-        while let Some(node) = consumer1.pop() {
-            producer2.push(node);
-            counter += 1;
+            unsafe { (*tail).next.store(node_ptr, RELAXED) };
+            tail = node_ptr;
         }
-        assert_eq!(counter, 10000);
 
-        let mut counter = 0;
-        while let Some(_) = consumer2.pop() {
-            counter += 1;
-        }
-        assert_eq!(counter, 10000);
+        queue
     }
 
-    #[test]
-    fn drop_occurs() {
-        struct S(Arc<Cell<usize>>);
+    /// Creates a new queue containing the values from a snapshot, in order.
+    ///
+    /// A node is allocated for each value.
+    ///
+    /// [`Consumer::snapshot()`] and [`Consumer::visit()`] can be used to take
+    /// a snapshot of a queue's contents.
+    ///
+    /// [`Consumer::snapshot()`]: crate::Consumer::snapshot
+    /// [`Consumer::visit()`]: crate::Consumer::visit
+    #[cfg(feature = "alloc-constructors")]
+    pub fn from_snapshot<I: IntoIterator<Item = T>>(values: I) -> Queue<T> {
+        Queue::with_initial(values.into_iter().map(Node::new))
+    }
 
-        impl Drop for S {
-            fn drop(&mut self) {
-                self.0.set(self.0.get() + 1);
-            }
-        }
+    /// Splits a queue into its producer and consumer halves.
+    pub fn split(mut self) -> (Producer<T>, Consumer<T>) {
+        // Sequence numbers start over from the current front of the queue.
+        *self.acked.get_mut() = 0;
 
-        let (mut producer, mut consumer) = Queue::new().split();
+        Queue::split_shared(QueueRef::Single(Arc::new(self)))
+    }
 
-        let counter = Arc::new(Cell::new(0));
+    /// Splits the queue at `index` of a group of queues sharing an
+    /// allocation. Each queue must be split at most once.
+    #[cfg(feature = "alloc-constructors")]
+    pub(crate) fn split_grouped(
+        queues: &Arc<[Queue<T>]>,
+        index: usize,
+    ) -> (Producer<T>, Consumer<T>) {
+        Queue::split_shared(QueueRef::grouped(queues.clone(), index))
+    }
 
-        for _ in 0..10000 {
-            producer.push(Node::new(S(counter.clone())));
-        }
+    fn split_shared(queue: QueueRef<T>) -> (Producer<T>, Consumer<T>) {
+        let (tail, pushed) = unsafe { queue.take_tail() };
 
-        while let Some(_) = consumer.pop() {}
+        let producer =
+            Producer { queue: queue.clone(), tail, prev: ptr::null_mut(), pushed, total_pushed: 0 };
+        let consumer = Consumer {
+            queue,
+            evicted: Chain::new(),
+            popped: 0,
+            total_popped: 0,
+            recent: VecDeque::new(),
+            keep_recent: 0,
+        };
 
-        assert_eq!(counter.get(), 10000);
+        (producer, consumer)
+    }
+}
+
+#[cfg(feature = "alloc-constructors")]
+impl<T> Default for Queue<T> {
+    fn default() -> Queue<T> {
+        Queue::new()
+    }
+}
+
+impl<T> IntoIterator for Queue<T> {
+    type Item = Node<T>;
+    type IntoIter = IntoIter<T>;
+
+    /// Converts a queue into an iterator which removes and returns its
+    /// elements in order.
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { queue: self }
+    }
+}
+
+/// An iterator which removes and returns the elements of a [`Queue`].
+///
+/// [`Queue`]: crate::Queue
+pub struct IntoIter<T> {
+    queue: Queue<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = Node<T>;
+
+    fn next(&mut self) -> Option<Node<T>> {
+        // The iterator has exclusive ownership of the queue.
+        unsafe { self.queue.pop() }
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let head = self.head.get();
+            // The nodes of a grouped queue may have been moved out by
+            // `QueueRef::into_queue()`.
+            if head.is_null() {
+                return;
+            }
+            let mut current = (*head).next.load(RELAXED);
+
+            self.free_node(head);
+
+            while !is_end(current) {
+                let next = (*current).next.load(RELAXED);
+                #[cfg(feature = "observer")]
+                if let Some(observer) = &self.observer {
+                    observer.on_drop_unconsumed(&*(*current).data.as_ptr());
+                }
+                match &mut self.discard {
+                    Some(discard) => discard(ptr::read((*current).data.as_ptr())),
+                    None => ptr::drop_in_place((*current).data.as_mut_ptr()),
+                }
+                self.free_node(current);
+                current = next;
+            }
+        }
+    }
+}
+
+/// Tag stored in the last node's `next` pointer once the producer has been
+/// closed.
+const CLOSED: usize = 0b01;
+/// Tag stored in the last node's `next` pointer once the producer has been
+/// poisoned.
+const POISONED: usize = 0b10;
+
+/// Returns a null `next` pointer carrying `tag`.
+///
+/// The tags occupy the low bits of an otherwise null pointer, which can never
+/// be the address of a node, so a tagged pointer is unambiguous and marks the
+/// end of the queue just like a null pointer. The pointer has no provenance
+/// and is never dereferenced.
+fn end_tagged<T>(tag: usize) -> *mut NodeInner<T> {
+    ptr::without_provenance_mut(tag)
+}
+
+/// Returns `true` if `next` is null, with or without a tag.
+fn is_end<T>(next: *mut NodeInner<T>) -> bool {
+    next.addr() & !(CLOSED | POISONED) == 0
+}
+
+/// Returns the value stored in the retraction slot while `node` is being
+/// retracted.
+///
+/// Nodes are at least 2-byte aligned, so the low bit of the address is free.
+/// The tagged pointer keeps the node's provenance, although it is only ever
+/// compared and never dereferenced.
+fn retracting<T>(node: *mut NodeInner<T>) -> *mut NodeInner<T> {
+    node.map_addr(|addr| addr | 1)
+}
+
+/// The queue shared by a producer and a consumer: either a queue of its own,
+/// or one of the queues of a [`ChannelGroup`], which share an allocation.
+///
+/// [`ChannelGroup`]: crate::group::ChannelGroup
+enum QueueRef<T> {
+    Single(Arc<Queue<T>>),
+    Grouped(Arc<[Queue<T>]>, usize),
+}
+
+impl<T> QueueRef<T> {
+    fn grouped(queues: Arc<[Queue<T>]>, index: usize) -> QueueRef<T> {
+        queues[index].halves.fetch_add(1, RELAXED);
+        QueueRef::Grouped(queues, index)
+    }
+
+    /// Returns `true` if this is the only reference to the queue.
+    fn is_unique(&self) -> bool {
+        match self {
+            QueueRef::Single(queue) => Arc::strong_count(queue) == 1,
+            QueueRef::Grouped(_, _) => self.halves.load(ACQUIRE) == 1,
+        }
+    }
+
+    /// Moves the queue out if this is the only reference to it.
+    fn into_queue(self) -> Option<Queue<T>> {
+        if !self.is_unique() {
+            return None;
+        }
+
+        let this = mem::ManuallyDrop::new(self);
+        match &*this {
+            QueueRef::Single(queue) => Arc::try_unwrap(unsafe { ptr::read(queue) }).ok(),
+            QueueRef::Grouped(_, _) => {
+                // Grouped queues are split as soon as they are created, so
+                // they have no settings besides their contents to carry over.
+                let head = this.head.replace(ptr::null_mut());
+                let mut queue = Queue::from_sentinel(head);
+                *queue.len.get_mut() = this.len.load(RELAXED);
+                *queue.rejected.get_mut() = this.rejected.load(RELAXED);
+                drop(mem::ManuallyDrop::into_inner(this));
+                Some(queue)
+            }
+        }
+    }
+}
+
+impl<T> Clone for QueueRef<T> {
+    fn clone(&self) -> QueueRef<T> {
+        match self {
+            QueueRef::Single(queue) => QueueRef::Single(queue.clone()),
+            QueueRef::Grouped(queues, index) => QueueRef::grouped(queues.clone(), *index),
+        }
+    }
+}
+
+impl<T> Deref for QueueRef<T> {
+    type Target = Queue<T>;
+
+    fn deref(&self) -> &Queue<T> {
+        match self {
+            QueueRef::Single(queue) => queue,
+            // The index was checked when the reference was created.
+            QueueRef::Grouped(queues, index) => unsafe { queues.get_unchecked(*index) },
+        }
+    }
+}
+
+impl<T> Drop for QueueRef<T> {
+    fn drop(&mut self) {
+        if let QueueRef::Grouped(_, _) = self {
+            self.halves.fetch_sub(1, RELEASE);
+        }
+    }
+}
+
+/// The consumer half of a [`Queue`].
+///
+/// [`Queue`]: crate::Queue
+pub struct Consumer<T> {
+    queue: QueueRef<T>,
+    evicted: Chain<T>,
+    popped: usize,
+    total_popped: u64,
+    recent: VecDeque<Node<T>>,
+    keep_recent: usize,
+}
+
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+impl<T> Consumer<T> {
+    /// Attempts to remove and return an element from the queue. Returns `None`
+    /// if the queue is empty.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        #[cfg(feature = "fault-injection")]
+        {
+            if fault::delay_pop() {
+                return None;
+            }
+        }
+
+        self.evict();
+        self.pop_raw()
+    }
+
+    /// Removes every element from the queue and returns only the most
+    /// recently pushed one. Returns `None` if the queue is empty.
+    ///
+    /// This is the usual way to consume messages which each carry a complete
+    /// snapshot of some state, where only the newest one matters. The older
+    /// elements are dropped, and their nodes are added to the queue's reclaim
+    /// pool, if it has one, or deallocated otherwise.
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let (mut producer, mut consumer) = Queue::new().split();
+    /// for position in [0.25, 0.5, 0.75] {
+    ///     producer.push(Node::new(position));
+    /// }
+    ///
+    /// assert_eq!(*consumer.pop_latest().unwrap(), 0.75);
+    /// assert!(consumer.pop_latest().is_none());
+    /// ```
+    pub fn pop_latest(&mut self) -> Option<Node<T>> {
+        let mut latest = self.pop()?;
+        while let Some(node) = self.pop() {
+            self.discard(mem::replace(&mut latest, node));
+        }
+        Some(latest)
+    }
+
+    /// Drops the value of a node which has been popped, and deallocates the
+    /// node or adds it to the reclaim pool.
+    pub(crate) fn discard(&self, node: Node<T>) {
+        let inner = node.inner.as_ptr();
+        mem::forget(node);
+        unsafe {
+            ptr::drop_in_place((*inner).data.as_mut_ptr());
+            self.queue.free_node(inner);
+        }
+    }
+
+    /// Deallocates a node whose value has been moved out, or adds it to the
+    /// reclaim pool.
+    #[cfg(feature = "alloc-constructors")]
+    pub(crate) fn release(&self, node: Node<MaybeUninit<T>>) {
+        let inner = node.inner.cast::<NodeInner<T>>().as_ptr();
+        mem::forget(node);
+        unsafe { self.queue.free_node(inner) };
+    }
+
+    /// Puts an element back at the front of the queue, so that the next call
+    /// to [`pop()`] returns it. This allows an element whose processing failed
+    /// to be retried without reordering the queue.
+    ///
+    /// The element counts towards the queue's capacity, so under the
+    /// [`Overflow::DropOldest`] policy it may be evicted by the next call to
+    /// [`pop()`] if the queue is over capacity.
+    ///
+    /// [`pop()`]: crate::Consumer::pop
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    pub fn push_front(&mut self, node: Node<T>) {
+        unsafe {
+            let node_ptr = node.inner.as_ptr();
+            mem::forget(node);
+
+            // The current head becomes an element node holding the value, and
+            // the new node takes its place as the head. The producer only
+            // ever accesses the head through its `next` pointer.
+            let head = self.queue.head.get();
+            ptr::copy_nonoverlapping((*node_ptr).data.as_ptr(), (*head).data.as_mut_ptr(), 1);
+            #[cfg(feature = "timestamps")]
+            {
+                (*head).pushed = None;
+            }
+
+            (*node_ptr).next.store(head, RELAXED);
+            self.queue.head.set(node_ptr);
+        }
+
+        if self.queue.is_counted() {
+            self.queue.len.fetch_add(1, RELAXED);
+        }
+        self.popped = self.popped.wrapping_sub(1);
+    }
+
+    /// Returns the nodes of elements which were discarded under the
+    /// [`Overflow::DropOldest`] policy since the last call, leaving an empty
+    /// chain in their place.
+    ///
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    pub fn take_evicted(&mut self) -> Chain<T> {
+        mem::take(&mut self.evicted)
+    }
+
+    /// Makes the consumer keep the `n` most recently retired nodes for
+    /// inspection with [`recent()`], allocating space for them.
+    ///
+    /// This should be called during setup, since it allocates. Nodes already
+    /// kept beyond the new limit are dropped.
+    ///
+    /// [`recent()`]: crate::Consumer::recent
+    #[cfg(feature = "alloc-constructors")]
+    pub fn keep_recent(&mut self, n: usize) {
+        while self.recent.len() > n {
+            self.recent.pop_front();
+        }
+        self.recent.reserve_exact(n - self.recent.len());
+        self.keep_recent = n;
+    }
+
+    /// Hands a popped node back to the consumer once it has been processed,
+    /// keeping it among the recent nodes returned by [`recent()`]. Returns the
+    /// oldest kept node, which is no longer needed and can be recycled, once
+    /// the limit set with [`keep_recent()`] is reached.
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let (mut producer, mut consumer) = Queue::new().split();
+    /// consumer.keep_recent(2);
+    ///
+    /// for i in 0..3 {
+    ///     producer.push(Node::new(i));
+    /// }
+    /// let mut recycled = Vec::new();
+    /// while let Some(node) = consumer.pop() {
+    ///     recycled.extend(consumer.retire(node));
+    /// }
+    ///
+    /// assert_eq!(*recycled[0], 0);
+    /// assert_eq!(consumer.recent().copied().collect::<Vec<_>>(), [1, 2]);
+    /// ```
+    ///
+    /// [`recent()`]: crate::Consumer::recent
+    /// [`keep_recent()`]: crate::Consumer::keep_recent
+    pub fn retire(&mut self, node: Node<T>) -> Option<Node<T>> {
+        if self.keep_recent == 0 {
+            return Some(node);
+        }
+
+        let oldest =
+            if self.recent.len() == self.keep_recent { self.recent.pop_front() } else { None };
+        self.recent.push_back(node);
+        oldest
+    }
+
+    /// Returns an iterator over the most recently retired elements, from
+    /// oldest to newest.
+    ///
+    /// This can be used after a failure to inspect what the consumer processed
+    /// just before it.
+    pub fn recent(&self) -> Recent<'_, T> {
+        Recent { iter: self.recent.iter() }
+    }
+
+    /// Returns the number of elements rejected by [`Producer::try_push()`]
+    /// under the [`Overflow::DropNewest`] policy.
+    ///
+    /// [`Producer::try_push()`]: crate::Producer::try_push
+    /// [`Overflow::DropNewest`]: crate::Overflow::DropNewest
+    pub fn rejected(&self) -> usize {
+        self.queue.rejected.load(RELAXED)
+    }
+
+    /// Returns `true` if the producer has been closed or dropped and every
+    /// element pushed before then has been popped, so that no more elements
+    /// will arrive.
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let (mut producer, mut consumer) = Queue::new().split();
+    /// producer.push(Node::new(1));
+    /// producer.close();
+    ///
+    /// assert!(!consumer.is_closed());
+    /// assert_eq!(*consumer.pop().unwrap(), 1);
+    /// assert!(consumer.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.end_tag() != 0
+    }
+
+    /// Returns `true` if the producer has been poisoned, either with
+    /// [`Producer::poison()`] or by being dropped while its thread was
+    /// panicking, and every element pushed before then has been popped.
+    ///
+    /// A poisoned producer also counts as closed.
+    ///
+    /// [`Producer::poison()`]: crate::Producer::poison
+    pub fn is_poisoned(&self) -> bool {
+        self.end_tag() == POISONED
+    }
+
+    /// Returns the tag stored at the end of the queue if every element has
+    /// been popped, or 0 otherwise.
+    fn end_tag(&self) -> usize {
+        let next = unsafe { (*self.queue.head.get()).next.load(ACQUIRE) };
+        if is_end(next) {
+            next.addr()
+        } else {
+            0
+        }
+    }
+
+    /// Under the [`Overflow::DropOldest`] policy, moves elements from the
+    /// front of the queue to the evicted chain until the queue is within its
+    /// capacity.
+    ///
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    fn evict(&mut self) {
+        if let (Some(capacity), Overflow::DropOldest) = (self.queue.capacity, self.queue.overflow) {
+            while self.queue.len.load(RELAXED) > capacity {
+                match self.pop_evicted() {
+                    Some(node) => self.evicted.push_back(node),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    /// Recovers the queue, including any unconsumed elements, if the producer
+    /// half has been dropped. Returns `None` otherwise.
+    ///
+    /// Nodes which have not been collected with [`take_evicted()`] are
+    /// dropped.
+    ///
+    /// [`take_evicted()`]: crate::Consumer::take_evicted
+    pub fn into_queue(self) -> Option<Queue<T>> {
+        self.queue.into_queue()
+    }
+
+    /// Returns `true` if the consumer holds the only reference to the queue.
+    pub(crate) fn producer_dropped(&self) -> bool {
+        self.queue.is_unique()
+    }
+
+    fn pop_raw(&mut self) -> Option<Node<T>> {
+        let node = unsafe { self.queue.pop() };
+        if node.is_some() {
+            self.popped = self.popped.wrapping_add(1);
+            self.total_popped = self.total_popped.wrapping_add(1);
+        }
+        node
+    }
+
+    /// Pops an element which is being discarded rather than consumed, so that
+    /// the time it spent in the queue is left out of the latency statistics.
+    fn pop_evicted(&mut self) -> Option<Node<T>> {
+        #[cfg(feature = "timestamps")]
+        unsafe {
+            let next = self.queue.load_next(self.queue.head.get());
+            if !next.is_null() {
+                (*next).pushed = None;
+            }
+        }
+
+        self.pop_raw()
+    }
+
+    /// Returns the number of elements which this consumer has popped,
+    /// including those evicted under the [`Overflow::DropOldest`] policy.
+    ///
+    /// The count is kept by the consumer alone, so maintaining it costs no
+    /// synchronization, and it is available whether or not the queue keeps
+    /// count of its elements. Elements put back with [`push_front()`] count
+    /// again when they are popped again.
+    ///
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    /// [`push_front()`]: crate::Consumer::push_front
+    pub fn total_popped(&self) -> u64 {
+        self.total_popped
+    }
+
+    /// Grants the producer `credits` more pushes with
+    /// [`Producer::push_with_credit()`].
+    ///
+    /// [`Producer::push_with_credit()`]: crate::Producer::push_with_credit
+    pub fn grant(&self, credits: usize) {
+        self.queue.credits.fetch_add(credits, RELEASE);
+    }
+
+    /// Acknowledges that every element popped so far has been fully
+    /// processed. The producer can observe the acknowledgement with
+    /// [`Producer::acked()`].
+    ///
+    /// Elements evicted under the [`Overflow::DropOldest`] policy count as
+    /// popped.
+    ///
+    /// [`Producer::acked()`]: crate::Producer::acked
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    pub fn ack(&mut self) {
+        self.queue.acked.store(self.popped, RELEASE);
+    }
+
+    /// Calls `f` with a reference to each element currently in the queue, from
+    /// front to back, without removing them.
+    ///
+    /// Elements pushed while the queue is being visited may or may not be
+    /// included.
+    pub fn visit<F: FnMut(&T)>(&self, mut f: F) {
+        unsafe {
+            let mut current = self.queue.load_next(self.queue.head.get());
+
+            while !current.is_null() {
+                f(&*(*current).data.as_ptr());
+                current = self.queue.load_next(current);
+            }
+        }
+    }
+
+    /// Returns the number of elements currently in the queue, by walking the
+    /// links from the front of the queue to the back.
+    ///
+    /// This takes O(n) time, and the result may already be stale by the time
+    /// it is returned, as elements pushed during the walk may or may not be
+    /// counted. It is intended for tests and occasional diagnostics; queues
+    /// which need their length often should keep count of their elements
+    /// instead, and report it through [`Producer::lag()`].
+    ///
+    /// [`Producer::lag()`]: crate::Producer::lag
+    pub fn count_queued(&self) -> usize {
+        let mut count = 0;
+        self.visit(|_| count += 1);
+        count
+    }
+
+    /// Returns an iterator over references to up to the first `n` elements in
+    /// the queue, from front to back, without removing them.
+    ///
+    /// Under the [`Overflow::DropOldest`] policy, elements which will be
+    /// evicted by the next call to [`pop()`] are included.
+    ///
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    /// [`pop()`]: crate::Consumer::pop
+    pub fn peek_n(&self, n: usize) -> Peek<'_, T> {
+        Peek { queue: &self.queue, current: self.queue.head.get(), remaining: n }
+    }
+
+    /// Returns a copy of the elements currently in the queue, from front to
+    /// back, without removing them.
+    ///
+    /// The snapshot can later be turned back into a queue with
+    /// [`Queue::from_snapshot()`].
+    ///
+    /// [`Queue::from_snapshot()`]: crate::Queue::from_snapshot
+    #[cfg(feature = "alloc-constructors")]
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::new();
+        self.visit(|value| values.push(value.clone()));
+        values
+    }
+
+    /// Removes all elements currently in the queue and appends their values
+    /// to `values`, deallocating their nodes. Returns the number of elements
+    /// removed.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn pop_all_into(&mut self, values: &mut Vec<T>) -> usize {
+        let mut count = 0;
+        while let Some(node) = self.pop() {
+            values.push(Node::into_inner(node));
+            count += 1;
+        }
+        count
+    }
+
+    /// Removes and returns the first element in the queue for which `f`
+    /// returns `true`, leaving the elements before it in the queue in their
+    /// original order. Returns `None` if no element matches.
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let (mut producer, mut consumer) = Queue::new().split();
+    /// for i in 1..=4 {
+    ///     producer.push(Node::new(i));
+    /// }
+    ///
+    /// assert_eq!(*consumer.pop_matching(|&x| x % 2 == 0).unwrap(), 2);
+    /// assert!(consumer.pop_matching(|&x| x > 4).is_none());
+    /// assert_eq!(*consumer.pop().unwrap(), 1);
+    /// assert_eq!(*consumer.pop().unwrap(), 3);
+    /// ```
+    pub fn pop_matching<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> Option<Node<T>> {
+        self.evict();
+
+        unsafe {
+            let first = self.queue.load_next(self.queue.head.get());
+
+            let mut current = first;
+            while !current.is_null() && !f(&*(*current).data.as_ptr()) {
+                current = self.queue.load_next(current);
+            }
+
+            if current.is_null() {
+                return None;
+            }
+
+            // Move the matching element to the front of the queue, shifting
+            // each element before it back by one. Every node up to the match
+            // has been claimed, so none of them can be retracted.
+            let mut value = ptr::read((*current).data.as_ptr());
+            let mut node = first;
+            loop {
+                value = ptr::replace((*node).data.as_mut_ptr(), value);
+                if node == current {
+                    break;
+                }
+                node = (*node).next.load(ACQUIRE);
+            }
+
+            // The last value displaced is the matching element itself, which
+            // is now at the front.
+            mem::forget(value);
+        }
+
+        self.pop_raw()
+    }
+
+    /// Returns a guard exposing the element at the front of the queue, or
+    /// `None` if the queue is empty.
+    ///
+    /// The element is only removed if [`PopGuard::commit()`] is called.
+    /// Dropping the guard leaves the queue untouched, so an element is not
+    /// lost if processing fails between reading and acting on it.
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let (mut producer, mut consumer) = Queue::new().split();
+    /// producer.push(Node::new(1));
+    ///
+    /// let guard = consumer.pop_guard().unwrap();
+    /// assert_eq!(*guard, 1);
+    /// drop(guard);
+    ///
+    /// let node = consumer.pop_guard().unwrap().commit();
+    /// assert_eq!(*node, 1);
+    /// assert!(consumer.pop_guard().is_none());
+    /// ```
+    ///
+    /// [`PopGuard::commit()`]: crate::PopGuard::commit
+    pub fn pop_guard(&mut self) -> Option<PopGuard<'_, T>> {
+        let value = self.peek()? as *const T;
+        Some(PopGuard { consumer: self, value })
+    }
+
+    /// Returns a reference to the element at the front of the queue without
+    /// removing it.
+    pub(crate) fn peek(&mut self) -> Option<&T> {
+        self.evict();
+
+        unsafe {
+            let next = self.queue.load_next(self.queue.head.get());
+
+            if !next.is_null() {
+                return Some(&*(*next).data.as_ptr());
+            }
+
+            None
+        }
+    }
+}
+
+/// A guard exposing the element at the front of a queue, which is only
+/// removed once the guard is committed.
+///
+/// Returned by [`Consumer::pop_guard()`].
+///
+/// [`Consumer::pop_guard()`]: crate::Consumer::pop_guard
+pub struct PopGuard<'a, T> {
+    consumer: &'a mut Consumer<T>,
+    value: *const T,
+}
+
+impl<T> PopGuard<'_, T> {
+    /// Removes the element from the queue and returns its node.
+    pub fn commit(self) -> Node<T> {
+        // The element was claimed when it was peeked, so it is still at the
+        // front of the queue.
+        self.consumer.pop_raw().unwrap()
+    }
+}
+
+impl<T> Deref for PopGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+/// An iterator over references to the elements most recently retired by a
+/// consumer.
+///
+/// Returned by [`Consumer::recent()`].
+///
+/// [`Consumer::recent()`]: crate::Consumer::recent
+pub struct Recent<'a, T> {
+    iter: alloc::collections::vec_deque::Iter<'a, Node<T>>,
+}
+
+impl<'a, T> Iterator for Recent<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|node| &**node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over references to the first elements of a queue.
+///
+/// Returned by [`Consumer::peek_n()`].
+///
+/// [`Consumer::peek_n()`]: crate::Consumer::peek_n
+pub struct Peek<'a, T> {
+    queue: &'a Queue<T>,
+    current: *mut NodeInner<T>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Peek<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        unsafe {
+            let next = self.queue.load_next(self.current);
+            if next.is_null() {
+                self.remaining = 0;
+                return None;
+            }
+
+            self.current = next;
+            self.remaining -= 1;
+            Some(&*(*next).data.as_ptr())
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// The producer half of a [`Queue`].
+///
+/// [`Queue`]: crate::Queue
+pub struct Producer<T> {
+    queue: QueueRef<T>,
+    tail: *mut NodeInner<T>,
+    prev: *mut NodeInner<T>,
+    pushed: usize,
+    total_pushed: u64,
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+
+impl<T> Producer<T> {
+    /// Adds an element to the queue.
+    pub fn push(&mut self, node: Node<T>) {
+        let node_ptr = unsafe { self.queue.link(self.tail, node) };
+
+        self.prev = self.tail;
+        self.tail = node_ptr;
+        self.pushed = self.pushed.wrapping_add(1);
+        self.total_pushed = self.total_pushed.wrapping_add(1);
+    }
+
+    /// Adds an element to the queue if a credit is available, consuming it.
+    /// Returns the node in an error if no credits are left.
+    ///
+    /// Credits are set initially with [`Queue::set_credits()`] and granted by
+    /// the consumer with [`Consumer::grant()`], giving the consumer control
+    /// over how far the producer may get ahead without dropping elements.
+    ///
+    /// [`Queue::set_credits()`]: crate::Queue::set_credits
+    /// [`Consumer::grant()`]: crate::Consumer::grant
+    pub fn push_with_credit(&mut self, node: Node<T>) -> Result<(), PushError<Node<T>>> {
+        // Only the producer spends credits, so they cannot run out between the
+        // load and the subtraction.
+        if self.queue.credits.load(ACQUIRE) == 0 {
+            return Err(PushError::NoCredits(node));
+        }
+        self.queue.credits.fetch_sub(1, RELAXED);
+
+        self.push(node);
+        Ok(())
+    }
+
+    /// Returns the number of credits available to [`push_with_credit()`].
+    ///
+    /// [`push_with_credit()`]: crate::Producer::push_with_credit
+    pub fn credits(&self) -> usize {
+        self.queue.credits.load(ACQUIRE)
+    }
+
+    /// Returns the sequence number which will be assigned to the next pushed
+    /// element.
+    ///
+    /// Elements are numbered consecutively from zero, starting with any
+    /// elements the queue contained when it was split. Sequence numbers wrap
+    /// around on overflow.
+    pub fn next_sequence(&self) -> usize {
+        self.pushed
+    }
+
+    /// Returns the number of elements which this producer has pushed.
+    ///
+    /// Unlike [`next_sequence()`], the count starts at zero regardless of the
+    /// queue's initial contents and is not reduced by [`try_retract()`]. It is
+    /// kept by the producer alone, so maintaining it costs no
+    /// synchronization.
+    ///
+    /// [`next_sequence()`]: crate::Producer::next_sequence
+    /// [`try_retract()`]: crate::Producer::try_retract
+    pub fn total_pushed(&self) -> u64 {
+        self.total_pushed
+    }
+
+    /// Returns the number of elements which the consumer has acknowledged
+    /// with [`Consumer::ack()`].
+    ///
+    /// Every element with a sequence number less than the returned value has
+    /// been fully processed, so any resources it refers to may be reused.
+    ///
+    /// [`Consumer::ack()`]: crate::Consumer::ack
+    pub fn acked(&self) -> usize {
+        self.queue.acked.load(ACQUIRE)
+    }
+
+    /// Removes and returns the most recently pushed element, if the consumer
+    /// has not yet popped or inspected it.
+    ///
+    /// Returns `None` if retraction has not been enabled with
+    /// [`Queue::enable_retraction()`], if the consumer has already claimed the
+    /// element, or if the element has already been retracted. Only the most
+    /// recent element can be retracted, and elements pushed with
+    /// [`push_chain()`] cannot be retracted.
+    ///
+    /// [`Queue::enable_retraction()`]: crate::Queue::enable_retraction
+    /// [`push_chain()`]: crate::Producer::push_chain
+    pub fn try_retract(&mut self) -> Option<Node<T>> {
+        if !self.queue.retractable || self.prev.is_null() {
+            return None;
+        }
+
+        let tail = self.tail;
+        if self.queue.retract.compare_exchange(tail, retracting(tail), ACQUIRE, RELAXED).is_err() {
+            return None;
+        }
+
+        unsafe {
+            // The consumer cannot move past the previous node without claiming
+            // the retracted one, so the previous node is still in the queue.
+            (*self.prev).next.store(ptr::null_mut(), RELAXED);
+            self.queue.retractions.fetch_add(1, RELAXED);
+            self.queue.retract.store(ptr::null_mut(), RELEASE);
+
+            self.tail = self.prev;
+            self.prev = ptr::null_mut();
+            self.pushed = self.pushed.wrapping_sub(1);
+
+            if self.queue.is_counted() {
+                self.queue.len.fetch_sub(1, RELAXED);
+            }
+
+            Some(Node { inner: NonNull::new_unchecked(tail), phantom: PhantomData })
+        }
+    }
+
+    /// Adds an element to the queue in place of the most recently pushed
+    /// element if the consumer has not claimed that yet, returning the
+    /// replaced element, and otherwise adds it like [`push()`].
+    ///
+    /// A stream of progress updates or controller values pushed this way
+    /// does not pile up behind a slow consumer.
+    ///
+    /// Returns the node in a [`PushError::NotRetractable`] error if
+    /// retraction has not been enabled with [`Queue::enable_retraction()`],
+    /// since elements would then silently pile up instead of being replaced.
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let mut queue = Queue::new();
+    /// queue.enable_retraction();
+    /// let (mut producer, mut consumer) = queue.split();
+    ///
+    /// for percent in [10, 20, 30] {
+    ///     producer.push_or_replace(Node::new(percent)).unwrap();
+    /// }
+    /// assert_eq!(*consumer.pop().unwrap(), 30);
+    /// assert!(consumer.pop().is_none());
+    /// ```
+    ///
+    /// [`push()`]: crate::Producer::push
+    /// [`PushError::NotRetractable`]: crate::error::PushError::NotRetractable
+    /// [`Queue::enable_retraction()`]: crate::Queue::enable_retraction
+    pub fn push_or_replace(
+        &mut self,
+        node: Node<T>,
+    ) -> Result<Option<Node<T>>, PushError<Node<T>>> {
+        if !self.queue.retractable {
+            return Err(PushError::NotRetractable(node));
+        }
+
+        let replaced = self.try_retract();
+        self.push(node);
+        Ok(replaced)
+    }
+
+    /// Returns the number of elements which have been pushed but not yet
+    /// popped, or `None` if the queue does not keep count.
+    ///
+    /// Queues created with [`Queue::with_counter()`] or
+    /// [`Queue::with_capacity()`] keep count of their elements.
+    ///
+    /// [`Queue::with_counter()`]: crate::Queue::with_counter
+    /// [`Queue::with_capacity()`]: crate::Queue::with_capacity
+    pub fn lag(&self) -> Option<usize> {
+        if self.queue.is_counted() {
+            Some(self.queue.len.load(RELAXED))
+        } else {
+            None
+        }
+    }
+
+    /// Attempts to add an element to the queue, applying the queue's
+    /// [`Overflow`] policy if it is full. Returns the node in an error if it
+    /// was rejected.
+    ///
+    /// For a queue without a capacity, this is equivalent to [`push()`].
+    ///
+    /// [`Overflow`]: crate::Overflow
+    /// [`push()`]: crate::Producer::push
+    pub fn try_push(&mut self, node: Node<T>) -> Result<(), PushError<Node<T>>> {
+        if let Some(capacity) = self.queue.capacity {
+            if self.queue.len.load(RELAXED) >= capacity {
+                #[cfg(feature = "observer")]
+                if self.queue.overflow != Overflow::DropOldest {
+                    if let Some(observer) = &self.queue.observer {
+                        observer.on_reject(&node);
+                    }
+                }
+
+                match self.queue.overflow {
+                    Overflow::Reject => return Err(PushError::Full(node)),
+                    Overflow::DropOldest => {}
+                    Overflow::DropNewest => {
+                        self.queue.rejected.fetch_add(1, RELAXED);
+                        return Err(PushError::Full(node));
+                    }
+                }
+            }
+        }
+
+        self.push(node);
+        Ok(())
+    }
+
+    /// Returns `true` if the producer holds the only reference to the queue.
+    #[cfg(feature = "alloc-constructors")]
+    pub(crate) fn consumer_dropped(&self) -> bool {
+        self.queue.is_unique()
+    }
+
+    /// Recovers the queue, including any unconsumed elements, if the consumer
+    /// half has been dropped. Returns `None`, closing the producer, otherwise.
+    pub fn into_queue(self) -> Option<Queue<T>> {
+        if !self.queue.is_unique() {
+            return None;
+        }
+
+        // The queue is not closed, since it will be split again.
+        let this = mem::ManuallyDrop::new(self);
+        unsafe { ptr::read(&this.queue) }.into_queue()
+    }
+
+    /// Closes the producer, indicating that no more elements will be pushed.
+    /// The consumer observes this with [`Consumer::is_closed()`] once it has
+    /// popped every element.
+    ///
+    /// Dropping the producer has the same effect.
+    ///
+    /// [`Consumer::is_closed()`]: crate::Consumer::is_closed
+    pub fn close(self) {}
+
+    /// Closes the producer, like [`close()`], and returns the number of
+    /// elements it pushed; see [`total_pushed()`]. Once the producer has
+    /// finished, the consumer can take the remaining elements all at once with
+    /// [`Consumer::into_remaining()`].
+    ///
+    /// [`close()`]: crate::Producer::close
+    /// [`total_pushed()`]: crate::Producer::total_pushed
+    /// [`Consumer::into_remaining()`]: crate::Consumer::into_remaining
+    pub fn finish(self) -> u64 {
+        self.total_pushed
+    }
+
+    /// Closes the producer and marks it as poisoned, indicating to the
+    /// consumer that it stopped because of a failure. The consumer observes
+    /// this with [`Consumer::is_poisoned()`] once it has popped every element.
+    ///
+    /// With the `std` feature, a producer dropped while its thread is
+    /// panicking is poisoned automatically.
+    ///
+    /// [`Consumer::is_poisoned()`]: crate::Consumer::is_poisoned
+    pub fn poison(self) {
+        let this = mem::ManuallyDrop::new(self);
+        this.end(POISONED);
+        drop(unsafe { ptr::read(&this.queue) });
+    }
+
+    /// Tags the last node's `next` pointer, so that the consumer sees the
+    /// tag after every element pushed so far.
+    fn end(&self, tag: usize) {
+        unsafe { (*self.tail).next.store(end_tagged(tag), RELEASE) };
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        let tag = if std::thread::panicking() { POISONED } else { CLOSED };
+        #[cfg(not(feature = "std"))]
+        let tag = CLOSED;
+
+        self.end(tag);
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn multithreaded() {
+        let (mut producer, mut consumer) = Queue::new().split();
+
+        let thread1 = std::thread::spawn(move || {
+            for _ in 0..10000 {
+                producer.push(Node::new(false));
+            }
+
+            producer.push(Node::new(true));
+        });
+
+        let thread2 = std::thread::spawn(move || {
+            let mut counter = 0;
+
+            loop {
+                if let Some(node) = consumer.pop() {
+                    if *node {
+                        break;
+                    } else {
+                        counter += 1;
+                    }
+                }
+            }
+
+            assert_eq!(counter, 10000);
+        });
+
+        thread1.join().unwrap();
+        thread2.join().unwrap();
+    }
+
+    #[test]
+    fn borrowed_payloads() {
+        let data: Vec<alloc::string::String> = (0..100).map(|i| alloc::format!("{}", i)).collect();
+        let (mut producer, mut consumer) = Queue::<&alloc::string::String>::new().split();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for value in &data {
+                    producer.push(Node::new(value));
+                }
+            });
+            scope.spawn(|| {
+                for value in &data {
+                    let node = loop {
+                        if let Some(node) = consumer.pop() {
+                            break node;
+                        }
+                    };
+                    assert!(ptr::eq(*node, value));
+                }
+            });
+        });
+
+        let first = &data[0][..];
+        let mut queue = Queue::new();
+        let (mut producer, mut consumer) = queue.split_ref();
+        std::thread::scope(|scope| {
+            scope.spawn(move || producer.push(Node::new(first)));
+        });
+        assert_eq!(*consumer.pop().unwrap(), "0");
+    }
+
+    #[test]
+    #[allow(clippy::redundant_pattern_matching)]
+    fn multiple_queues() {
+        let (mut producer1, mut consumer1) = Queue::new().split();
+        let (mut producer2, mut consumer2) = Queue::new().split();
+
+        for _ in 0..10000 {
+            producer1.push(Node::new(()));
+        }
+
+        let mut counter = 0;
+        while let Some(node) = consumer1.pop() {
+            producer2.push(node);
+            counter += 1;
+        }
+        assert_eq!(counter, 10000);
+
+        let mut counter = 0;
+        while let Some(_) = consumer2.pop() {
+            counter += 1;
+        }
+        assert_eq!(counter, 10000);
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let (mut producer, mut consumer) = Queue::new().split();
+
+        for i in 0..10 {
+            producer.push(Node::new(i));
+        }
+        assert_eq!(*consumer.pop().unwrap(), 0);
+
+        let snapshot = consumer.snapshot();
+        assert_eq!(snapshot, (1..10).collect::<Vec<_>>());
+        assert_eq!(*consumer.pop().unwrap(), 1);
+
+        let (mut producer, mut consumer) = Queue::from_snapshot(snapshot).split();
+        producer.push(Node::new(10));
+        for i in 1..11 {
+            assert_eq!(*consumer.pop().unwrap(), i);
+        }
+        assert!(consumer.pop().is_none());
+    }
+
+    #[test]
+    fn capacity_reject() {
+        let (mut producer, mut consumer) = Queue::with_capacity(2, Overflow::Reject).split();
+
+        assert!(producer.try_push(Node::new(0)).is_ok());
+        assert!(producer.try_push(Node::new(1)).is_ok());
+        assert_eq!(*producer.try_push(Node::new(2)).unwrap_err().into_inner(), 2);
+
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        assert!(producer.try_push(Node::new(3)).is_ok());
+
+        producer.push(Node::new(4));
+        assert!(producer.try_push(Node::new(5)).is_err());
+
+        assert_eq!(*consumer.pop().unwrap(), 1);
+        assert_eq!(*consumer.pop().unwrap(), 3);
+        assert_eq!(*consumer.pop().unwrap(), 4);
+        assert!(consumer.pop().is_none());
+    }
+
+    #[test]
+    fn capacity_drop_oldest() {
+        let (mut producer, mut consumer) = Queue::with_capacity(2, Overflow::DropOldest).split();
+
+        for i in 0..5 {
+            assert!(producer.try_push(Node::new(i)).is_ok());
+        }
+
+        assert_eq!(*consumer.pop().unwrap(), 3);
+
+        let evicted = consumer.take_evicted();
+        assert_eq!(evicted.iter().copied().collect::<Vec<_>>(), [0, 1, 2]);
+        for mut node in evicted {
+            *node += 5;
+            assert!(producer.try_push(node).is_ok());
+        }
+
+        assert_eq!(*consumer.pop().unwrap(), 6);
+        assert_eq!(*consumer.pop().unwrap(), 7);
+        assert!(consumer.pop().is_none());
+        assert_eq!(consumer.take_evicted().len(), 2);
+    }
+
+    #[test]
+    fn capacity_drop_newest() {
+        let (mut producer, mut consumer) = Queue::with_capacity(1, Overflow::DropNewest).split();
+
+        assert!(producer.try_push(Node::new(0)).is_ok());
+        assert_eq!(*producer.try_push(Node::new(1)).unwrap_err().into_inner(), 1);
+        assert_eq!(*producer.try_push(Node::new(2)).unwrap_err().into_inner(), 2);
+        assert_eq!(consumer.rejected(), 2);
+
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        assert!(producer.try_push(Node::new(3)).is_ok());
+        assert_eq!(*consumer.pop().unwrap(), 3);
+        assert_eq!(consumer.rejected(), 2);
+    }
+
+    #[test]
+    fn lag() {
+        let (mut producer, mut consumer) = Queue::with_counter().split();
+        assert_eq!(producer.lag(), Some(0));
+
+        for i in 0..3 {
+            producer.push(Node::new(i));
+        }
+        assert_eq!(producer.lag(), Some(3));
+
+        consumer.pop();
+        assert_eq!(producer.lag(), Some(2));
+
+        let (producer, _) = Queue::<()>::new().split();
+        assert_eq!(producer.lag(), None);
+    }
+
+    #[test]
+    fn totals() {
+        let queue = Queue::with_initial((0..2).map(Node::new));
+        let (mut producer, mut consumer) = queue.split();
+        assert_eq!(producer.next_sequence(), 2);
+        assert_eq!(producer.total_pushed(), 0);
+
+        producer.push(Node::new(2));
+        let mut chain = Chain::new();
+        chain.push_back(Node::new(3));
+        chain.push_back(Node::new(4));
+        producer.push_chain(chain);
+        assert_eq!(producer.total_pushed(), 3);
+
+        let node = consumer.pop().unwrap();
+        consumer.push_front(node);
+        while consumer.pop().is_some() {}
+        assert_eq!(consumer.total_popped(), 6);
+    }
+
+    #[test]
+    fn with_initial() {
+        let (mut producer, mut consumer) = Queue::with_initial((0..3).map(Node::new)).split();
+        producer.push(Node::new(3));
+
+        for i in 0..4 {
+            assert_eq!(*consumer.pop().unwrap(), i);
+        }
+        assert!(consumer.pop().is_none());
+    }
+
+    #[test]
+    fn pop_all_into() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        for i in 0..3 {
+            producer.push(Node::new(i));
+        }
+
+        let mut values = alloc::vec![-1];
+        assert_eq!(consumer.pop_all_into(&mut values), 3);
+        assert_eq!(consumer.pop_all_into(&mut values), 0);
+        assert_eq!(values, [-1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn push_front() {
+        let mut queue = Queue::with_counter();
+        queue.enable_retraction();
+        let (mut producer, mut consumer) = queue.split();
+        consumer.push_front(Node::new(0));
+        producer.push(Node::new(1));
+        producer.push(Node::new(2));
+
+        let node = consumer.pop().unwrap();
+        assert_eq!(*node, 0);
+        consumer.push_front(node);
+        let node = consumer.pop().unwrap();
+        let next = consumer.pop().unwrap();
+        consumer.push_front(next);
+        consumer.push_front(node);
+        assert_eq!(producer.lag(), Some(3));
+
+        assert_eq!(*producer.try_retract().unwrap(), 2);
+        let values: Vec<_> = core::iter::from_fn(|| consumer.pop()).map(|node| *node).collect();
+        assert_eq!(values, [0, 1]);
+    }
+
+    #[test]
+    fn pop_guard() {
+        let mut queue = Queue::new();
+        queue.enable_retraction();
+        let (mut producer, mut consumer) = queue.split();
+        producer.push(Node::new(0));
+
+        assert_eq!(*consumer.pop_guard().unwrap(), 0);
+        assert!(producer.try_retract().is_none());
+        producer.push(Node::new(1));
+
+        assert_eq!(*consumer.pop_guard().unwrap().commit(), 0);
+        assert_eq!(*consumer.pop().unwrap(), 1);
+        assert!(consumer.pop_guard().is_none());
+    }
+
+    #[test]
+    fn pop_latest() {
+        let pool = Arc::new(pool::SharedPool::new());
+        let mut queue = Queue::new();
+        queue.set_reclaim_pool(pool.clone());
+        let (mut producer, mut consumer) = queue.split();
+
+        producer.push(Node::new(0));
+        assert_eq!(*consumer.pop_latest().unwrap(), 0);
+        for i in 1..4 {
+            producer.push(Node::new(i));
+        }
+        assert_eq!(*consumer.pop_latest().unwrap(), 3);
+        assert_eq!(pool.len(), 2);
+        assert!(consumer.pop_latest().is_none());
+    }
+
+    #[test]
+    fn pop_matching() {
+        let mut queue = Queue::new();
+        queue.enable_retraction();
+        let (mut producer, mut consumer) = queue.split();
+        for i in 0..5 {
+            producer.push(Node::new(alloc::format!("{}", i)));
+        }
+
+        assert_eq!(*consumer.pop_matching(|x| x == "4").unwrap(), "4");
+        assert!(producer.try_retract().is_none());
+        assert_eq!(*consumer.pop_matching(|x| x == "2").unwrap(), "2");
+        assert_eq!(*consumer.pop_matching(|x| x == "0").unwrap(), "0");
+        assert!(consumer.pop_matching(|x| x == "2").is_none());
+
+        let values: Vec<_> = core::iter::from_fn(|| consumer.pop()).map(Node::into_inner).collect();
+        assert_eq!(values, ["1", "3"]);
+    }
+
+    #[test]
+    fn try_new() {
+        let (mut producer, mut consumer) = Queue::try_new().unwrap().split();
+        producer.push(Node::try_new(1).unwrap());
+        assert_eq!(*consumer.pop().unwrap(), 1);
+    }
+
+    #[test]
+    fn new_with() {
+        let node = Node::new_with(|| [7u64; 1024]);
+        assert!(node.iter().all(|&x| x == 7));
+
+        let mut node = Node::<alloc::string::String>::new_uninit();
+        node.write(alloc::string::String::from("a"));
+        let node = unsafe { Node::assume_init(node) };
+        assert_eq!(*node, "a");
+
+        let node = unsafe { Node::assume_init(Node::<[usize; 3]>::new_zeroed()) };
+        assert_eq!(*node, [0; 3]);
+    }
+
+    #[test]
+    fn new_uninit_slice() {
+        let (mut producer, mut consumer) = Queue::new().split();
+
+        let mut node = Node::new_uninit_slice(4096);
+        assert_eq!(node.len(), 4096);
+        for (i, byte) in node.iter_mut().enumerate() {
+            byte.write(i as u8);
+        }
+        producer.push(unsafe { Node::assume_init_slice(node) });
+
+        let empty = Node::new_uninit_slice(0);
+        producer.push(unsafe { Node::assume_init_slice(empty) });
+
+        let node = consumer.pop().unwrap();
+        assert!(node.iter().enumerate().all(|(i, &byte)| byte == i as u8));
+        assert!(consumer.pop().unwrap().is_empty());
+    }
+
+    #[test]
+    fn forget_value() {
+        let mut node = Node::new(alloc::string::String::from("a"));
+        let value = unsafe { ptr::read(Node::as_mut_ptr(&mut node)) };
+        let mut node = Node::forget_value(node);
+        assert_eq!(value, "a");
+
+        node.write(alloc::string::String::from("b"));
+        let node = unsafe { Node::assume_init(node) };
+        assert_eq!(*node, "b");
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_channels() {
+        #[derive(Channels, Debug, PartialEq)]
+        enum Message {
+            Stop,
+            NoteOn(u8, f32),
+            SetGain(f32),
+        }
+
+        let (mut sender, mut receiver) = Message::channels();
+        sender.send(Message::SetGain(0.5));
+        sender.send_note_on(Node::new((60, 1.0)));
+        sender.send(Message::Stop);
+
+        assert_eq!(receiver.recv(), Some(Message::Stop));
+        assert_eq!(receiver.recv(), Some(Message::NoteOn(60, 1.0)));
+        assert_eq!(*receiver.recv_set_gain().unwrap(), 0.5);
+        assert_eq!(receiver.recv(), None);
+    }
+
+    #[test]
+    fn peek_n() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        assert!(consumer.peek_n(2).next().is_none());
+
+        for i in 0..3 {
+            producer.push(Node::new(i));
+        }
+        assert_eq!(consumer.peek_n(2).copied().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(consumer.peek_n(5).copied().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        assert_eq!(consumer.peek_n(1).copied().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn count_queued() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        assert_eq!(consumer.count_queued(), 0);
+
+        for i in 0..3 {
+            producer.push(Node::new(i));
+        }
+        assert_eq!(consumer.count_queued(), 3);
+        consumer.pop();
+        assert_eq!(consumer.count_queued(), 2);
+    }
+
+    #[test]
+    fn into_iter() {
+        let queue = Queue::with_initial((0..3).map(Node::new));
+        assert_eq!(queue.into_iter().map(Node::into_inner).collect::<Vec<_>>(), [0, 1, 2]);
+
+        let mut iter = Queue::from_snapshot(0..3).into_iter();
+        assert_eq!(*iter.next().unwrap(), 0);
+    }
+
+    #[test]
+    fn into_queue() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push(Node::new(0));
+        producer.push(Node::new(1));
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        drop(producer);
+
+        let (mut producer, consumer) = consumer.into_queue().unwrap().split();
+        producer.push(Node::new(2));
+        drop(consumer);
+
+        let queue = producer.into_queue().unwrap();
+        assert_eq!(queue.into_iter().map(Node::into_inner).collect::<Vec<_>>(), [1, 2]);
+
+        let (producer, _consumer) = Queue::<()>::new().split();
+        assert!(producer.into_queue().is_none());
+    }
+
+    #[test]
+    fn closed_and_poisoned() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push(Node::new(0));
+        producer.poison();
+        assert!(!consumer.is_closed());
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        assert!(consumer.pop().is_none());
+        assert!(consumer.is_closed() && consumer.is_poisoned());
+
+        let (producer, consumer) = consumer.into_queue().unwrap().split();
+        assert!(!consumer.is_closed());
+        drop(producer);
+        assert!(consumer.is_closed() && !consumer.is_poisoned());
+
+        #[cfg(feature = "std")]
+        {
+            let (producer, consumer) = Queue::<()>::new().split();
+            let result = std::thread::spawn(move || {
+                let _producer = producer;
+                panic!();
+            })
+            .join();
+            assert!(result.is_err());
+            assert!(consumer.is_poisoned());
+        }
+    }
+
+    #[cfg(feature = "observer")]
+    #[test]
+    fn observer() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Log(Mutex<Vec<(&'static str, i32)>>);
+
+        impl observer::Observer<i32> for Arc<Log> {
+            fn on_push(&self, value: &i32) {
+                self.0.lock().unwrap().push(("push", *value));
+            }
+
+            fn on_pop(&self, value: &i32) {
+                self.0.lock().unwrap().push(("pop", *value));
+            }
+
+            fn on_drop_unconsumed(&self, value: &i32) {
+                self.0.lock().unwrap().push(("drop", *value));
+            }
+        }
+
+        let log = Arc::new(Log::default());
+        let mut queue = Queue::new();
+        queue.set_observer(log.clone());
+
+        let (mut producer, mut consumer) = queue.split();
+        producer.push(Node::new(1));
+        let mut chain = Chain::new();
+        chain.push_back(Node::new(2));
+        producer.push_chain(chain);
+        consumer.pop();
+        drop((producer, consumer));
+
+        assert_eq!(*log.0.lock().unwrap(), [("push", 1), ("push", 2), ("pop", 1), ("drop", 2)]);
+    }
+
+    #[test]
+    fn recent() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        assert_eq!(*consumer.retire(Node::new(0)).unwrap(), 0);
+
+        consumer.keep_recent(3);
+        for i in 1..=5 {
+            producer.push(Node::new(i));
+        }
+        let mut recycled = Vec::new();
+        while let Some(node) = consumer.pop() {
+            recycled.extend(consumer.retire(node).map(Node::into_inner));
+        }
+        assert_eq!(recycled, [1, 2]);
+        assert_eq!(consumer.recent().copied().collect::<Vec<_>>(), [3, 4, 5]);
+
+        consumer.keep_recent(1);
+        assert_eq!(consumer.recent().copied().collect::<Vec<_>>(), [5]);
+    }
+
+    #[test]
+    fn discard_handler() {
+        use std::sync::Mutex;
+
+        let discarded = Arc::new(Mutex::new(Vec::new()));
+        let mut queue = Queue::from_snapshot(0..3);
+        let log = discarded.clone();
+        queue.set_discard_handler(move |value| log.lock().unwrap().push(value));
+
+        let (producer, mut consumer) = queue.split();
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        drop(consumer);
+        assert!(discarded.lock().unwrap().is_empty());
+
+        drop(producer);
+        assert_eq!(*discarded.lock().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn reclaim_pool() {
+        let pool = Arc::new(pool::SharedPool::new());
+        let mut queue = Queue::from_snapshot(0..3);
+        queue.set_reclaim_pool(pool.clone());
+
+        let (_, mut consumer) = queue.split();
+        let node = consumer.pop().unwrap();
+        drop(consumer);
+
+        // The sentinel and the two unconsumed nodes.
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.recycle(node), 0);
+    }
+
+    #[test]
+    fn retract() {
+        let mut queue = Queue::new();
+        queue.enable_retraction();
+        let (mut producer, mut consumer) = queue.split();
+        assert!(producer.try_retract().is_none());
+
+        producer.push(Node::new(0));
+        producer.push(Node::new(1));
+        assert_eq!(*producer.try_retract().unwrap(), 1);
+        assert!(producer.try_retract().is_none());
+
+        producer.push(Node::new(2));
+        assert_eq!(consumer.snapshot(), [0, 2]);
+        assert!(producer.try_retract().is_none());
+
+        producer.push(Node::new(3));
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        assert_eq!(*consumer.pop().unwrap(), 2);
+        assert_eq!(*producer.try_retract().unwrap(), 3);
+        assert!(consumer.pop().is_none());
+
+        let (mut producer, mut consumer) = Queue::new().split();
+        producer.push(Node::new(0));
+        assert!(producer.try_retract().is_none());
+        assert_eq!(*consumer.pop().unwrap(), 0);
+    }
+
+    #[test]
+    fn push_or_replace() {
+        let mut queue = Queue::with_counter();
+        queue.enable_retraction();
+        let (mut producer, mut consumer) = queue.split();
+
+        assert!(producer.push_or_replace(Node::new(0)).unwrap().is_none());
+        assert_eq!(*producer.push_or_replace(Node::new(1)).unwrap().unwrap(), 0);
+        assert_eq!(producer.lag(), Some(1));
+
+        assert_eq!(*consumer.pop().unwrap(), 1);
+        assert!(producer.push_or_replace(Node::new(2)).unwrap().is_none());
+        assert_eq!(*producer.push_or_replace(Node::new(3)).unwrap().unwrap(), 2);
+        assert_eq!(consumer.snapshot(), [3]);
+    }
+
+    #[test]
+    fn push_or_replace_without_retraction() {
+        let (mut producer, mut consumer) = Queue::new().split();
+        match producer.push_or_replace(Node::new(0)) {
+            Err(PushError::NotRetractable(node)) => assert_eq!(*node, 0),
+            _ => panic!("expected NotRetractable"),
+        }
+        assert!(consumer.pop().is_none());
+    }
+
+    #[test]
+    fn retract_multithreaded() {
+        let mut queue = Queue::new();
+        queue.enable_retraction();
+        let (mut producer, mut consumer) = queue.split();
+
+        let thread = std::thread::spawn(move || {
+            let mut retracted = 0;
+            for i in 0..10000 {
+                producer.push(Node::new(i));
+                if i % 2 == 0 && producer.try_retract().is_some() {
+                    retracted += 1;
+                }
+            }
+            producer.push(Node::new(usize::MAX));
+            retracted
+        });
+
+        let mut popped = 0;
+        let mut last = None;
+        loop {
+            if let Some(node) = consumer.pop() {
+                if *node == usize::MAX {
+                    break;
+                }
+                assert!(last < Some(*node));
+                last = Some(*node);
+                popped += 1;
+            }
+        }
+
+        assert_eq!(popped + thread.join().unwrap(), 10000);
+    }
+
+    #[test]
+    fn ack() {
+        let (mut producer, mut consumer) = Queue::from_snapshot(0..2).split();
+        assert_eq!(producer.next_sequence(), 2);
+        producer.push(Node::new(2));
+        assert_eq!(producer.acked(), 0);
+
+        consumer.pop().unwrap();
+        consumer.pop().unwrap();
+        assert_eq!(producer.acked(), 0);
+        consumer.ack();
+        assert_eq!(producer.acked(), 2);
+
+        consumer.pop().unwrap();
+        consumer.ack();
+        assert_eq!(producer.acked(), producer.next_sequence());
+    }
+
+    #[test]
+    fn credits() {
+        let mut queue = Queue::new();
+        queue.set_credits(1);
+        let (mut producer, mut consumer) = queue.split();
+
+        assert!(producer.push_with_credit(Node::new(0)).is_ok());
+        assert!(producer.push_with_credit(Node::new(1)).is_err());
+        assert_eq!(producer.credits(), 0);
+
+        assert_eq!(*consumer.pop().unwrap(), 0);
+        consumer.grant(2);
+        assert!(producer.push_with_credit(Node::new(1)).is_ok());
+        assert_eq!(producer.credits(), 1);
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync, clippy::redundant_pattern_matching)]
+    fn drop_occurs() {
+        struct S(Arc<Cell<usize>>);
+
+        impl Drop for S {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let (mut producer, mut consumer) = Queue::new().split();
+
+        let counter = Arc::new(Cell::new(0));
+
+        for _ in 0..10000 {
+            producer.push(Node::new(S(counter.clone())));
+        }
+
+        while let Some(_) = consumer.pop() {}
+
+        assert_eq!(counter.get(), 10000);
+    }
+}
+
+#[cfg(all(test, feature = "shuttle", feature = "alloc-constructors"))]
+mod shuttle_tests {
+    use super::*;
+    use crate::sync::thread;
+
+    #[test]
+    fn shuttle_push_pop() {
+        shuttle::check_random(
+            || {
+                let (mut producer, mut consumer) = Queue::new().split();
+
+                let thread = thread::spawn(move || {
+                    for i in 0..3 {
+                        producer.push(Node::new(i));
+                    }
+                });
+
+                let mut expected = 0;
+                while expected < 3 {
+                    match consumer.pop() {
+                        Some(node) => {
+                            assert_eq!(*node, expected);
+                            expected += 1;
+                        }
+                        None => thread::yield_now(),
+                    }
+                }
+
+                thread.join().unwrap();
+            },
+            1000,
+        );
+    }
+
+    #[test]
+    fn shuttle_retract() {
+        shuttle::check_random(
+            || {
+                let mut queue = Queue::new();
+                queue.enable_retraction();
+                let (mut producer, mut consumer) = queue.split();
+
+                let thread = thread::spawn(move || {
+                    let mut retracted = 0;
+                    for i in 0..3 {
+                        producer.push(Node::new(i));
+                        if producer.try_retract().is_some() {
+                            retracted += 1;
+                        }
+                    }
+                    producer.push(Node::new(usize::MAX));
+                    retracted
+                });
+
+                let mut popped = 0;
+                loop {
+                    match consumer.pop() {
+                        Some(node) if *node == usize::MAX => break,
+                        Some(_) => popped += 1,
+                        None => thread::yield_now(),
+                    }
+                }
+
+                assert_eq!(popped + thread.join().unwrap(), 3);
+            },
+            1000,
+        );
     }
 }
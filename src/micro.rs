@@ -0,0 +1,326 @@
+//! A fixed-size queue with nodes addressed by 8- or 16-bit indices.
+//!
+//! [`MicroQueue`] is intended for microcontrollers with only a few kilobytes
+//! of RAM, such as Cortex-M0 parts. Its nodes live in an inline array rather
+//! than on the heap, and are linked by `u8` or `u16` indices instead of
+//! pointers, so each node only adds one or two bytes (plus padding) to its
+//! value. A queue with `u8` indices can have up to 255 nodes, and one with
+//! `u16` indices up to 65535.
+//!
+//! Nodes which the consumer is done with are passed back to the producer on
+//! a second index-linked list, so the queue never allocates and only needs
+//! atomic loads and stores, which are available even on targets without
+//! compare-and-swap instructions. Two of the `N` nodes serve as sentinels, so
+//! the queue holds up to `N - 2` elements.
+//!
+//! [`MicroQueue::new()`] is a `const fn`, so a queue can be placed in a
+//! `static`, e.g. through a `static_cell::StaticCell`, rather than on the
+//! stack of a device with little of it.
+//!
+//! ```rust
+//! use llq::micro::MicroQueue;
+//!
+//! let mut queue = MicroQueue::<[u8; 3], u8, 16>::new();
+//! let (mut producer, mut consumer) = queue.split();
+//!
+//! producer.push([0x90, 60, 100]).unwrap();
+//! assert_eq!(consumer.pop(), Some([0x90, 60, 100]));
+//! assert_eq!(consumer.pop(), None);
+//! ```
+//!
+//! [`MicroQueue`]: crate::micro::MicroQueue
+//! [`MicroQueue::new()`]: crate::micro::MicroQueue::new
+
+use crate::error::PushError;
+use crate::sync::{AtomicU16, AtomicU8, ACQUIRE, RELAXED, RELEASE};
+
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::sync::atomic::Ordering;
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+}
+
+/// An index type for a [`MicroQueue`], implemented for `u8` and `u16`.
+///
+/// [`MicroQueue`]: crate::micro::MicroQueue
+pub trait MicroIndex: Copy + Eq + private::Sealed {
+    #[doc(hidden)]
+    type Atomic;
+
+    /// The index used as a null link. Node indices are always below it.
+    #[doc(hidden)]
+    const NONE: Self;
+    /// The largest number of nodes which can be addressed.
+    #[doc(hidden)]
+    const MAX_NODES: usize;
+    #[doc(hidden)]
+    const ZERO: Self;
+    #[doc(hidden)]
+    const ONE: Self;
+    #[doc(hidden)]
+    const TWO: Self;
+    #[doc(hidden)]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ATOMIC_NONE: Self::Atomic;
+
+    #[doc(hidden)]
+    fn load(atomic: &Self::Atomic, order: Ordering) -> Self;
+    #[doc(hidden)]
+    fn store(atomic: &Self::Atomic, index: Self, order: Ordering);
+    #[doc(hidden)]
+    fn from_usize(index: usize) -> Self;
+    #[doc(hidden)]
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! micro_index {
+    ($index:ty, $atomic:ty) => {
+        impl MicroIndex for $index {
+            type Atomic = $atomic;
+
+            const NONE: $index = <$index>::MAX;
+            const MAX_NODES: usize = <$index>::MAX as usize;
+            const ZERO: $index = 0;
+            const ONE: $index = 1;
+            const TWO: $index = 2;
+            #[allow(clippy::declare_interior_mutable_const)]
+            const ATOMIC_NONE: $atomic = <$atomic>::new(<$index>::MAX);
+
+            fn load(atomic: &$atomic, order: Ordering) -> $index {
+                atomic.load(order)
+            }
+
+            fn store(atomic: &$atomic, index: $index, order: Ordering) {
+                atomic.store(index, order)
+            }
+
+            fn from_usize(index: usize) -> $index {
+                index as $index
+            }
+
+            fn to_usize(self) -> usize {
+                self as usize
+            }
+        }
+    };
+}
+
+micro_index!(u8, AtomicU8);
+micro_index!(u16, AtomicU16);
+
+struct Slot<T, I: MicroIndex> {
+    next: I::Atomic,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T, I: MicroIndex> Slot<T, I> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Slot<T, I> =
+        Slot { next: I::ATOMIC_NONE, value: UnsafeCell::new(MaybeUninit::uninit()) };
+}
+
+/// A fixed-size SPSC queue of `N` nodes linked by indices of type `I`.
+pub struct MicroQueue<T, I: MicroIndex, const N: usize> {
+    slots: [Slot<T, I>; N],
+    /// The sentinel at the front of the element list, owned by the consumer.
+    head: Cell<I>,
+    /// The last node of the element list, owned by the producer.
+    tail: Cell<I>,
+    /// The sentinel at the front of the free list, owned by the producer.
+    free_head: Cell<I>,
+    /// The last node of the free list, owned by the consumer.
+    free_tail: Cell<I>,
+    /// The first node which has never been used, owned by the producer.
+    fresh: Cell<I>,
+}
+
+impl<T, I: MicroIndex, const N: usize> MicroQueue<T, I, N> {
+    /// Creates a new queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is less than 2, or too large to be addressed by `I`.
+    pub const fn new() -> MicroQueue<T, I, N> {
+        assert!(N >= 2 && N <= I::MAX_NODES, "invalid number of nodes");
+
+        // Node 0 is the element list's sentinel and node 1 the free list's.
+        // The remaining nodes are handed out in order as they are first
+        // needed, so that no links have to be set up here.
+        MicroQueue {
+            slots: [Slot::EMPTY; N],
+            head: Cell::new(I::ZERO),
+            tail: Cell::new(I::ZERO),
+            free_head: Cell::new(I::ONE),
+            free_tail: Cell::new(I::ONE),
+            fresh: Cell::new(I::TWO),
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    pub fn capacity(&self) -> usize {
+        N - 2
+    }
+
+    /// Splits the queue into producer and consumer halves, which borrow it.
+    pub fn split(&mut self) -> (MicroProducer<'_, T, I, N>, MicroConsumer<'_, T, I, N>) {
+        let queue = &*self;
+        (MicroProducer { queue }, MicroConsumer { queue })
+    }
+
+    fn next(&self, index: I) -> &I::Atomic {
+        &self.slots[index.to_usize()].next
+    }
+
+    fn value(&self, index: I) -> *mut MaybeUninit<T> {
+        self.slots[index.to_usize()].value.get()
+    }
+}
+
+impl<T, I: MicroIndex, const N: usize> Default for MicroQueue<T, I, N> {
+    fn default() -> MicroQueue<T, I, N> {
+        MicroQueue::new()
+    }
+}
+
+impl<T, I: MicroIndex, const N: usize> Drop for MicroQueue<T, I, N> {
+    fn drop(&mut self) {
+        let mut current = I::load(self.next(self.head.get()), RELAXED);
+        while current != I::NONE {
+            unsafe { (*self.value(current)).assume_init_drop() };
+            current = I::load(self.next(current), RELAXED);
+        }
+    }
+}
+
+/// The producer half of a [`MicroQueue`].
+///
+/// [`MicroQueue`]: crate::micro::MicroQueue
+pub struct MicroProducer<'a, T, I: MicroIndex, const N: usize> {
+    queue: &'a MicroQueue<T, I, N>,
+}
+
+unsafe impl<'a, T: Send, I: MicroIndex, const N: usize> Send for MicroProducer<'a, T, I, N> {}
+
+impl<'a, T, I: MicroIndex, const N: usize> MicroProducer<'a, T, I, N> {
+    /// Adds an element to the queue, returning it in an error if every node is
+    /// in use.
+    pub fn push(&mut self, value: T) -> Result<(), PushError<T>> {
+        let queue = self.queue;
+
+        // Take the free list's sentinel, making its successor the new
+        // sentinel, or else a node which has never been used.
+        let sentinel = queue.free_head.get();
+        let next = I::load(queue.next(sentinel), ACQUIRE);
+        let node = if next != I::NONE {
+            queue.free_head.set(next);
+            sentinel
+        } else if queue.fresh.get().to_usize() < N {
+            let node = queue.fresh.get();
+            queue.fresh.set(I::from_usize(node.to_usize() + 1));
+            node
+        } else {
+            return Err(PushError::Full(value));
+        };
+
+        unsafe { (*queue.value(node)).write(value) };
+        I::store(queue.next(node), I::NONE, RELAXED);
+        I::store(queue.next(queue.tail.get()), node, RELEASE);
+        queue.tail.set(node);
+
+        Ok(())
+    }
+}
+
+/// The consumer half of a [`MicroQueue`].
+///
+/// [`MicroQueue`]: crate::micro::MicroQueue
+pub struct MicroConsumer<'a, T, I: MicroIndex, const N: usize> {
+    queue: &'a MicroQueue<T, I, N>,
+}
+
+unsafe impl<'a, T: Send, I: MicroIndex, const N: usize> Send for MicroConsumer<'a, T, I, N> {}
+
+impl<'a, T, I: MicroIndex, const N: usize> MicroConsumer<'a, T, I, N> {
+    /// Attempts to remove and return an element from the queue. Returns `None`
+    /// if the queue is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let queue = self.queue;
+
+        // The first element's node becomes the new sentinel, and the old
+        // sentinel is passed back to the producer on the free list.
+        let head = queue.head.get();
+        let next = I::load(queue.next(head), ACQUIRE);
+        if next == I::NONE {
+            return None;
+        }
+        let value = unsafe { (*queue.value(next)).assume_init_read() };
+        queue.head.set(next);
+
+        I::store(queue.next(head), I::NONE, RELAXED);
+        I::store(queue.next(queue.free_tail.get()), head, RELEASE);
+        queue.free_tail.set(head);
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn recycles_nodes() {
+        let mut queue = MicroQueue::<alloc::string::String, u8, 4>::new();
+        assert_eq!(queue.capacity(), 2);
+
+        let (mut producer, mut consumer) = queue.split();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                for i in 0..1000 {
+                    let mut value = alloc::format!("{}", i);
+                    while let Err(rejected) = producer.push(value) {
+                        value = rejected.into_inner();
+                    }
+                }
+            });
+
+            for i in 0..1000 {
+                loop {
+                    if let Some(value) = consumer.pop() {
+                        assert_eq!(value, alloc::format!("{}", i));
+                        break;
+                    }
+                }
+            }
+        });
+
+        let (mut producer, _) = queue.split();
+        producer.push(alloc::string::String::from("dropped")).unwrap();
+        producer.push(alloc::string::String::from("dropped")).unwrap();
+        assert!(producer.push(alloc::string::String::new()).is_err());
+    }
+
+    #[test]
+    fn const_new() {
+        let mut queue = const { MicroQueue::<u16, u16, 300>::new() };
+        let (mut producer, mut consumer) = queue.split();
+
+        for i in 0..298 {
+            producer.push(i).unwrap();
+        }
+        assert!(producer.push(298).is_err());
+        for i in 0..298 {
+            assert_eq!(consumer.pop(), Some(i));
+        }
+        assert_eq!(consumer.pop(), None);
+        producer.push(298).unwrap();
+        assert_eq!(consumer.pop(), Some(298));
+    }
+}
@@ -0,0 +1,243 @@
+//! Property testing against a reference model.
+//!
+//! With the `arbitrary` feature enabled, [`Node`], [`Chain`] and [`Queue`]
+//! implement [`Arbitrary`], so that they can be generated by fuzzers and
+//! property testing tools built on the `arbitrary` crate. This module adds
+//! [`Op`], a queue operation which can itself be generated, and [`check()`],
+//! which runs a sequence of operations against a queue and a pool and
+//! compares every observable result with a simple reference model. A fuzz
+//! target or property test for llq, or for a wrapper around it, can then be
+//! a single call.
+//!
+//! ```rust
+//! use arbitrary::Unstructured;
+//! use llq::model::{self, Op};
+//!
+//! let bytes = [7, 1, 42, 0, 3, 9, 2, 5, 1, 8, 0, 4];
+//! let ops: Vec<Op<u8>> = Unstructured::new(&bytes).arbitrary().unwrap();
+//! model::check(&ops).unwrap();
+//!
+//! let ops = [Op::Push(1), Op::PushFromPool(2), Op::Pop, Op::Recycle, Op::Pop];
+//! model::check(&ops).unwrap();
+//! ```
+//!
+//! [`Node`]: crate::Node
+//! [`Chain`]: crate::Chain
+//! [`Queue`]: crate::Queue
+//! [`Arbitrary`]: https://docs.rs/arbitrary/1/arbitrary/trait.Arbitrary.html
+//! [`Op`]: crate::model::Op
+//! [`check()`]: crate::model::check
+
+use crate::pool::Pool;
+use crate::{Chain, Node, Queue};
+
+use alloc::collections::VecDeque;
+use arbitrary::{Arbitrary, Result, Unstructured};
+use core::fmt;
+
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Node<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Node<T>> {
+        Ok(Node::new(T::arbitrary(u)?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        T::size_hint(depth)
+    }
+}
+
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Chain<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Chain<T>> {
+        let mut chain = Chain::new();
+        for value in u.arbitrary_iter::<T>()? {
+            chain.push_back(Node::new(value?));
+        }
+        Ok(chain)
+    }
+}
+
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Queue<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Queue<T>> {
+        let chain = Chain::arbitrary(u)?;
+        Ok(Queue::with_initial(chain))
+    }
+}
+
+/// An operation on a queue and a pool, as run by [`check()`].
+///
+/// [`check()`]: crate::model::check
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Op<T> {
+    /// Pushes the value in a newly allocated node.
+    Push(T),
+    /// Pushes the value in a node taken from the pool, or in a newly
+    /// allocated node if the pool is empty.
+    PushFromPool(T),
+    /// Pops an element and drops it.
+    Pop,
+    /// Pops an element and recycles its node into the pool.
+    Recycle,
+    /// Retracts the most recently pushed element and recycles its node into
+    /// the pool.
+    Retract,
+}
+
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Op<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Op<T>> {
+        Ok(match u.choose_index(5)? {
+            0 => Op::Push(T::arbitrary(u)?),
+            1 => Op::PushFromPool(T::arbitrary(u)?),
+            2 => Op::Pop,
+            3 => Op::Recycle,
+            _ => Op::Retract,
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        let (_, upper) = T::size_hint(depth);
+        (0, upper.and_then(|upper| upper.checked_add(4)))
+    }
+}
+
+/// Something observed while running an operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Observation<T> {
+    /// The element returned by a pop or retraction, if any.
+    Element(Option<T>),
+    /// The number of allocations in the pool after the operation.
+    PoolLen(usize),
+}
+
+/// A difference between the queue and the reference model, as returned by
+/// [`check()`].
+///
+/// [`check()`]: crate::model::check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch<T> {
+    /// The index of the operation after which the difference was observed.
+    pub step: usize,
+    /// What the reference model predicted.
+    pub expected: Observation<T>,
+    /// What the queue did.
+    pub actual: Observation<T>,
+}
+
+impl<T: fmt::Debug> fmt::Display for Mismatch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "step {}: expected {:?}, got {:?}", self.step, self.expected, self.actual)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> std::error::Error for Mismatch<T> {}
+
+/// Runs `ops` on a single thread against a queue with retraction enabled
+/// and a pool, comparing each element popped or retracted and the length of
+/// the pool with a reference model. Returns the first difference found.
+pub fn check<T: Clone + PartialEq>(ops: &[Op<T>]) -> core::result::Result<(), Mismatch<T>> {
+    let mut queue = Queue::new();
+    queue.enable_retraction();
+    let (mut producer, mut consumer) = queue.split();
+    let mut pool = Pool::new();
+
+    let mut model = VecDeque::new();
+    let mut pooled = 0;
+    // Whether the most recently pushed element can still be retracted.
+    let mut retractable = false;
+
+    let mut result = Ok(());
+    for (step, op) in ops.iter().enumerate() {
+        let compare = |expected, actual| {
+            if expected != actual {
+                Err(Mismatch { step, expected, actual })
+            } else {
+                Ok(())
+            }
+        };
+
+        let outcome = match op {
+            Op::Push(value) => {
+                producer.push(Node::new(value.clone()));
+                model.push_back(value.clone());
+                retractable = true;
+                Ok(())
+            }
+            Op::PushFromPool(value) => {
+                let node = match pool.try_alloc(value.clone()) {
+                    Ok(node) => node,
+                    Err(err) => Node::new(err.into_inner()),
+                };
+                producer.push(node);
+                model.push_back(value.clone());
+                pooled = usize::saturating_sub(pooled, 1);
+                retractable = true;
+                Ok(())
+            }
+            Op::Pop | Op::Recycle => {
+                let expected = model.pop_front();
+                if model.is_empty() {
+                    retractable = false;
+                }
+
+                let actual = consumer.pop().map(|node| {
+                    if *op == Op::Recycle {
+                        pooled += 1;
+                        pool.recycle(node)
+                    } else {
+                        Node::into_inner(node)
+                    }
+                });
+                compare(Observation::Element(expected), Observation::Element(actual))
+            }
+            Op::Retract => {
+                let expected = if retractable { model.pop_back() } else { None };
+                retractable = false;
+
+                let actual = producer.try_retract().map(|node| {
+                    pooled += 1;
+                    pool.recycle(node)
+                });
+                compare(Observation::Element(expected), Observation::Element(actual))
+            }
+        };
+
+        result = outcome
+            .and_then(|()| compare(Observation::PoolLen(pooled), Observation::PoolLen(pool.len())));
+        if result.is_err() {
+            break;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    #[test]
+    fn model() {
+        let ops = [
+            Op::Push(1),
+            Op::PushFromPool(2),
+            Op::Retract,
+            Op::Retract,
+            Op::Recycle,
+            Op::PushFromPool(3),
+            Op::Pop,
+            Op::Pop,
+        ];
+        assert_eq!(check(&ops), Ok(()));
+
+        for seed in 0..64u8 {
+            let bytes: Vec<u8> = (0..64u8).map(|i| seed ^ i.wrapping_mul(37)).collect();
+            let ops: Vec<Op<u8>> = Unstructured::new(&bytes).arbitrary().unwrap();
+            assert_eq!(check(&ops), Ok(()));
+        }
+
+        let queue: Queue<u8> = Unstructured::new(&[1, 2, 3]).arbitrary().unwrap();
+        let (_producer, consumer) = queue.split();
+        assert!(consumer.count_queued() <= 3);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{Chain, Node, Producer};
+
+use core::fmt;
+use core::ops::Deref;
+use core::str;
+
+/// A string with a fixed capacity of `N` bytes, stored inline.
+///
+/// Because its contents live inside the value itself, a `NodeStr` stored in a
+/// [`Node`] can be built, sent and reused without ever touching the
+/// allocator. Appending text which does not fit truncates it at a character
+/// boundary instead of reallocating.
+///
+/// ```rust
+/// use llq::{Node, NodeStr};
+///
+/// let mut node = Node::new(NodeStr::<8>::new());
+/// assert!(node.push_str("xrun"));
+/// assert!(!node.push_str(" at 42"));
+/// assert_eq!(node.as_str(), "xrun at ");
+/// ```
+///
+/// [`Node`]: crate::Node
+#[derive(Copy, Clone)]
+pub struct NodeStr<const N: usize> {
+    len: usize,
+    buf: [u8; N],
+}
+
+impl<const N: usize> NodeStr<N> {
+    /// Creates a new, empty string.
+    pub const fn new() -> NodeStr<N> {
+        NodeStr { len: 0, buf: [0; N] }
+    }
+
+    /// Returns the capacity of the string in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the length of the string in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes which can still be appended.
+    pub fn remaining(&self) -> usize {
+        N - self.len
+    }
+
+    /// Returns the contents of the string.
+    pub fn as_str(&self) -> &str {
+        // The buffer only ever has complete UTF-8 sequences written to it.
+        unsafe { str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+
+    /// Truncates the string to zero length.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Appends as much of `s` as fits, truncating at a character boundary.
+    /// Returns `true` if all of `s` was appended.
+    pub fn push_str(&mut self, s: &str) -> bool {
+        let mut end = s.len().min(self.remaining());
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        self.buf[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+
+        end == s.len()
+    }
+
+    /// Appends a character if it fits. Returns `true` if it was appended.
+    pub fn push(&mut self, c: char) -> bool {
+        if c.len_utf8() > self.remaining() {
+            return false;
+        }
+
+        self.push_str(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl<const N: usize> Default for NodeStr<N> {
+    fn default() -> NodeStr<N> {
+        NodeStr::new()
+    }
+}
+
+impl<const N: usize> Deref for NodeStr<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> AsRef<str> for NodeStr<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> PartialEq for NodeStr<N> {
+    fn eq(&self, other: &NodeStr<N>) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const N: usize> Eq for NodeStr<N> {}
+
+impl<const N: usize> PartialEq<str> for NodeStr<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for NodeStr<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<const N: usize> fmt::Debug for NodeStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Display for NodeStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Write for NodeStr<N> {
+    /// Appends as much of `s` as fits. Never fails; text which does not fit is
+    /// discarded.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+/// A [`fmt::Write`] adapter which formats text into preallocated
+/// [`NodeStr`] nodes and pushes them onto a queue.
+///
+/// Text is written into the current node until [`flush()`] pushes it. Nodes
+/// are taken from a free list which is replenished with [`recycle()`], so
+/// formatting never allocates; text which does not fit in a node is
+/// truncated, and writing fails with [`fmt::Error`] if no free node is
+/// available.
+///
+/// ```rust
+/// use core::fmt::Write;
+/// use llq::{Node, NodeStr, Queue, StrWriter};
+///
+/// let (producer, mut consumer) = Queue::<NodeStr<32>>::new().split();
+/// let mut log = StrWriter::new(producer);
+/// log.recycle(Node::new(NodeStr::new()));
+///
+/// write!(log, "xrun at {}", 1024).unwrap();
+/// log.flush();
+///
+/// let message = consumer.pop().unwrap();
+/// assert_eq!(message.as_str(), "xrun at 1024");
+/// log.recycle(message);
+/// ```
+///
+/// [`fmt::Write`]: core::fmt::Write
+/// [`fmt::Error`]: core::fmt::Error
+/// [`NodeStr`]: crate::NodeStr
+/// [`flush()`]: crate::StrWriter::flush
+/// [`recycle()`]: crate::StrWriter::recycle
+pub struct StrWriter<const N: usize> {
+    producer: Producer<NodeStr<N>>,
+    free: Chain<NodeStr<N>>,
+    current: Option<Node<NodeStr<N>>>,
+}
+
+impl<const N: usize> StrWriter<N> {
+    /// Creates a new writer which pushes onto `producer`. The writer starts
+    /// with no free nodes.
+    pub fn new(producer: Producer<NodeStr<N>>) -> StrWriter<N> {
+        StrWriter { producer, free: Chain::new(), current: None }
+    }
+
+    /// Adds a node to the writer's free list. Its contents are cleared before
+    /// it is written to.
+    pub fn recycle(&mut self, node: Node<NodeStr<N>>) {
+        self.free.push_back(node);
+    }
+
+    /// Returns the number of nodes in the free list, not counting a
+    /// partially written node.
+    pub fn free_len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Pushes the text written since the last flush as a single node. Returns
+    /// `false` if nothing has been written.
+    pub fn flush(&mut self) -> bool {
+        match self.current.take() {
+            Some(node) => {
+                self.producer.push(node);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the inner producer and free list. A partially written node is
+    /// returned to the free list.
+    pub fn into_inner(mut self) -> (Producer<NodeStr<N>>, Chain<NodeStr<N>>) {
+        if let Some(node) = self.current.take() {
+            self.free.push_back(node);
+        }
+        (self.producer, self.free)
+    }
+}
+
+impl<const N: usize> fmt::Write for StrWriter<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.current.is_none() {
+            let mut node = self.free.pop_front().ok_or(fmt::Error)?;
+            node.clear();
+            self.current = Some(node);
+        }
+
+        if let Some(node) = &mut self.current {
+            node.push_str(s);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncation() {
+        let mut s = NodeStr::<5>::new();
+        assert!(s.push_str("ab"));
+        assert!(!s.push_str("cdéf"));
+        // "é" is two bytes and does not fit after "abcd".
+        assert_eq!(s, "abcd");
+        assert!(!s.push('é'));
+        assert!(s.push('e'));
+        assert_eq!(s.remaining(), 0);
+
+        s.clear();
+        assert!(s.is_empty());
+        assert!(s.push('é'));
+        assert_eq!(s.len(), 2);
+    }
+
+    #[test]
+    fn str_writer() {
+        use crate::Queue;
+        use core::fmt::Write;
+
+        let (producer, mut consumer) = Queue::<NodeStr<8>>::new().split();
+        let mut writer = StrWriter::new(producer);
+        assert!(write!(writer, "a").is_err());
+
+        writer.recycle(Node::new(NodeStr::new()));
+        write!(writer, "{} {}", 1, 23456789).unwrap();
+        assert!(writer.flush());
+        assert!(!writer.flush());
+
+        let node = consumer.pop().unwrap();
+        assert_eq!(node.as_str(), "1 234567");
+        writer.recycle(node);
+
+        write!(writer, "b").unwrap();
+        writer.flush();
+        assert_eq!(consumer.pop().unwrap().as_str(), "b");
+    }
+}
@@ -0,0 +1,68 @@
+//! Hooks for observing the elements passing through a queue.
+//!
+//! An [`Observer`] installed with [`Queue::set_observer()`] is called with a
+//! reference to each element as it is pushed and popped, and with each
+//! element still in the queue when it is dropped. This can be used for
+//! telemetry, invariant checking or recording a queue's traffic.
+//!
+//! The hooks run inline on the producer and consumer threads, so they should
+//! be as cheap as the code around them requires. Without the `observer`
+//! feature, the hooks are compiled out entirely.
+//!
+//! ```rust
+//! use llq::observer::Observer;
+//! use llq::{Node, Queue};
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! struct Count(Arc<AtomicUsize>);
+//!
+//! impl Observer<u32> for Count {
+//!     fn on_push(&self, _value: &u32) {
+//!         self.0.fetch_add(1, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! let count = Arc::new(AtomicUsize::new(0));
+//! let mut queue = Queue::new();
+//! queue.set_observer(Count(count.clone()));
+//!
+//! let (mut producer, _consumer) = queue.split();
+//! producer.push(Node::new(1));
+//! assert_eq!(count.load(Ordering::Relaxed), 1);
+//! ```
+//!
+//! [`Observer`]: crate::observer::Observer
+//! [`Queue::set_observer()`]: crate::Queue::set_observer
+
+/// Hooks called as elements pass through a queue.
+///
+/// The producer and consumer may call hooks concurrently, so the observer
+/// must be `Sync`. Every hook has an empty default implementation.
+pub trait Observer<T>: Send + Sync {
+    /// Called by the producer with each element it pushes.
+    fn on_push(&self, value: &T) {
+        let _ = value;
+    }
+
+    /// Called by the consumer with each element it pops, including elements
+    /// evicted under the [`Overflow::DropOldest`] policy.
+    ///
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    fn on_pop(&self, value: &T) {
+        let _ = value;
+    }
+
+    /// Called with each element still in the queue when it is dropped.
+    fn on_drop_unconsumed(&self, value: &T) {
+        let _ = value;
+    }
+
+    /// Called by the producer with each element which
+    /// [`Producer::try_push()`] rejects because the queue is full.
+    ///
+    /// [`Producer::try_push()`]: crate::Producer::try_push
+    fn on_reject(&self, value: &T) {
+        let _ = value;
+    }
+}
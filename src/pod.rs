@@ -0,0 +1,75 @@
+use crate::Node;
+
+#[cfg(feature = "alloc-constructors")]
+use bytemuck::NoUninit;
+use bytemuck::{Pod, PodCastError};
+
+/// Reinterpretation of byte nodes as slices of plain-old-data values.
+///
+/// A node's payload follows the node's link pointer, so the payload of a
+/// `Node<[u8; N]>` is always aligned to at least the alignment of a pointer.
+/// Casting it to a slice of `f32`, `i16`, `u64` and the like therefore only
+/// fails if `N` is not a multiple of the element size.
+///
+/// ```rust
+/// use llq::{Node, Queue};
+///
+/// let (mut producer, mut consumer) = Queue::<[u8; 16]>::new().split();
+///
+/// producer.push(Node::from_pod_slice(&[0.5f32, 0.25, -0.5, 1.0]).unwrap());
+///
+/// let mut node = consumer.pop().unwrap();
+/// let block: &mut [f32] = node.try_cast_slice_mut().unwrap();
+/// block[1] *= 2.0;
+/// assert_eq!(node.try_cast_slice::<f32>().unwrap(), [0.5, 0.5, -0.5, 1.0]);
+/// ```
+impl<const N: usize> Node<[u8; N]> {
+    /// Creates a byte node holding the bytes of `values`, followed by zeros
+    /// if they are shorter than `N` bytes. Fails with
+    /// [`PodCastError::SizeMismatch`] if they are longer.
+    ///
+    /// [`PodCastError::SizeMismatch`]: https://docs.rs/bytemuck/1/bytemuck/enum.PodCastError.html
+    #[cfg(feature = "alloc-constructors")]
+    pub fn from_pod_slice<T: NoUninit>(values: &[T]) -> Result<Node<[u8; N]>, PodCastError> {
+        let bytes = bytemuck::cast_slice::<T, u8>(values);
+        if bytes.len() > N {
+            return Err(PodCastError::SizeMismatch);
+        }
+
+        let mut node = Node::new([0; N]);
+        node[..bytes.len()].copy_from_slice(bytes);
+        Ok(node)
+    }
+
+    /// Returns the payload as a slice of `T`, checking its size and
+    /// alignment.
+    pub fn try_cast_slice<T: Pod>(&self) -> Result<&[T], PodCastError> {
+        bytemuck::try_cast_slice(&self[..])
+    }
+
+    /// Returns the payload as a mutable slice of `T`, checking its size and
+    /// alignment.
+    pub fn try_cast_slice_mut<T: Pod>(&mut self) -> Result<&mut [T], PodCastError> {
+        bytemuck::try_cast_slice_mut(&mut self[..])
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cast_payload() {
+        let node = Node::<[u8; 12]>::from_pod_slice(&[1u32, 2]).unwrap();
+        assert_eq!(node.try_cast_slice::<u32>().unwrap(), [1, 2, 0]);
+        assert_eq!(node.try_cast_slice::<u64>(), Err(PodCastError::OutputSliceWouldHaveSlop));
+        assert_eq!(
+            Node::<[u8; 4]>::from_pod_slice(&[1u32, 2]).err(),
+            Some(PodCastError::SizeMismatch)
+        );
+
+        let mut node = Node::<[u8; 16]>::from_pod_slice(&[3u64]).unwrap();
+        node.try_cast_slice_mut::<u64>().unwrap()[1] = 4;
+        assert_eq!(node.try_cast_slice::<u64>().unwrap(), [3, 4]);
+    }
+}
@@ -0,0 +1,691 @@
+//! Reuse of node allocations.
+//!
+//! A [`Pool`] keeps a free list of node allocations which no longer hold a
+//! value. [`Pool::alloc()`] takes an allocation from the free list if one is
+//! available, and [`Pool::recycle()`] moves the value out of a node and
+//! returns its allocation to the free list, so that a steady stream of
+//! messages can be sent without touching the allocator.
+//!
+//! A pool which served a burst of traffic can release its surplus
+//! allocations with [`Pool::shrink_to()`] or [`Pool::trim_idle()`].
+//!
+//! ```rust
+//! use llq::pool::Pool;
+//! use llq::Queue;
+//!
+//! let (mut producer, mut consumer) = Queue::new().split();
+//! let mut pool = Pool::with_capacity(2);
+//!
+//! producer.push(pool.try_alloc(1).unwrap());
+//! producer.push(pool.try_alloc(2).unwrap());
+//! assert!(pool.try_alloc(3).is_err());
+//!
+//! let node = consumer.pop().unwrap();
+//! assert_eq!(pool.recycle(node), 1);
+//! assert_eq!(pool.len(), 1);
+//!
+//! let node = consumer.pop().unwrap();
+//! assert_eq!(pool.recycle(node), 2);
+//! ```
+//!
+//! [`SharedPool`] provides the same operations through a shared reference,
+//! using a lock-free stack, so that a single set of allocations can be
+//! recycled across any number of queues and threads.
+//!
+//! With the `leak-check` feature enabled, each pool counts the allocations
+//! it has made and deallocated, and dropping a pool panics if some of the
+//! allocations it made are not back in its free list, since they have most
+//! likely been leaked along with a forgotten node or container. Nodes taken
+//! from a pool, including those used as the sentinels of queues, must then be
+//! recycled into it before it is dropped rather than dropped elsewhere.
+//! Allocations which did not come from a pool, such as nodes created with
+//! [`Node::new()`] and the nodes a [`Queue`] allocates for itself, are not
+//! tracked.
+//!
+//! [`Pool`]: crate::pool::Pool
+//! [`SharedPool`]: crate::pool::SharedPool
+//! [`Pool::alloc()`]: crate::pool::Pool::alloc
+//! [`Pool::recycle()`]: crate::pool::Pool::recycle
+//! [`Pool::shrink_to()`]: crate::pool::Pool::shrink_to
+//! [`Pool::trim_idle()`]: crate::pool::Pool::trim_idle
+//! [`Node::new()`]: crate::Node::new
+//! [`Queue`]: crate::Queue
+
+use crate::error::AllocError;
+use crate::sync::{AtomicPtr, AtomicUsize, ACQUIRE, RELAXED, RELEASE};
+use crate::{Node, NodeInner};
+
+use core::marker::PhantomData;
+use core::mem;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::ptr::NonNull;
+#[cfg(feature = "async")]
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A free list of node allocations.
+pub struct Pool<T> {
+    free: *mut NodeInner<T>,
+    len: usize,
+    low_water: usize,
+    #[cfg(feature = "leak-check")]
+    allocated: usize,
+    #[cfg(feature = "accounting")]
+    taken: usize,
+    #[cfg(feature = "accounting")]
+    returned: usize,
+    phantom: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Pool<T> {}
+unsafe impl<T: Sync> Sync for Pool<T> {}
+
+impl<T> Pool<T> {
+    /// Creates a new, empty pool.
+    pub fn new() -> Pool<T> {
+        Pool {
+            free: ptr::null_mut(),
+            len: 0,
+            low_water: 0,
+            #[cfg(feature = "leak-check")]
+            allocated: 0,
+            #[cfg(feature = "accounting")]
+            taken: 0,
+            #[cfg(feature = "accounting")]
+            returned: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates a new pool holding `capacity` allocations.
+    pub fn with_capacity(capacity: usize) -> Pool<T> {
+        let mut pool = Pool::new();
+        pool.reserve(capacity);
+        pool
+    }
+
+    /// Allocates `additional` nodes and adds them to the free list.
+    pub fn reserve(&mut self, additional: usize) {
+        for _ in 0..additional {
+            let node = NodeInner::alloc_uninit().as_ptr();
+            unsafe { self.push_free(node) };
+            #[cfg(feature = "leak-check")]
+            {
+                self.allocated = self.allocated.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Returns the number of allocations in the free list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the free list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a node containing `value`, taking its allocation from the free
+    /// list if possible and allocating a new one otherwise.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn alloc(&mut self, value: T) -> Node<T> {
+        match self.try_alloc(value) {
+            Ok(node) => node,
+            Err(err) => {
+                #[cfg(feature = "leak-check")]
+                {
+                    self.allocated = self.allocated.wrapping_add(1);
+                }
+                #[cfg(feature = "accounting")]
+                {
+                    self.taken = self.taken.wrapping_add(1);
+                }
+                Node::new(err.into_inner())
+            }
+        }
+    }
+
+    /// Returns a node containing `value` if an allocation is available in the
+    /// free list. Never allocates; returns the value in an error if the free
+    /// list is empty.
+    pub fn try_alloc(&mut self, value: T) -> Result<Node<T>, AllocError<T>> {
+        #[cfg(feature = "fault-injection")]
+        {
+            if crate::fault::pools_exhausted() {
+                return Err(AllocError::Exhausted(value));
+            }
+        }
+
+        let node = self.free;
+        if node.is_null() {
+            return Err(AllocError::Exhausted(value));
+        }
+
+        unsafe {
+            self.free = (*node).next.load(RELAXED);
+            self.len -= 1;
+            self.low_water = self.low_water.min(self.len);
+            #[cfg(feature = "accounting")]
+            {
+                self.taken = self.taken.wrapping_add(1);
+            }
+
+            (*node).next.store(ptr::null_mut(), RELAXED);
+            (*node).data = MaybeUninit::new(value);
+
+            Ok(Node { inner: NonNull::new_unchecked(node), phantom: PhantomData })
+        }
+    }
+
+    /// Moves the value out of `node` and adds its allocation to the free
+    /// list.
+    pub fn recycle(&mut self, node: Node<T>) -> T {
+        #[cfg(feature = "accounting")]
+        {
+            self.returned = self.returned.wrapping_add(1);
+        }
+
+        unsafe {
+            let inner = node.inner.as_ptr();
+            mem::forget(node);
+
+            let value = ptr::read((*inner).data.as_ptr());
+            self.push_free(inner);
+            value
+        }
+    }
+
+    /// Deallocates nodes from the free list until it holds at most `len`
+    /// allocations.
+    pub fn shrink_to(&mut self, len: usize) {
+        while self.len > len {
+            unsafe {
+                let node = self.free;
+                self.free = (*node).next.load(RELAXED);
+                NodeInner::free(node);
+            }
+            self.len -= 1;
+            #[cfg(feature = "leak-check")]
+            {
+                self.allocated = self.allocated.wrapping_sub(1);
+            }
+        }
+        self.low_water = self.low_water.min(self.len);
+    }
+
+    /// Deallocates every allocation which has remained unused in the free
+    /// list since the previous call to `trim_idle()`, returning the number of
+    /// allocations released.
+    ///
+    /// Calling this periodically lets a pool which grew during a burst of
+    /// traffic return to the size it needs in the steady state.
+    pub fn trim_idle(&mut self) -> usize {
+        let idle = self.low_water;
+        self.shrink_to(self.len - idle);
+        self.low_water = self.len;
+        idle
+    }
+
+    unsafe fn push_free(&mut self, node: *mut NodeInner<T>) {
+        (*node).next.store(self.free, RELAXED);
+        self.free = node;
+        self.len += 1;
+    }
+
+    /// Returns the number of nodes taken from the pool which have not been
+    /// recycled into it yet.
+    #[cfg(feature = "accounting")]
+    pub(crate) fn outstanding_nodes(&self) -> usize {
+        outstanding(self.taken, self.returned)
+    }
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Pool<T> {
+        Pool::new()
+    }
+}
+
+impl<T> Drop for Pool<T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "leak-check")]
+        check_leaks(self.allocated, self.len);
+        self.shrink_to(0);
+    }
+}
+
+/// A free list of node allocations which can be shared between threads.
+///
+/// Allocations are kept on a lock-free stack. Any thread may recycle a node
+/// into the pool or take an allocation from it, so a `SharedPool` stored in a
+/// `static` or an `Arc` can absorb the allocator traffic of many short-lived
+/// queues.
+///
+/// Taking an allocation briefly detaches the whole stack, so a concurrent
+/// [`try_alloc()`] on another thread may fail even though the pool is not
+/// empty, in which case [`alloc()`] falls back to the allocator.
+///
+/// ```rust
+/// use llq::pool::SharedPool;
+/// use llq::Queue;
+///
+/// static POOL: SharedPool<u32> = SharedPool::new();
+///
+/// let (mut producer, mut consumer) = Queue::new().split();
+/// std::thread::spawn(move || producer.push(POOL.alloc(1))).join().unwrap();
+///
+/// let node = consumer.pop().unwrap();
+/// assert_eq!(POOL.recycle(node), 1);
+/// assert_eq!(POOL.len(), 1);
+/// ```
+///
+/// [`try_alloc()`]: crate::pool::SharedPool::try_alloc
+/// [`alloc()`]: crate::pool::SharedPool::alloc
+pub struct SharedPool<T> {
+    head: AtomicPtr<NodeInner<T>>,
+    len: AtomicUsize,
+    #[cfg(feature = "leak-check")]
+    allocated: AtomicUsize,
+    #[cfg(feature = "accounting")]
+    taken: AtomicUsize,
+    #[cfg(feature = "accounting")]
+    returned: AtomicUsize,
+    #[cfg(feature = "async")]
+    waker: atomic_waker::AtomicWaker,
+    phantom: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for SharedPool<T> {}
+unsafe impl<T: Send> Sync for SharedPool<T> {}
+
+impl<T> SharedPool<T> {
+    /// Creates a new, empty pool.
+    pub const fn new() -> SharedPool<T> {
+        SharedPool {
+            head: AtomicPtr::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
+            #[cfg(feature = "leak-check")]
+            allocated: AtomicUsize::new(0),
+            #[cfg(feature = "accounting")]
+            taken: AtomicUsize::new(0),
+            #[cfg(feature = "accounting")]
+            returned: AtomicUsize::new(0),
+            #[cfg(feature = "async")]
+            waker: atomic_waker::AtomicWaker::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates a new pool holding `capacity` allocations.
+    pub fn with_capacity(capacity: usize) -> SharedPool<T> {
+        let pool = SharedPool::new();
+        pool.reserve(capacity);
+        pool
+    }
+
+    /// Allocates `additional` nodes and adds them to the free list.
+    pub fn reserve(&self, additional: usize) {
+        for _ in 0..additional {
+            let node = NodeInner::alloc_uninit().as_ptr();
+            unsafe { self.push_free(node) };
+            #[cfg(feature = "leak-check")]
+            self.allocated.fetch_add(1, RELAXED);
+        }
+    }
+
+    /// Returns the number of allocations in the free list. The count may be
+    /// out of date by the time it is returned.
+    pub fn len(&self) -> usize {
+        self.len.load(RELAXED)
+    }
+
+    /// Returns `true` if the free list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a node containing `value`, taking its allocation from the free
+    /// list if possible and allocating a new one otherwise.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn alloc(&self, value: T) -> Node<T> {
+        match self.try_alloc(value) {
+            Ok(node) => node,
+            Err(err) => {
+                #[cfg(feature = "leak-check")]
+                self.allocated.fetch_add(1, RELAXED);
+                #[cfg(feature = "accounting")]
+                self.taken.fetch_add(1, RELAXED);
+                Node::new(err.into_inner())
+            }
+        }
+    }
+
+    /// Returns a node containing `value` if an allocation is available in the
+    /// free list. Never allocates; returns the value in an error otherwise.
+    pub fn try_alloc(&self, value: T) -> Result<Node<T>, AllocError<T>> {
+        #[cfg(feature = "fault-injection")]
+        {
+            if crate::fault::pools_exhausted() {
+                return Err(AllocError::Exhausted(value));
+            }
+        }
+
+        let node = self.take_all();
+        if node.is_null() {
+            return Err(AllocError::Exhausted(value));
+        }
+
+        unsafe {
+            self.len.fetch_sub(1, RELAXED);
+            #[cfg(feature = "accounting")]
+            self.taken.fetch_add(1, RELAXED);
+            self.put_back((*node).next.load(RELAXED));
+
+            (*node).next.store(ptr::null_mut(), RELAXED);
+            (*node).data = MaybeUninit::new(value);
+
+            Ok(Node { inner: NonNull::new_unchecked(node), phantom: PhantomData })
+        }
+    }
+
+    /// Moves the value out of `node` and adds its allocation to the free
+    /// list.
+    pub fn recycle(&self, node: Node<T>) -> T {
+        unsafe {
+            let inner = node.inner.as_ptr();
+            mem::forget(node);
+
+            let value = ptr::read((*inner).data.as_ptr());
+            self.reclaim(inner);
+            value
+        }
+    }
+
+    /// Deallocates nodes from the free list until it holds at most `len`
+    /// allocations.
+    pub fn shrink_to(&self, len: usize) {
+        let mut list = self.take_all();
+        let mut kept = ptr::null_mut();
+        let mut count = 0;
+
+        unsafe {
+            while !list.is_null() {
+                let next = (*list).next.load(RELAXED);
+                if count < len {
+                    (*list).next.store(kept, RELAXED);
+                    kept = list;
+                    count += 1;
+                } else {
+                    self.len.fetch_sub(1, RELAXED);
+                    #[cfg(feature = "leak-check")]
+                    self.allocated.fetch_sub(1, RELAXED);
+                    NodeInner::free(list);
+                }
+                list = next;
+            }
+
+            self.put_back(kept);
+        }
+    }
+
+    /// Returns an allocation which was in use and no longer holds a value to
+    /// the free list.
+    pub(crate) unsafe fn reclaim(&self, node: *mut NodeInner<T>) {
+        #[cfg(feature = "accounting")]
+        self.returned.fetch_add(1, RELAXED);
+        self.push_free(node);
+    }
+
+    /// Returns the number of nodes taken from the pool which have not been
+    /// recycled into it yet. The count may be out of date by the time it is
+    /// returned.
+    #[cfg(feature = "accounting")]
+    pub(crate) fn outstanding_nodes(&self) -> usize {
+        outstanding(self.taken.load(RELAXED), self.returned.load(RELAXED))
+    }
+
+    /// Adds an allocation which does not hold a value to the free list.
+    unsafe fn push_free(&self, node: *mut NodeInner<T>) {
+        self.len.fetch_add(1, RELAXED);
+
+        let mut head = self.head.load(RELAXED);
+        loop {
+            (*node).next.store(head, RELAXED);
+            match self.head.compare_exchange_weak(head, node, RELEASE, RELAXED) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+
+        #[cfg(feature = "async")]
+        self.waker.wake();
+    }
+
+    /// Detaches the entire free list.
+    ///
+    /// Nodes are only ever removed by detaching the whole stack, rather than
+    /// by swinging the head to its successor, which avoids the ABA problem.
+    fn take_all(&self) -> *mut NodeInner<T> {
+        self.head.swap(ptr::null_mut(), ACQUIRE)
+    }
+
+    /// Returns a detached list of nodes to the free list.
+    unsafe fn put_back(&self, mut list: *mut NodeInner<T>) {
+        while !list.is_null() {
+            match self.head.compare_exchange(ptr::null_mut(), list, RELEASE, RELAXED) {
+                Ok(_) => {
+                    // A task may have found the pool empty while the list was
+                    // detached.
+                    #[cfg(feature = "async")]
+                    self.waker.wake();
+                    return;
+                }
+                Err(_) => {
+                    // Nodes were recycled while the list was detached. Take
+                    // them as well and try again.
+                    let mut other = self.take_all();
+                    while !other.is_null() {
+                        let next = (*other).next.load(RELAXED);
+                        (*other).next.store(list, RELAXED);
+                        list = other;
+                        other = next;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> SharedPool<T> {
+    /// Returns a future which resolves to a node containing `value` once an
+    /// allocation is available in the free list. Never allocates.
+    ///
+    /// The pool can only wake one waiting task at a time, so only one task
+    /// should wait on a given pool at once.
+    pub fn acquire(&self, value: T) -> Acquire<'_, T> {
+        Acquire { pool: self, value: Some(value) }
+    }
+}
+
+/// A future which resolves to a node once an allocation is available.
+///
+/// Returned by [`SharedPool::acquire()`].
+///
+/// [`SharedPool::acquire()`]: crate::pool::SharedPool::acquire
+#[cfg(feature = "async")]
+pub struct Acquire<'a, T> {
+    pool: &'a SharedPool<T>,
+    value: Option<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T> Unpin for Acquire<'_, T> {}
+
+#[cfg(feature = "async")]
+impl<T> Future for Acquire<'_, T> {
+    type Output = Node<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Node<T>> {
+        let this = self.get_mut();
+        let value = this.value.take().expect("`Acquire` polled after completion");
+
+        let value = match this.pool.try_alloc(value) {
+            Ok(node) => return Poll::Ready(node),
+            Err(err) => err.into_inner(),
+        };
+
+        this.pool.waker.register(cx.waker());
+
+        // Check again in case a node was recycled before the waker was
+        // registered.
+        match this.pool.try_alloc(value) {
+            Ok(node) => Poll::Ready(node),
+            Err(err) => {
+                this.value = Some(err.into_inner());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Default for SharedPool<T> {
+    fn default() -> SharedPool<T> {
+        SharedPool::new()
+    }
+}
+
+impl<T> Drop for SharedPool<T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "leak-check")]
+        check_leaks(*self.allocated.get_mut(), *self.len.get_mut());
+        self.shrink_to(0);
+    }
+}
+
+/// Returns the number of nodes taken from a pool and not returned to it.
+///
+/// Nodes from elsewhere which were recycled into the pool make the difference
+/// negative, in which case none are counted as outstanding.
+#[cfg(feature = "accounting")]
+fn outstanding(taken: usize, returned: usize) -> usize {
+    (taken.wrapping_sub(returned) as isize).max(0) as usize
+}
+
+/// Panics if a pool which is being dropped has made more allocations than it
+/// holds, unless the thread is already panicking.
+#[cfg(feature = "leak-check")]
+fn check_leaks(allocated: usize, len: usize) {
+    // Allocations made elsewhere may have been recycled into the pool, in
+    // which case the difference is negative.
+    let leaked = allocated.wrapping_sub(len) as isize;
+    if leaked <= 0 {
+        return;
+    }
+
+    #[cfg(feature = "std")]
+    if std::thread::panicking() {
+        return;
+    }
+
+    panic!("pool dropped while {} of its allocations are still in use", leaked);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "leak-check", feature = "alloc-constructors"))]
+    #[test]
+    #[should_panic(expected = "pool dropped while 1 of its allocations are still in use")]
+    fn leak_check() {
+        let pool = SharedPool::with_capacity(2);
+        let a = pool.alloc(0);
+        let b = pool.alloc(1);
+        let c = pool.alloc(2);
+        pool.recycle(a);
+        pool.recycle(b);
+        mem::forget(c);
+        pool.shrink_to(1);
+    }
+
+    #[test]
+    fn trim() {
+        let mut pool = Pool::with_capacity(4);
+        // Nothing has been idle for a full period yet.
+        assert_eq!(pool.trim_idle(), 0);
+
+        let a = pool.try_alloc(0).unwrap();
+        let b = pool.try_alloc(1).unwrap();
+        assert_eq!(pool.recycle(a), 0);
+        assert_eq!(pool.recycle(b), 1);
+
+        // Only two of the four allocations were used.
+        assert_eq!(pool.trim_idle(), 2);
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.trim_idle(), 2);
+        assert!(pool.is_empty());
+
+        pool.reserve(3);
+        pool.shrink_to(1);
+        assert_eq!(pool.len(), 1);
+        let node = pool.try_alloc(2).unwrap();
+        assert!(pool.try_alloc(3).is_err());
+        assert_eq!(pool.recycle(node), 2);
+    }
+
+    #[test]
+    fn shared() {
+        extern crate std;
+
+        use alloc::sync::Arc;
+
+        let pool = Arc::new(SharedPool::with_capacity(8));
+
+        let threads: alloc::vec::Vec<_> = (0..4)
+            .map(|i| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    // There are never more than four nodes out of the pool.
+                    for j in 0..1000 {
+                        let node = pool.try_alloc(i * 1000 + j).unwrap();
+                        assert_eq!(pool.recycle(node), i * 1000 + j);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert!(pool.len() >= 8);
+        pool.shrink_to(3);
+        assert_eq!(pool.len(), 3);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn acquire() {
+        extern crate std;
+
+        use alloc::sync::Arc;
+        use futures::executor::block_on;
+
+        let pool = Arc::new(SharedPool::with_capacity(1));
+        let node = pool.try_alloc(0).unwrap();
+
+        let thread = {
+            let pool = pool.clone();
+            std::thread::spawn(move || block_on(pool.acquire(1)))
+        };
+
+        std::thread::sleep(core::time::Duration::from_millis(10));
+        assert_eq!(pool.recycle(node), 0);
+        assert_eq!(pool.recycle(thread.join().unwrap()), 1);
+    }
+}
@@ -0,0 +1,201 @@
+//! Rate limiting of pushes.
+//!
+//! [`RateLimitedProducer`] wraps a [`Producer`] with a token bucket, so that a
+//! thread which floods a queue, such as a GUI sending parameter changes to an
+//! audio thread, can be policed at the channel layer. Time is read from a
+//! pluggable [`Clock`].
+//!
+//! ```rust
+//! use core::time::Duration;
+//! use llq::rate::{Clock, RateLimitedProducer};
+//! use llq::{Node, Queue};
+//!
+//! // A clock which never advances.
+//! struct FrozenClock;
+//!
+//! impl Clock for FrozenClock {
+//!     fn now(&mut self) -> Duration {
+//!         Duration::ZERO
+//!     }
+//! }
+//!
+//! let (producer, _consumer) = Queue::new().split();
+//!
+//! // One push every 10ms, with bursts of up to 2.
+//! let mut producer = RateLimitedProducer::new(producer, FrozenClock, Duration::from_millis(10), 2);
+//! assert!(producer.try_push(Node::new(0)).is_ok());
+//! assert!(producer.try_push(Node::new(1)).is_ok());
+//! assert!(producer.try_push(Node::new(2)).is_err());
+//! assert_eq!(producer.time_until_ready(), Duration::from_millis(10));
+//! ```
+//!
+//! [`RateLimitedProducer`]: crate::rate::RateLimitedProducer
+//! [`Producer`]: crate::Producer
+//! [`Clock`]: crate::rate::Clock
+
+use crate::error::PushError;
+use crate::{Node, Producer};
+
+use core::time::Duration;
+
+/// A source of monotonic time for a [`RateLimitedProducer`].
+///
+/// [`RateLimitedProducer`]: crate::rate::RateLimitedProducer
+pub trait Clock {
+    /// Returns the time elapsed since an arbitrary fixed point. Successive
+    /// calls must never return decreasing values.
+    fn now(&mut self) -> Duration;
+}
+
+/// A [`Clock`] backed by [`std::time::Instant`].
+///
+/// [`Clock`]: crate::rate::Clock
+#[cfg(feature = "std")]
+pub struct StdClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdClock {
+    /// Creates a new clock starting at the current instant.
+    pub fn new() -> StdClock {
+        StdClock { start: std::time::Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> StdClock {
+        StdClock::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A [`Producer`] which limits the rate of pushes with a token bucket.
+///
+/// The bucket holds up to `burst` tokens and gains one token every
+/// `interval`. Each push consumes a token, and pushes are rejected while the
+/// bucket is empty.
+///
+/// [`Producer`]: crate::Producer
+pub struct RateLimitedProducer<T, C> {
+    producer: Producer<T>,
+    clock: C,
+    interval: Duration,
+    burst: u32,
+    tokens: u32,
+    refilled: Duration,
+}
+
+impl<T, C: Clock> RateLimitedProducer<T, C> {
+    /// Creates a new rate-limited producer allowing one push per `interval`,
+    /// with bursts of up to `burst` pushes. The bucket starts full.
+    pub fn new(
+        producer: Producer<T>,
+        mut clock: C,
+        interval: Duration,
+        burst: u32,
+    ) -> RateLimitedProducer<T, C> {
+        let refilled = clock.now();
+        RateLimitedProducer { producer, clock, interval, burst, tokens: burst, refilled }
+    }
+
+    /// Adds an element to the queue if the rate limit allows it. Returns the
+    /// node in an error otherwise.
+    pub fn try_push(&mut self, node: Node<T>) -> Result<(), PushError<Node<T>>> {
+        self.refill();
+        if self.tokens == 0 {
+            return Err(PushError::RateLimited(node));
+        }
+
+        self.tokens -= 1;
+        self.producer.push(node);
+        Ok(())
+    }
+
+    /// Returns how long to wait before the next push will be allowed, or
+    /// zero if a push is allowed now.
+    pub fn time_until_ready(&mut self) -> Duration {
+        self.refill();
+        if self.tokens > 0 {
+            return Duration::ZERO;
+        }
+
+        let elapsed = self.clock.now().saturating_sub(self.refilled);
+        self.interval.saturating_sub(elapsed)
+    }
+
+    /// Returns the inner producer.
+    pub fn into_inner(self) -> Producer<T> {
+        self.producer
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.now();
+        if self.interval.is_zero() {
+            self.tokens = self.burst;
+            self.refilled = now;
+            return;
+        }
+
+        let elapsed = now.saturating_sub(self.refilled);
+        let new_tokens = elapsed.as_nanos() / self.interval.as_nanos();
+        let missing = self.burst - self.tokens;
+
+        if new_tokens >= missing as u128 {
+            self.tokens = self.burst;
+            self.refilled = now;
+        } else {
+            // Keep the remainder of a partial interval for the next token.
+            self.tokens += new_tokens as u32;
+            self.refilled += self.interval * new_tokens as u32;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    use core::cell::Cell;
+
+    struct TestClock<'a>(&'a Cell<Duration>);
+
+    impl Clock for TestClock<'_> {
+        fn now(&mut self) -> Duration {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn token_bucket() {
+        let time = Cell::new(Duration::ZERO);
+        let (producer, mut consumer) = Queue::new().split();
+        let mut producer =
+            RateLimitedProducer::new(producer, TestClock(&time), Duration::from_millis(10), 2);
+
+        assert!(producer.try_push(Node::new(0)).is_ok());
+        assert!(producer.try_push(Node::new(1)).is_ok());
+        assert!(producer.try_push(Node::new(2)).is_err());
+
+        time.set(Duration::from_millis(15));
+        assert!(producer.try_push(Node::new(3)).is_ok());
+        assert_eq!(producer.time_until_ready(), Duration::from_millis(5));
+
+        time.set(Duration::from_millis(100));
+        assert!(producer.try_push(Node::new(4)).is_ok());
+        assert!(producer.try_push(Node::new(5)).is_ok());
+        assert!(producer.try_push(Node::new(6)).is_err());
+
+        let values: alloc::vec::Vec<_> =
+            core::iter::from_fn(|| consumer.pop()).map(|n| *n).collect();
+        assert_eq!(values, [0, 1, 3, 4, 5]);
+    }
+}
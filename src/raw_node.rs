@@ -0,0 +1,112 @@
+use crate::sync::AtomicPtr;
+use crate::{heap, Node, NodeInner};
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::ptr::NonNull;
+
+/// An untyped node allocation.
+///
+/// A `RawNode` can be converted into a [`Node<T>`] for any `T` whose nodes
+/// have exactly the same layout, and a node can be converted back into a
+/// `RawNode` once its value has been taken out. This allows a single pool of
+/// allocations to serve queues with different element types, for instance by
+/// padding message types to a common size.
+///
+/// ```rust
+/// use llq::{Node, RawNode};
+///
+/// let raw = RawNode::new::<[u32; 4]>();
+/// assert!(raw.fits::<[f32; 4]>());
+///
+/// let node: Node<[f32; 4]> = raw.into_node([1.0; 4]).ok().unwrap();
+/// let (raw, value) = RawNode::from_node(node);
+/// assert_eq!(value, [1.0; 4]);
+///
+/// let node = raw.into_node([1u32, 2, 3, 4]).ok().unwrap();
+/// assert_eq!(*node, [1, 2, 3, 4]);
+/// ```
+///
+/// [`Node<T>`]: crate::Node
+pub struct RawNode {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+unsafe impl Send for RawNode {}
+unsafe impl Sync for RawNode {}
+
+impl RawNode {
+    /// Allocates storage suitable for a `Node<T>`.
+    pub fn new<T>() -> RawNode {
+        let layout = Layout::new::<NodeInner<T>>();
+        let ptr = unsafe { NonNull::new_unchecked(heap::allocate(layout)) };
+        RawNode { ptr, layout }
+    }
+
+    /// Returns the layout of the allocation.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Returns `true` if the allocation can hold a `Node<T>`.
+    pub fn fits<T>(&self) -> bool {
+        self.layout == Layout::new::<NodeInner<T>>()
+    }
+
+    /// Converts the allocation into a node containing `value`. Returns the
+    /// allocation and the value if the allocation cannot hold a `Node<T>`.
+    pub fn into_node<T>(self, value: T) -> Result<Node<T>, (RawNode, T)> {
+        if !self.fits::<T>() {
+            return Err((self, value));
+        }
+
+        let node = self.ptr.cast::<NodeInner<T>>();
+        mem::forget(self);
+
+        unsafe {
+            node.as_ptr().write(NodeInner {
+                next: AtomicPtr::new(ptr::null_mut()),
+                #[cfg(feature = "timestamps")]
+                pushed: None,
+                data: MaybeUninit::new(value),
+            });
+        }
+
+        Ok(Node { inner: node, phantom: PhantomData })
+    }
+
+    /// Moves the value out of a node, returning it along with the node's
+    /// allocation.
+    pub fn from_node<T>(node: Node<T>) -> (RawNode, T) {
+        let inner = node.inner;
+        mem::forget(node);
+
+        let value = unsafe { ptr::read((*inner.as_ptr()).data.as_ptr()) };
+        (RawNode { ptr: inner.cast(), layout: Layout::new::<NodeInner<T>>() }, value)
+    }
+}
+
+impl Drop for RawNode {
+    fn drop(&mut self) {
+        unsafe { heap::deallocate(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatched_layout() {
+        let raw = RawNode::new::<[u64; 2]>();
+        assert!(!raw.fits::<u8>());
+
+        let (raw, value) = raw.into_node(7u8).err().unwrap();
+        assert_eq!(value, 7);
+        assert_eq!(*raw.into_node([8u64; 2]).ok().unwrap(), [8; 2]);
+    }
+}
@@ -0,0 +1,195 @@
+//! Recording the elements pushed onto a queue and replaying them later.
+//!
+//! A [`Recorder`] is an [`Observer`] which passes each element pushed onto a
+//! queue to a sink, as an [`Entry`] carrying its sequence number and the time
+//! since recording started. [`replay()`] pushes a recording onto a queue
+//! again, either with the original pacing or as fast as possible, so that a
+//! consumer can be fed exactly what it saw while reproducing a bug.
+//!
+//! The sink runs inline on the producer thread, so on a real-time thread it
+//! should do no more than, for instance, push the entry onto another queue.
+//!
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//!
+//! use llq::record::{replay, Entry, Pacing, Recorder};
+//! use llq::{Node, Queue};
+//!
+//! let recording = Arc::new(Mutex::new(Vec::new()));
+//! let log = recording.clone();
+//!
+//! let mut queue = Queue::new();
+//! queue.set_observer(Recorder::new(move |entry: Entry<&[u8; 3]>| {
+//!     log.lock().unwrap().push(entry.cloned());
+//! }));
+//! let (mut producer, _consumer) = queue.split();
+//! producer.push(Node::new([0x90, 60, 100]));
+//! producer.push(Node::new([0x80, 60, 0]));
+//!
+//! let (mut producer, mut consumer) = Queue::new().split();
+//! let recording = recording.lock().unwrap().clone();
+//! assert_eq!(replay(&mut producer, recording, Pacing::Original), 2);
+//! assert_eq!(*consumer.pop().unwrap(), [0x90, 60, 100]);
+//! assert_eq!(*consumer.pop().unwrap(), [0x80, 60, 0]);
+//! ```
+//!
+//! [`Recorder`]: crate::record::Recorder
+//! [`Observer`]: crate::observer::Observer
+//! [`Entry`]: crate::record::Entry
+//! [`replay()`]: crate::record::replay
+
+use crate::observer::Observer;
+use crate::{Node, Producer};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A recorded element.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entry<T> {
+    /// The number of elements recorded before this one.
+    pub sequence: u64,
+    /// The time between the start of the recording and the push.
+    pub offset: Duration,
+    /// The element.
+    pub value: T,
+}
+
+impl<T: Clone> Entry<&T> {
+    /// Returns an entry with a clone of the element.
+    pub fn cloned(&self) -> Entry<T> {
+        Entry { sequence: self.sequence, offset: self.offset, value: self.value.clone() }
+    }
+}
+
+/// An [`Observer`] which passes each pushed element to a sink.
+///
+/// Install it with [`Queue::set_observer()`]. Recording starts when the
+/// recorder is created.
+///
+/// [`Observer`]: crate::observer::Observer
+/// [`Queue::set_observer()`]: crate::Queue::set_observer
+pub struct Recorder<S> {
+    start: Instant,
+    sequence: AtomicU64,
+    // Only the producer calls the sink, so the lock is never contended.
+    sink: Mutex<S>,
+}
+
+impl<S> Recorder<S> {
+    /// Creates a recorder which passes each entry to `sink`.
+    pub fn new(sink: S) -> Recorder<S> {
+        Recorder { start: Instant::now(), sequence: AtomicU64::new(0), sink: Mutex::new(sink) }
+    }
+
+    /// Returns the number of elements recorded so far.
+    pub fn len(&self) -> u64 {
+        self.sequence.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if no element has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, S> Observer<T> for Recorder<S>
+where
+    S: FnMut(Entry<&T>) + Send,
+{
+    fn on_push(&self, value: &T) {
+        let offset = self.start.elapsed();
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+
+        let mut sink = match self.sink.lock() {
+            Ok(sink) => sink,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        sink(Entry { sequence, offset, value });
+    }
+}
+
+/// The pacing with which [`replay()`] pushes a recording.
+///
+/// [`replay()`]: crate::record::replay
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    /// Each element is pushed at its original offset from the start of the
+    /// replay.
+    Original,
+    /// Elements are pushed one after another without waiting.
+    AsFastAsPossible,
+}
+
+/// Pushes the elements of a recording onto a queue in order, returning the
+/// number of elements pushed.
+///
+/// With [`Pacing::Original`], the calling thread sleeps until each element
+/// is due. Allocates a node for each element.
+///
+/// [`Pacing::Original`]: crate::record::Pacing::Original
+pub fn replay<T, I>(producer: &mut Producer<T>, recording: I, pacing: Pacing) -> usize
+where
+    I: IntoIterator<Item = Entry<T>>,
+{
+    let start = Instant::now();
+    let mut count = 0;
+
+    for entry in recording {
+        if pacing == Pacing::Original {
+            if let Some(wait) = entry.offset.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+
+        producer.push(Node::new(entry.value));
+        count += 1;
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn record_and_replay() {
+        let recording = Arc::new(Mutex::new(Vec::new()));
+        let log = recording.clone();
+
+        let mut queue = Queue::new();
+        queue.set_observer(Recorder::new(move |entry: Entry<&u32>| {
+            log.lock().unwrap().push(entry.cloned());
+        }));
+        let (mut producer, mut consumer) = queue.split();
+
+        for i in 0..3 {
+            producer.push(Node::new(i));
+            thread::sleep(Duration::from_millis(2));
+        }
+        while consumer.pop().is_some() {}
+
+        let mut recording = recording.lock().unwrap().clone();
+        let sequences: Vec<_> = recording.iter().map(|entry| entry.sequence).collect();
+        assert_eq!(sequences, [0, 1, 2]);
+        assert!(recording[2].offset >= Duration::from_millis(4));
+
+        let (mut producer, mut consumer) = Queue::new().split();
+        let start = Instant::now();
+        assert_eq!(replay(&mut producer, recording.clone(), Pacing::Original), 3);
+        assert!(start.elapsed() >= recording[2].offset);
+
+        recording[2].offset = Duration::from_secs(60);
+        assert_eq!(replay(&mut producer, recording, Pacing::AsFastAsPossible), 3);
+
+        let values: Vec<_> = std::iter::from_fn(|| consumer.pop()).map(|node| *node).collect();
+        assert_eq!(values, [0, 1, 2, 0, 1, 2]);
+    }
+}
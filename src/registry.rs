@@ -0,0 +1,126 @@
+//! A rendezvous point for queues shared between components.
+//!
+//! A [`Registry`] maps names to queues. A queue is registered once, typically
+//! at startup, and its producer and consumer halves can then each be claimed
+//! exactly once by name, for instance by dynamically loaded plugins which
+//! have no other way to reach each other. [`Registry::global()`] returns a
+//! process-wide registry.
+//!
+//! ```rust
+//! use llq::registry::Registry;
+//! use llq::{Node, Queue};
+//!
+//! let registry = Registry::new();
+//! assert!(registry.register("midi", Queue::<[u8; 3]>::new()));
+//!
+//! let mut producer = registry.take_producer::<[u8; 3]>("midi").unwrap();
+//! let mut consumer = registry.take_consumer::<[u8; 3]>("midi").unwrap();
+//! assert!(registry.take_producer::<[u8; 3]>("midi").is_none());
+//!
+//! producer.push(Node::new([0x90, 60, 100]));
+//! assert_eq!(*consumer.pop().unwrap(), [0x90, 60, 100]);
+//! ```
+//!
+//! [`Registry`]: crate::registry::Registry
+//! [`Registry::global()`]: crate::registry::Registry::global
+
+use crate::{Consumer, Producer, Queue};
+
+use core::any::Any;
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::string::String;
+use std::sync::{Mutex, OnceLock};
+
+struct Entry {
+    producer: Option<Box<dyn Any + Send>>,
+    consumer: Option<Box<dyn Any + Send>>,
+}
+
+/// A map from names to queues whose halves can each be claimed once.
+///
+/// Registering and claiming take a lock, so they should happen during setup
+/// rather than on a real-time thread.
+pub struct Registry {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl Registry {
+    /// Creates an empty registry.
+    pub fn new() -> Registry {
+        Registry { entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the process-wide registry.
+    pub fn global() -> &'static Registry {
+        static GLOBAL: OnceLock<Registry> = OnceLock::new();
+        GLOBAL.get_or_init(Registry::new)
+    }
+
+    /// Registers a queue under `name`. Returns `false`, dropping the queue, if
+    /// a queue is already registered under that name.
+    pub fn register<T: Send + 'static>(&self, name: &str, queue: Queue<T>) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(name) {
+            return false;
+        }
+
+        let (producer, consumer) = queue.split();
+        entries.insert(
+            String::from(name),
+            Entry { producer: Some(Box::new(producer)), consumer: Some(Box::new(consumer)) },
+        );
+        true
+    }
+
+    /// Claims the producer half of the queue registered under `name`.
+    ///
+    /// Returns `None` if no queue is registered under that name, if its
+    /// element type is not `T`, or if the producer has already been claimed.
+    pub fn take_producer<T: Send + 'static>(&self, name: &str) -> Option<Producer<T>> {
+        let mut entries = self.entries.lock().unwrap();
+        take(&mut entries.get_mut(name)?.producer)
+    }
+
+    /// Claims the consumer half of the queue registered under `name`.
+    ///
+    /// Returns `None` if no queue is registered under that name, if its
+    /// element type is not `T`, or if the consumer has already been claimed.
+    pub fn take_consumer<T: Send + 'static>(&self, name: &str) -> Option<Consumer<T>> {
+        let mut entries = self.entries.lock().unwrap();
+        take(&mut entries.get_mut(name)?.consumer)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+/// Takes a half out of its slot if it has the expected type.
+fn take<H: 'static>(slot: &mut Option<Box<dyn Any + Send>>) -> Option<H> {
+    if !slot.as_ref()?.is::<H>() {
+        return None;
+    }
+
+    slot.take()?.downcast().ok().map(|half| *half)
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_claims() {
+        let registry = Registry::global();
+        assert!(registry.register("typed_claims", Queue::<u32>::new()));
+        assert!(!registry.register("typed_claims", Queue::<u32>::new()));
+
+        assert!(registry.take_consumer::<u64>("typed_claims").is_none());
+        assert!(registry.take_consumer::<u32>("missing").is_none());
+        assert!(registry.take_consumer::<u32>("typed_claims").is_some());
+        assert!(registry.take_consumer::<u32>("typed_claims").is_none());
+        assert!(registry.take_producer::<u32>("typed_claims").is_some());
+    }
+}
@@ -0,0 +1,291 @@
+//! A bounded wait-free SPSC ring buffer.
+//!
+//! [`Ring`] stores elements by value in a fixed-size buffer allocated up
+//! front, rather than in individually allocated nodes. This gives better
+//! cache behavior for streams of small elements, at the cost of a fixed
+//! capacity. Its halves, [`RingProducer`] and [`RingConsumer`], mirror
+//! [`Producer`] and [`Consumer`], and both operations are wait-free.
+//!
+//! Elements can be moved between a ring and a node-based queue without
+//! allocating: [`RingProducer::push_node()`] moves the value out of a node and
+//! hands back the empty node, and [`RingConsumer::pop_into()`] moves a value
+//! into an empty node.
+//!
+//! ```rust
+//! use llq::ring::Ring;
+//! use llq::{Node, Queue};
+//!
+//! let (mut ring_producer, mut ring_consumer) = Ring::with_capacity(4).split();
+//! let (mut producer, mut consumer) = Queue::new().split();
+//!
+//! ring_producer.push(1).unwrap();
+//! let empty = Node::forget_value(Node::new(0));
+//! producer.push(ring_consumer.pop_into(empty).ok().unwrap());
+//!
+//! let empty = ring_producer.push_node(consumer.pop().unwrap()).ok().unwrap();
+//! assert_eq!(ring_consumer.pop(), Some(1));
+//! assert!(ring_consumer.pop_into(empty).is_err());
+//! ```
+//!
+//! [`Ring`]: crate::ring::Ring
+//! [`RingProducer`]: crate::ring::RingProducer
+//! [`RingConsumer`]: crate::ring::RingConsumer
+//! [`RingProducer::push_node()`]: crate::ring::RingProducer::push_node
+//! [`RingConsumer::pop_into()`]: crate::ring::RingConsumer::pop_into
+//! [`Producer`]: crate::Producer
+//! [`Consumer`]: crate::Consumer
+
+use crate::error::PushError;
+use crate::spsc::{SpscConsumer, SpscProducer};
+use crate::sync::{AtomicUsize, ACQUIRE, RELAXED, RELEASE};
+use crate::Node;
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr;
+
+/// A bounded wait-free SPSC ring buffer.
+pub struct Ring<T> {
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    /// Creates a ring buffer which can hold at least `capacity` elements.
+    ///
+    /// The capacity is rounded up to a power of two.
+    pub fn with_capacity(capacity: usize) -> Ring<T> {
+        let capacity = capacity.max(1).next_power_of_two();
+        let buffer = (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect();
+
+        Ring { buffer, head: AtomicUsize::new(0), tail: AtomicUsize::new(0) }
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Splits a ring buffer into producer and consumer halves.
+    pub fn split(self) -> (RingProducer<T>, RingConsumer<T>) {
+        let head = self.head.load(RELAXED);
+        let tail = self.tail.load(RELAXED);
+        let ring = Arc::new(self);
+
+        (
+            RingProducer { ring: ring.clone(), tail, head_cache: head },
+            RingConsumer { ring, head, tail_cache: tail },
+        )
+    }
+
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.buffer[index & (self.buffer.len() - 1)].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let mut head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        while head != tail {
+            unsafe { ptr::drop_in_place((*self.slot(head)).as_mut_ptr()) };
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+/// The producer half of a [`Ring`].
+///
+/// [`Ring`]: crate::ring::Ring
+pub struct RingProducer<T> {
+    ring: Arc<Ring<T>>,
+    tail: usize,
+    head_cache: usize,
+}
+
+impl<T> RingProducer<T> {
+    /// Adds an element to the ring buffer, returning it in an error if the
+    /// buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), PushError<T>> {
+        if self.is_full() {
+            return Err(PushError::Full(value));
+        }
+
+        unsafe { self.write(value) };
+        Ok(())
+    }
+
+    /// Moves the value out of `node` into the ring buffer, returning the
+    /// empty node so that it can be reused. Returns the node unchanged in an
+    /// error if the buffer is full.
+    pub fn push_node(
+        &mut self,
+        mut node: Node<T>,
+    ) -> Result<Node<MaybeUninit<T>>, PushError<Node<T>>> {
+        if self.is_full() {
+            return Err(PushError::Full(node));
+        }
+
+        unsafe { self.write(ptr::read(Node::as_mut_ptr(&mut node))) };
+        Ok(Node::forget_value(node))
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+
+    pub(crate) fn is_full(&mut self) -> bool {
+        if self.tail.wrapping_sub(self.head_cache) == self.ring.capacity() {
+            self.head_cache = self.ring.head.load(ACQUIRE);
+        }
+        self.tail.wrapping_sub(self.head_cache) == self.ring.capacity()
+    }
+
+    /// Returns `true` if the consumer has removed every element pushed so far.
+    #[cfg(any(feature = "embedded-io", feature = "async"))]
+    pub(crate) fn is_drained(&mut self) -> bool {
+        self.head_cache = self.ring.head.load(ACQUIRE);
+        self.head_cache == self.tail
+    }
+
+    /// Returns `true` if the consumer half has been dropped.
+    #[cfg(feature = "embedded-io")]
+    pub(crate) fn consumer_dropped(&self) -> bool {
+        Arc::strong_count(&self.ring) == 1
+    }
+
+    /// Writes a value into the next slot. The buffer must not be full.
+    unsafe fn write(&mut self, value: T) {
+        (*self.ring.slot(self.tail)).write(value);
+        self.tail = self.tail.wrapping_add(1);
+        self.ring.tail.store(self.tail, RELEASE);
+    }
+}
+
+impl<T> SpscProducer<T> for RingProducer<T> {
+    fn try_send(&mut self, value: T) -> Result<(), T> {
+        self.push(value).map_err(PushError::into_inner)
+    }
+}
+
+/// The consumer half of a [`Ring`].
+///
+/// [`Ring`]: crate::ring::Ring
+pub struct RingConsumer<T> {
+    ring: Arc<Ring<T>>,
+    head: usize,
+    tail_cache: usize,
+}
+
+impl<T> RingConsumer<T> {
+    /// Attempts to remove and return an element from the ring buffer. Returns
+    /// `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        Some(unsafe { self.read() })
+    }
+
+    /// Moves an element from the ring buffer into the empty `node`. Returns
+    /// the node unchanged if the buffer is empty.
+    pub fn pop_into(
+        &mut self,
+        mut node: Node<MaybeUninit<T>>,
+    ) -> Result<Node<T>, Node<MaybeUninit<T>>> {
+        if self.is_empty() {
+            return Err(node);
+        }
+
+        node.write(unsafe { self.read() });
+        Ok(unsafe { Node::assume_init(node) })
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+
+    /// Returns `true` if the ring buffer is empty.
+    pub fn is_empty(&mut self) -> bool {
+        if self.head == self.tail_cache {
+            self.tail_cache = self.ring.tail.load(ACQUIRE);
+        }
+        self.head == self.tail_cache
+    }
+
+    /// Returns `true` if the producer half has been dropped.
+    #[cfg(feature = "embedded-io")]
+    pub(crate) fn producer_dropped(&self) -> bool {
+        Arc::strong_count(&self.ring) == 1
+    }
+
+    /// Reads the value from the next slot. The buffer must not be empty.
+    unsafe fn read(&mut self) -> T {
+        let value = (*self.ring.slot(self.head)).as_ptr().read();
+        self.head = self.head.wrapping_add(1);
+        self.ring.head.store(self.head, RELEASE);
+        value
+    }
+}
+
+impl<T> SpscConsumer<T> for RingConsumer<T> {
+    fn try_recv(&mut self) -> Option<T> {
+        self.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn wrap_around() {
+        let (mut producer, mut consumer) = Ring::with_capacity(3).split();
+        assert_eq!(producer.capacity(), 4);
+
+        for round in 0..10 {
+            for i in 0..4 {
+                producer.push(alloc::format!("{}", round * 4 + i)).unwrap();
+            }
+            assert!(producer.push(alloc::string::String::new()).is_err());
+            for i in 0..4 {
+                assert_eq!(consumer.pop().unwrap(), alloc::format!("{}", round * 4 + i));
+            }
+            assert!(consumer.pop().is_none());
+        }
+
+        producer.push(alloc::string::String::from("dropped")).unwrap();
+    }
+
+    #[test]
+    fn threads() {
+        let (mut producer, mut consumer) = Ring::with_capacity(16).split();
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..10000 {
+                while producer.push(i).is_err() {}
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 10000 {
+            if let Some(value) = consumer.pop() {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+        }
+
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+//! Scatter-gather transfer of large byte messages.
+//!
+//! A message is split across a [`Chain`] of fixed-capacity [`Segment`]
+//! nodes, the last of which is marked as the end of the message, so that
+//! large payloads never require a contiguous allocation or an extra copy.
+//! [`Producer::push_message()`] fills segments taken from a free list and
+//! publishes the whole message with a single atomic store, so
+//! [`Consumer::pop_message()`] always observes either none or all of a
+//! message's segments.
+//!
+//! ```rust
+//! use llq::scatter::Segment;
+//! use llq::{Chain, Node, Queue};
+//!
+//! let (mut producer, mut consumer) = Queue::<Segment<4>>::new().split();
+//!
+//! let mut free = Chain::new();
+//! free.extend((0..4).map(|_| Node::new(Segment::new())));
+//!
+//! assert!(producer.push_message(b"hello world", &mut free));
+//! assert_eq!(free.len(), 1);
+//!
+//! let message = consumer.pop_message().unwrap();
+//! assert_eq!(message.len(), 3);
+//! let bytes: Vec<u8> = message.iter().flat_map(|segment| segment.data()).copied().collect();
+//! assert_eq!(bytes, b"hello world");
+//!
+//! // Return the segments to the free list.
+//! free.extend(message);
+//! ```
+//!
+//! [`Chain`]: crate::Chain
+//! [`Segment`]: crate::scatter::Segment
+//! [`Producer::push_message()`]: crate::Producer::push_message
+//! [`Consumer::pop_message()`]: crate::Consumer::pop_message
+
+use crate::{Chain, Consumer, Producer};
+
+/// A fixed-capacity segment of a byte message.
+#[derive(Copy, Clone)]
+pub struct Segment<const N: usize> {
+    len: usize,
+    last: bool,
+    buf: [u8; N],
+}
+
+impl<const N: usize> Segment<N> {
+    /// Creates a new, empty segment.
+    pub const fn new() -> Segment<N> {
+        Segment { len: 0, last: false, buf: [0; N] }
+    }
+
+    /// Returns the bytes stored in the segment.
+    pub fn data(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Returns `true` if this is the last segment of its message.
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+
+    /// Fills the segment with as much of `data` as fits, returning the number
+    /// of bytes copied.
+    fn fill(&mut self, data: &[u8]) -> usize {
+        self.len = data.len().min(N);
+        self.buf[..self.len].copy_from_slice(&data[..self.len]);
+        self.len
+    }
+}
+
+impl<const N: usize> Default for Segment<N> {
+    fn default() -> Segment<N> {
+        Segment::new()
+    }
+}
+
+impl<const N: usize> Producer<Segment<N>> {
+    /// Copies `data` into segments taken from the front of `free` and pushes
+    /// them onto the queue as a single message.
+    ///
+    /// Returns `false`, leaving `free` unchanged, if it does not contain
+    /// enough segments. An empty message occupies one segment.
+    pub fn push_message(&mut self, data: &[u8], free: &mut Chain<Segment<N>>) -> bool {
+        let count = if N == 0 { usize::MAX } else { data.len().div_ceil(N).max(1) };
+        if count > free.len() {
+            return false;
+        }
+
+        let mut message = Chain::new();
+        let mut rest = data;
+        for i in 0..count {
+            let mut segment = free.pop_front().unwrap();
+            let len = segment.fill(rest);
+            rest = &rest[len..];
+            segment.last = i == count - 1;
+            message.push_back(segment);
+        }
+
+        self.push_chain(message);
+        true
+    }
+}
+
+impl<const N: usize> Consumer<Segment<N>> {
+    /// Removes and returns the segments of the next message. Returns `None`
+    /// if the queue is empty.
+    ///
+    /// Messages must be pushed with [`Producer::push_message()`]. Under the
+    /// [`Overflow::DropOldest`] policy, evictions may remove part of a
+    /// message.
+    ///
+    /// [`Producer::push_message()`]: crate::Producer::push_message
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    pub fn pop_message(&mut self) -> Option<Chain<Segment<N>>> {
+        let mut message = Chain::new();
+        while let Some(segment) = self.pop() {
+            let last = segment.is_last();
+            message.push_back(segment);
+            if last {
+                break;
+            }
+        }
+
+        if message.is_empty() {
+            None
+        } else {
+            Some(message)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::{Node, Queue};
+
+    #[test]
+    fn messages() {
+        let (mut producer, mut consumer) = Queue::<Segment<2>>::new().split();
+
+        let mut free = Chain::new();
+        free.extend((0..3).map(|_| Node::new(Segment::new())));
+
+        assert!(!producer.push_message(b"abcdefg", &mut free));
+        assert_eq!(free.len(), 3);
+        assert!(producer.push_message(b"", &mut free));
+        assert!(producer.push_message(b"abcd", &mut free));
+        assert!(free.is_empty());
+
+        let message = consumer.pop_message().unwrap();
+        assert_eq!(message.len(), 1);
+        assert!(message.iter().next().unwrap().data().is_empty());
+
+        let message = consumer.pop_message().unwrap();
+        let segments: alloc::vec::Vec<_> = message.iter().map(Segment::data).collect();
+        assert_eq!(segments, [b"ab", b"cd"]);
+        assert!(consumer.pop_message().is_none());
+    }
+}
@@ -0,0 +1,179 @@
+//! Queue halves which borrow the queue instead of sharing it through an
+//! `Arc`.
+//!
+//! [`Queue::split_ref()`] returns a [`ProducerRef`] and a [`ConsumerRef`]
+//! which hold a reference to the queue, so splitting neither allocates nor
+//! touches a reference count, and the queue itself can live on the stack.
+//! The halves can be sent to scoped threads, and once both have been dropped
+//! the queue can be used and split again.
+//!
+//! A queue which lives for the rest of the program, e.g. in firmware, can be
+//! split into `'static` halves with [`Queue::split_raw()`]. The halves can
+//! then be stored or sent anywhere without keeping a reference count.
+//!
+//! ```rust
+//! use llq::{Node, Queue};
+//!
+//! let mut queue = Queue::new();
+//! let (mut producer, mut consumer) = queue.split_ref();
+//!
+//! std::thread::scope(|scope| {
+//!     scope.spawn(move || {
+//!         for i in 0..3 {
+//!             producer.push(Node::new(i));
+//!         }
+//!     });
+//! });
+//!
+//! assert_eq!(*consumer.pop().unwrap(), 0);
+//! drop(consumer);
+//!
+//! let values: Vec<_> = queue.into_iter().map(Node::into_inner).collect();
+//! assert_eq!(values, [1, 2]);
+//! ```
+//!
+//! [`Queue::split_ref()`]: crate::Queue::split_ref
+//! [`Queue::split_raw()`]: crate::Queue::split_raw
+//! [`ProducerRef`]: crate::scoped::ProducerRef
+//! [`ConsumerRef`]: crate::scoped::ConsumerRef
+
+use crate::{Node, NodeInner, Queue};
+
+impl<T> Queue<T> {
+    /// Splits a queue into producer and consumer halves which borrow it.
+    pub fn split_ref(&mut self) -> (ProducerRef<'_, T>, ConsumerRef<'_, T>) {
+        unsafe { self.split_borrowed() }
+    }
+
+    /// Splits a queue which is never dropped into producer and consumer
+    /// halves which refer to it without reference counting.
+    ///
+    /// ```rust
+    /// use llq::{Node, Queue};
+    ///
+    /// let queue: &'static mut Queue<u8> = Box::leak(Box::new(Queue::new()));
+    ///
+    /// // The queue is not used again other than through the halves.
+    /// let (mut producer, mut consumer) = unsafe { queue.split_raw() };
+    ///
+    /// producer.push(Node::new(1));
+    /// assert_eq!(*consumer.pop().unwrap(), 1);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// While either half exists, the queue must not be split again, and must
+    /// not be accessed other than through the halves.
+    pub unsafe fn split_raw(&'static self) -> (ProducerRef<'static, T>, ConsumerRef<'static, T>) {
+        self.split_borrowed()
+    }
+
+    /// Splits a queue into halves which borrow it for `'a`.
+    ///
+    /// # Safety
+    ///
+    /// While either half exists, the queue must not be split again, and must
+    /// not be accessed other than through the halves.
+    pub(crate) unsafe fn split_borrowed<'a>(&'a self) -> (ProducerRef<'a, T>, ConsumerRef<'a, T>) {
+        let (tail, _) = self.take_tail();
+        (ProducerRef { queue: self, tail }, ConsumerRef { queue: self })
+    }
+}
+
+/// The producer half of a [`Queue`] split with [`Queue::split_ref()`].
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_ref()`]: crate::Queue::split_ref
+pub struct ProducerRef<'a, T> {
+    queue: &'a Queue<T>,
+    tail: *mut NodeInner<T>,
+}
+
+unsafe impl<'a, T: Send> Send for ProducerRef<'a, T> {}
+
+impl<'a, T> ProducerRef<'a, T> {
+    /// Adds an element to the queue.
+    pub fn push(&mut self, node: Node<T>) {
+        self.tail = unsafe { self.queue.link(self.tail, node) };
+    }
+}
+
+/// The consumer half of a [`Queue`] split with [`Queue::split_ref()`].
+///
+/// [`Queue`]: crate::Queue
+/// [`Queue::split_ref()`]: crate::Queue::split_ref
+pub struct ConsumerRef<'a, T> {
+    queue: &'a Queue<T>,
+}
+
+unsafe impl<'a, T: Send> Send for ConsumerRef<'a, T> {}
+
+impl<'a, T> ConsumerRef<'a, T> {
+    /// Attempts to remove and return an element from the queue. Returns `None`
+    /// if the queue is empty.
+    pub fn pop(&mut self) -> Option<Node<T>> {
+        // The consumer has exclusive access to the front of the queue.
+        unsafe { self.queue.pop() }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    #[test]
+    fn scoped_threads() {
+        let mut queue = Queue::new();
+
+        for round in 0..3 {
+            let (mut producer, mut consumer) = queue.split_ref();
+            std::thread::scope(|scope| {
+                scope.spawn(move || {
+                    for i in 0..1000 {
+                        producer.push(Node::new(alloc::format!("{}", round * 1000 + i)));
+                    }
+                });
+                scope.spawn(move || {
+                    for i in 0..999 {
+                        let value = loop {
+                            if let Some(node) = consumer.pop() {
+                                break Node::into_inner(node);
+                            }
+                        };
+                        assert_eq!(value, alloc::format!("{}", round * 1000 + i));
+                    }
+                });
+            });
+
+            // The last element of each round is left for the next one.
+            let (_, mut consumer) = queue.split_ref();
+            assert_eq!(*consumer.pop().unwrap(), alloc::format!("{}", round * 1000 + 999));
+        }
+    }
+
+    #[test]
+    fn split_raw() {
+        let queue: &'static Queue<usize> =
+            alloc::boxed::Box::leak(alloc::boxed::Box::new(Queue::new()));
+        let (mut producer, mut consumer) = unsafe { queue.split_raw() };
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..1000 {
+                producer.push(Node::new(i));
+            }
+        });
+
+        for i in 0..1000 {
+            let node = loop {
+                if let Some(node) = consumer.pop() {
+                    break node;
+                }
+            };
+            assert_eq!(*node, i);
+        }
+
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+//! Sending elements from POSIX signal handlers.
+//!
+//! A signal handler may only call async-signal-safe functions: it must not
+//! allocate, take locks, or call non-reentrant library functions, since it
+//! may interrupt a thread in the middle of doing any of those things.
+//! [`Producer::push()`], [`Consumer::pop()`] and [`SharedPool::try_alloc()`]
+//! meet these requirements: they only perform atomic operations on memory
+//! which has already been allocated, and with the `timestamps` feature they
+//! additionally call `clock_gettime`, which is async-signal-safe. Functions
+//! which allocate or free nodes, such as [`Node::new()`] or dropping a
+//! [`Node`], are not, and neither are hooks installed with the `observer`
+//! feature unless they are written to be. With the `async` feature,
+//! [`SharedPool::try_alloc()`] may wake a task waiting for a node, which runs
+//! that task's waker.
+//!
+//! [`SignalProducer`] packages a producer with a [`SharedPool`] of
+//! preallocated nodes behind a shared reference, so that it can be stored in
+//! a `static` and used from a signal handler. The consumer returns nodes to
+//! the pool once it is done with them.
+//!
+//! ```rust
+//! use llq::pool::SharedPool;
+//! use llq::signal::SignalProducer;
+//! use llq::Queue;
+//! use std::sync::{Arc, OnceLock};
+//!
+//! static SIGNALS: OnceLock<SignalProducer<i32>> = OnceLock::new();
+//!
+//! extern "C" fn handler(signal: i32) {
+//!     if let Some(producer) = SIGNALS.get() {
+//!         let _ = producer.send(signal);
+//!     }
+//! }
+//!
+//! let pool = Arc::new(SharedPool::with_capacity(16));
+//! let (producer, mut consumer) = Queue::new().split();
+//! let _ = SIGNALS.set(SignalProducer::new(producer, pool.clone()));
+//!
+//! // Registered with e.g. `sigaction` in a real program.
+//! handler(10);
+//!
+//! let node = consumer.pop().unwrap();
+//! assert_eq!(pool.recycle(node), 10);
+//! ```
+//!
+//! [`Producer::push()`]: crate::Producer::push
+//! [`Consumer::pop()`]: crate::Consumer::pop
+//! [`SharedPool::try_alloc()`]: crate::pool::SharedPool::try_alloc
+//! [`SharedPool`]: crate::pool::SharedPool
+//! [`Node::new()`]: crate::Node::new
+//! [`Node`]: crate::Node
+//! [`SignalProducer`]: crate::signal::SignalProducer
+
+use crate::error::PushError;
+use crate::pool::SharedPool;
+use crate::sync::{AtomicBool, ACQUIRE, RELEASE};
+use crate::{Node, Producer};
+
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+
+/// A producer which can be shared and used from signal handlers.
+///
+/// Only one call can push at a time. A call which interrupts another, e.g.
+/// from a nested signal handler, fails instead of waiting for it. Taking a
+/// node from the pool can also fail spuriously if the signal interrupted
+/// another thread's use of the same pool.
+pub struct SignalProducer<T> {
+    producer: UnsafeCell<Producer<T>>,
+    pool: Arc<SharedPool<T>>,
+    busy: AtomicBool,
+}
+
+unsafe impl<T: Send> Send for SignalProducer<T> {}
+unsafe impl<T: Send> Sync for SignalProducer<T> {}
+
+impl<T> SignalProducer<T> {
+    /// Creates a new signal producer which takes nodes from `pool`.
+    pub fn new(producer: Producer<T>, pool: Arc<SharedPool<T>>) -> SignalProducer<T> {
+        SignalProducer { producer: UnsafeCell::new(producer), pool, busy: AtomicBool::new(false) }
+    }
+
+    /// Sends a value in a node taken from the pool. Returns the value in an
+    /// error if the pool is empty or another call is in progress.
+    ///
+    /// This function is async-signal-safe.
+    pub fn send(&self, value: T) -> Result<(), PushError<T>> {
+        if !self.claim() {
+            return Err(PushError::Busy(value));
+        }
+
+        let result = match self.pool.try_alloc(value) {
+            Ok(node) => {
+                unsafe { (*self.producer.get()).push(node) };
+                Ok(())
+            }
+            Err(err) => Err(PushError::Full(err.into_inner())),
+        };
+
+        self.busy.store(false, RELEASE);
+        result
+    }
+
+    /// Pushes a node. Returns the node in an error if another call is in
+    /// progress.
+    ///
+    /// This function is async-signal-safe.
+    pub fn push(&self, node: Node<T>) -> Result<(), PushError<Node<T>>> {
+        if !self.claim() {
+            return Err(PushError::Busy(node));
+        }
+
+        unsafe { (*self.producer.get()).push(node) };
+
+        self.busy.store(false, RELEASE);
+        Ok(())
+    }
+
+    /// Returns the pool from which nodes are taken.
+    pub fn pool(&self) -> &Arc<SharedPool<T>> {
+        &self.pool
+    }
+
+    /// Returns the inner producer.
+    pub fn into_inner(self) -> Producer<T> {
+        self.producer.into_inner()
+    }
+
+    fn claim(&self) -> bool {
+        self.busy.compare_exchange(false, true, ACQUIRE, ACQUIRE).is_ok()
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn send_from_pool() {
+        let pool = Arc::new(SharedPool::with_capacity(1));
+        let (producer, mut consumer) = Queue::new().split();
+        let producer = SignalProducer::new(producer, pool.clone());
+
+        assert!(producer.send(1).is_ok());
+        assert_eq!(producer.send(2), Err(PushError::Full(2)));
+        assert_eq!(pool.recycle(consumer.pop().unwrap()), 1);
+
+        // A call interrupting another one fails rather than waiting.
+        producer.busy.store(true, RELEASE);
+        assert_eq!(producer.send(2), Err(PushError::Busy(2)));
+        producer.busy.store(false, RELEASE);
+
+        let node = pool.try_alloc(2).ok().unwrap();
+        assert!(producer.push(node).is_ok());
+        assert_eq!(pool.recycle(consumer.pop().unwrap()), 2);
+    }
+}
@@ -0,0 +1,213 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+/// A byte payload which is stored inline if it is at most `N` bytes long,
+/// and in a separate allocation otherwise.
+///
+/// Stored in a [`Node`], small payloads live in the node's own allocation, so
+/// reading them does not chase a second pointer, while occasional large
+/// payloads are still accepted without enlarging every node.
+/// Both cases are accessed the same way, as a `[u8]`.
+///
+/// ```rust
+/// use llq::{Node, Queue, SmallBytes};
+///
+/// let (mut producer, mut consumer) = Queue::<SmallBytes<16>>::new().split();
+///
+/// producer.push(Node::new(SmallBytes::from_slice(b"set gain 0.5")));
+/// producer.push(Node::new(SmallBytes::from_slice(&[0; 1024])));
+///
+/// let command = consumer.pop().unwrap();
+/// assert!(command.is_inline());
+/// assert_eq!(&command[..], b"set gain 0.5");
+///
+/// let blob = consumer.pop().unwrap();
+/// assert!(!blob.is_inline());
+/// assert_eq!(blob.len(), 1024);
+/// ```
+///
+/// [`Node`]: crate::Node
+#[derive(Clone)]
+pub struct SmallBytes<const N: usize> {
+    repr: Repr<N>,
+}
+
+#[derive(Clone)]
+enum Repr<const N: usize> {
+    Inline { len: usize, buf: [u8; N] },
+    Heap(Box<[u8]>),
+}
+
+impl<const N: usize> SmallBytes<N> {
+    /// Creates a new, empty payload.
+    pub const fn new() -> SmallBytes<N> {
+        SmallBytes { repr: Repr::Inline { len: 0, buf: [0; N] } }
+    }
+
+    /// Creates a payload containing a copy of `bytes`, allocating if it is
+    /// longer than `N` bytes.
+    pub fn from_slice(bytes: &[u8]) -> SmallBytes<N> {
+        if bytes.len() <= N {
+            let mut buf = [0; N];
+            buf[..bytes.len()].copy_from_slice(bytes);
+            SmallBytes { repr: Repr::Inline { len: bytes.len(), buf } }
+        } else {
+            SmallBytes { repr: Repr::Heap(bytes.into()) }
+        }
+    }
+
+    /// Creates a payload of `len` zero bytes, allocating if `len` is greater
+    /// than `N`, to be filled in place through [`as_mut_slice()`], e.g. by
+    /// reading from a socket.
+    ///
+    /// The bytes are zeroed before they are filled. To skip that, use a node
+    /// from [`Node::new_uninit_slice()`], which always stores its bytes in a
+    /// separate allocation but leaves them uninitialized.
+    ///
+    /// ```rust
+    /// use llq::{Node, SmallBytes};
+    ///
+    /// let mut node = Node::new(SmallBytes::<64>::zeroed(5));
+    /// node.as_mut_slice().copy_from_slice(b"hello");
+    /// assert_eq!(&node[..], b"hello");
+    /// ```
+    ///
+    /// [`as_mut_slice()`]: crate::SmallBytes::as_mut_slice
+    /// [`Node::new_uninit_slice()`]: crate::Node::new_uninit_slice
+    pub fn zeroed(len: usize) -> SmallBytes<N> {
+        if len <= N {
+            SmallBytes { repr: Repr::Inline { len, buf: [0; N] } }
+        } else {
+            SmallBytes { repr: Repr::Heap(alloc::vec![0; len].into_boxed_slice()) }
+        }
+    }
+
+    /// Returns the number of bytes which can be stored inline.
+    pub const fn inline_capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` if the payload is stored inline.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Inline { .. })
+    }
+
+    /// Returns the length of the payload in bytes.
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Returns `true` if the payload is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the contents of the payload.
+    pub fn as_slice(&self) -> &[u8] {
+        match &self.repr {
+            Repr::Inline { len, buf } => &buf[..*len],
+            Repr::Heap(bytes) => bytes,
+        }
+    }
+
+    /// Returns the contents of the payload mutably.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        match &mut self.repr {
+            Repr::Inline { len, buf } => &mut buf[..*len],
+            Repr::Heap(bytes) => bytes,
+        }
+    }
+}
+
+impl<const N: usize> Default for SmallBytes<N> {
+    fn default() -> SmallBytes<N> {
+        SmallBytes::new()
+    }
+}
+
+impl<const N: usize> Deref for SmallBytes<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl<const N: usize> DerefMut for SmallBytes<N> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for SmallBytes<N> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl<const N: usize> From<&[u8]> for SmallBytes<N> {
+    fn from(bytes: &[u8]) -> SmallBytes<N> {
+        SmallBytes::from_slice(bytes)
+    }
+}
+
+impl<const N: usize> From<Vec<u8>> for SmallBytes<N> {
+    /// Stores the bytes inline if they fit, and otherwise reuses the vector's
+    /// allocation.
+    fn from(bytes: Vec<u8>) -> SmallBytes<N> {
+        if bytes.len() <= N {
+            SmallBytes::from_slice(&bytes)
+        } else {
+            SmallBytes { repr: Repr::Heap(bytes.into_boxed_slice()) }
+        }
+    }
+}
+
+impl<const N: usize> PartialEq for SmallBytes<N> {
+    fn eq(&self, other: &SmallBytes<N>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<const N: usize> Eq for SmallBytes<N> {}
+
+impl<const N: usize> PartialEq<[u8]> for SmallBytes<N> {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl<const N: usize> fmt::Debug for SmallBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_and_spilled() {
+        let mut small = SmallBytes::<4>::from_slice(&[1, 2, 3, 4]);
+        assert!(small.is_inline());
+        small[0] = 0;
+        assert_eq!(small, *[0, 2, 3, 4].as_slice());
+
+        let large = SmallBytes::<4>::from(alloc::vec![1, 2, 3, 4, 5]);
+        assert!(!large.is_inline());
+        assert_eq!(large.len(), 5);
+        assert_ne!(large, small);
+
+        let empty = SmallBytes::<0>::new();
+        assert!(empty.is_empty() && empty.is_inline());
+
+        let mut zeroed = SmallBytes::<4>::zeroed(6);
+        assert!(!zeroed.is_inline());
+        zeroed[5] = 1;
+        assert_eq!(zeroed, *[0, 0, 0, 0, 0, 1].as_slice());
+        assert!(SmallBytes::<4>::zeroed(4).is_inline());
+    }
+}
@@ -0,0 +1,92 @@
+//! Traits for writing code which is generic over SPSC channel
+//! implementations.
+//!
+//! [`SpscProducer`] and [`SpscConsumer`] describe the sending and receiving
+//! halves of a single-producer single-consumer channel in terms of plain
+//! values, so that application code written against them can be switched
+//! between llq's queue types and other implementations, for instance to
+//! benchmark them against each other.
+//!
+//! The implementations for [`Producer`] and [`Consumer`] allocate and free a
+//! node for each value. Code which needs to avoid allocation should use the
+//! node-based methods on those types directly.
+//!
+//! ```rust
+//! use llq::spsc::{SpscConsumer, SpscProducer};
+//! use llq::Queue;
+//!
+//! fn forward<P: SpscProducer<u32>, C: SpscConsumer<u32>>(consumer: &mut C, producer: &mut P) {
+//!     while let Some(value) = consumer.try_recv() {
+//!         if producer.try_send(value).is_err() {
+//!             break;
+//!         }
+//!     }
+//! }
+//!
+//! let (mut producer1, mut consumer1) = Queue::new().split();
+//! let (mut producer2, mut consumer2) = Queue::new().split();
+//! producer1.try_send(1).unwrap();
+//! forward(&mut consumer1, &mut producer2);
+//! assert_eq!(consumer2.try_recv(), Some(1));
+//! ```
+//!
+//! [`SpscProducer`]: crate::spsc::SpscProducer
+//! [`SpscConsumer`]: crate::spsc::SpscConsumer
+//! [`Producer`]: crate::Producer
+//! [`Consumer`]: crate::Consumer
+
+#[cfg(feature = "alloc-constructors")]
+use crate::Producer;
+use crate::{Consumer, Node};
+
+/// The sending half of an SPSC channel.
+pub trait SpscProducer<T> {
+    /// Attempts to send a value, returning it if the channel cannot accept it.
+    fn try_send(&mut self, value: T) -> Result<(), T>;
+}
+
+/// The receiving half of an SPSC channel.
+pub trait SpscConsumer<T> {
+    /// Attempts to receive a value. Returns `None` if the channel is empty.
+    fn try_recv(&mut self) -> Option<T>;
+}
+
+#[cfg(feature = "alloc-constructors")]
+impl<T> SpscProducer<T> for Producer<T> {
+    /// Sends a value in a newly allocated node, applying the queue's
+    /// [`Overflow`] policy.
+    ///
+    /// [`Overflow`]: crate::Overflow
+    fn try_send(&mut self, value: T) -> Result<(), T> {
+        self.try_push(Node::new(value)).map_err(|err| Node::into_inner(err.into_inner()))
+    }
+}
+
+impl<T> SpscConsumer<T> for Consumer<T> {
+    fn try_recv(&mut self) -> Option<T> {
+        self.pop().map(Node::into_inner)
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::{Overflow, Queue};
+
+    fn fill<P: SpscProducer<u32>>(producer: &mut P) -> u32 {
+        let mut sent = 0;
+        while producer.try_send(sent).is_ok() {
+            sent += 1;
+        }
+        sent
+    }
+
+    #[test]
+    fn bounded() {
+        let (mut producer, mut consumer) = Queue::with_capacity(3, Overflow::Reject).split();
+        assert_eq!(fill(&mut producer), 3);
+        assert_eq!(consumer.try_recv(), Some(0));
+        assert_eq!(producer.try_send(3), Ok(()));
+        assert_eq!(producer.try_send(4), Err(4));
+    }
+}
@@ -0,0 +1,176 @@
+//! Forwarding elements between queues through a transform.
+//!
+//! A [`Stage`] owns the consumer half of one queue and the producer half of
+//! another, and forwards each element from the first to the second through a
+//! transform, such as one hop of a multi-threaded processing pipeline. The
+//! node which carried an element in is reused to carry the result out when
+//! nodes of the output type have the same size and alignment, which is
+//! always the case when the types are the same. Otherwise, a new node is
+//! allocated for the result, and the old one is deallocated or added to the
+//! input queue's reclaim pool. The nodes of elements which a
+//! [`Stage::filter_map()`] transform drops are likewise deallocated or
+//! reclaimed.
+//!
+//! ```rust
+//! use llq::stage::Stage;
+//! use llq::{Node, Queue};
+//!
+//! let (mut input, upstream) = Queue::<u32>::new().split();
+//! let (downstream, mut output) = Queue::<f32>::new().split();
+//! let mut stage = Stage::filter_map(upstream, downstream, |x| {
+//!     if x % 2 == 0 {
+//!         Some(x as f32 / 2.0)
+//!     } else {
+//!         None
+//!     }
+//! });
+//!
+//! for x in 0..4 {
+//!     input.push(Node::new(x));
+//! }
+//! drop(input);
+//!
+//! let worker = std::thread::spawn(move || {
+//!     while !stage.is_closed() {
+//!         stage.forward();
+//!     }
+//! });
+//! worker.join().unwrap();
+//!
+//! assert_eq!(*output.pop().unwrap(), 0.0);
+//! assert_eq!(*output.pop().unwrap(), 1.0);
+//! assert!(output.pop().is_none());
+//! ```
+//!
+//! [`Stage`]: crate::stage::Stage
+//! [`Stage::filter_map()`]: crate::stage::Stage::filter_map
+
+use crate::{Consumer, Node, Producer};
+
+use alloc::boxed::Box;
+
+/// A transform between two queues; see the [module documentation].
+///
+/// [module documentation]: crate::stage
+pub struct Stage<T, U> {
+    consumer: Consumer<T>,
+    producer: Producer<U>,
+    transform: Box<dyn FnMut(T) -> Option<U> + Send>,
+}
+
+impl<T, U> Stage<T, U> {
+    /// Creates a stage which converts each element with `f`.
+    pub fn map<F>(consumer: Consumer<T>, producer: Producer<U>, mut f: F) -> Stage<T, U>
+    where
+        F: FnMut(T) -> U + Send + 'static,
+    {
+        Stage::filter_map(consumer, producer, move |value| Some(f(value)))
+    }
+
+    /// Creates a stage which converts each element with `f`, dropping those
+    /// for which it returns `None`.
+    pub fn filter_map<F>(consumer: Consumer<T>, producer: Producer<U>, f: F) -> Stage<T, U>
+    where
+        F: FnMut(T) -> Option<U> + Send + 'static,
+    {
+        Stage { consumer, producer, transform: Box::new(f) }
+    }
+
+    /// Forwards the next element, if there is one. Returns `false` if the
+    /// input queue is empty.
+    pub fn forward_one(&mut self) -> bool {
+        let node = match self.consumer.pop() {
+            Some(node) => node,
+            None => return false,
+        };
+
+        let (value, empty) = Node::take(node);
+        let output = match (self.transform)(value) {
+            Some(output) => output,
+            None => {
+                self.consumer.release(empty);
+                return true;
+            }
+        };
+
+        match Node::recast::<U>(empty) {
+            Ok(mut node) => {
+                node.write(output);
+                self.producer.push(unsafe { Node::assume_init(node) });
+            }
+            Err(empty) => {
+                self.consumer.release(empty);
+                self.producer.push(Node::new(output));
+            }
+        }
+
+        true
+    }
+
+    /// Forwards every element currently in the input queue, returning the
+    /// number of elements taken from it, including those dropped by the
+    /// transform.
+    pub fn forward(&mut self) -> usize {
+        let mut count = 0;
+        while self.forward_one() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Returns `true` if the input queue's producer has been closed or
+    /// dropped and every element has been forwarded.
+    pub fn is_closed(&self) -> bool {
+        self.consumer.is_closed()
+    }
+
+    /// Returns the consumer and producer halves, dropping the transform.
+    pub fn into_inner(self) -> (Consumer<T>, Producer<U>) {
+        (self.consumer, self.producer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::SharedPool;
+    use crate::Queue;
+
+    use alloc::sync::Arc;
+
+    fn input<T>(pool: &Arc<SharedPool<T>>) -> (Producer<T>, Consumer<T>) {
+        let mut queue = Queue::new();
+        queue.set_reclaim_pool(pool.clone());
+        queue.split()
+    }
+
+    #[test]
+    fn reuse_nodes() {
+        // Nodes of `u32` and `i32` have the same layout, so nothing is
+        // reclaimed.
+        let pool = Arc::new(SharedPool::new());
+        let (mut producer, upstream) = input::<u32>(&pool);
+        let (downstream, mut output) = Queue::new().split();
+        let mut stage = Stage::map(upstream, downstream, |x| -(x as i32));
+
+        producer.push(Node::new(5));
+        assert_eq!(stage.forward(), 1);
+        assert_eq!(*output.pop().unwrap(), -5);
+        assert_eq!(pool.len(), 0);
+
+        let pool = Arc::new(SharedPool::new());
+        let (mut producer, upstream) = input::<u32>(&pool);
+        let (downstream, mut output) = Queue::new().split();
+        let positive = |x| if x > 0 { Some([x as u64; 4]) } else { None };
+        let mut stage = Stage::filter_map(upstream, downstream, positive);
+
+        producer.push(Node::new(0));
+        producer.push(Node::new(1));
+        drop(producer);
+        assert_eq!(stage.forward(), 2);
+        assert!(stage.is_closed());
+        assert_eq!(*output.pop().unwrap(), [1; 4]);
+        assert!(output.pop().is_none());
+        assert_eq!(pool.len(), 2);
+    }
+}
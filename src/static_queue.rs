@@ -0,0 +1,118 @@
+use crate::sync::{AtomicUsize, ACQUIRE, ACQ_REL, RELEASE};
+use crate::{Consumer, Producer, Queue};
+
+use core::cell::UnsafeCell;
+use core::hint;
+
+const INITIALIZING: usize = 1 << 0;
+const READY: usize = 1 << 1;
+const PRODUCER_TAKEN: usize = 1 << 2;
+const CONSUMER_TAKEN: usize = 1 << 3;
+
+/// A queue which can be created in a `const` context and stored in a
+/// `static`.
+///
+/// The queue's sentinel node is allocated the first time either half is
+/// taken. Each half can be taken exactly once, from any thread.
+///
+/// ```rust
+/// use llq::{Node, StaticQueue};
+///
+/// static MIDI_QUEUE: StaticQueue<[u8; 3]> = StaticQueue::new();
+///
+/// let mut producer = MIDI_QUEUE.take_producer().unwrap();
+/// let mut consumer = MIDI_QUEUE.take_consumer().unwrap();
+/// assert!(MIDI_QUEUE.take_producer().is_none());
+///
+/// producer.push(Node::new([0x90, 60, 100]));
+/// assert_eq!(*consumer.pop().unwrap(), [0x90, 60, 100]);
+/// ```
+pub struct StaticQueue<T> {
+    state: AtomicUsize,
+    producer: UnsafeCell<Option<Producer<T>>>,
+    consumer: UnsafeCell<Option<Consumer<T>>>,
+}
+
+unsafe impl<T: Send> Send for StaticQueue<T> {}
+unsafe impl<T: Send> Sync for StaticQueue<T> {}
+
+impl<T> StaticQueue<T> {
+    /// Creates a new static queue.
+    pub const fn new() -> StaticQueue<T> {
+        StaticQueue {
+            state: AtomicUsize::new(0),
+            producer: UnsafeCell::new(None),
+            consumer: UnsafeCell::new(None),
+        }
+    }
+
+    /// Takes the producer half of the queue. Returns `None` if it has already
+    /// been taken.
+    pub fn take_producer(&self) -> Option<Producer<T>> {
+        self.take(PRODUCER_TAKEN, |this| unsafe { (*this.producer.get()).take() })
+    }
+
+    /// Takes the consumer half of the queue. Returns `None` if it has already
+    /// been taken.
+    pub fn take_consumer(&self) -> Option<Consumer<T>> {
+        self.take(CONSUMER_TAKEN, |this| unsafe { (*this.consumer.get()).take() })
+    }
+
+    fn take<H, F: FnOnce(&Self) -> Option<H>>(&self, taken: usize, f: F) -> Option<H> {
+        self.initialize();
+
+        if self.state.fetch_or(taken, ACQ_REL) & taken != 0 {
+            return None;
+        }
+
+        f(self)
+    }
+
+    /// Allocates the queue if no other call has, and waits for it to be
+    /// ready if another thread is allocating it.
+    fn initialize(&self) {
+        match self.state.compare_exchange(0, INITIALIZING, ACQUIRE, ACQUIRE) {
+            Ok(_) => {
+                let (producer, consumer) = Queue::new().split();
+                unsafe {
+                    *self.producer.get() = Some(producer);
+                    *self.consumer.get() = Some(consumer);
+                }
+                self.state.fetch_or(READY, RELEASE);
+            }
+            Err(_) => {
+                while self.state.load(ACQUIRE) & READY == 0 {
+                    hint::spin_loop();
+                }
+            }
+        }
+    }
+}
+
+impl<T> Default for StaticQueue<T> {
+    fn default() -> StaticQueue<T> {
+        StaticQueue::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    extern crate std;
+
+    static QUEUE: StaticQueue<usize> = StaticQueue::new();
+
+    #[test]
+    fn take_once() {
+        let consumer = std::thread::spawn(|| QUEUE.take_consumer());
+        let mut producer = QUEUE.take_producer().unwrap();
+        let mut consumer = consumer.join().unwrap().unwrap();
+        assert!(QUEUE.take_producer().is_none());
+        assert!(QUEUE.take_consumer().is_none());
+
+        producer.push(Node::new(1));
+        assert_eq!(*consumer.pop().unwrap(), 1);
+    }
+}
@@ -0,0 +1,279 @@
+//! Publishing queue statistics through the `metrics` facade.
+//!
+//! [`instrument()`] installs an [`Observer`] on a queue which counts the
+//! elements pushed, popped, rejected and dropped unconsumed, and returns a
+//! [`QueueStats`] handle for reading the counts from any thread. A
+//! [`Sampler`] holds a set of named handles and, each time it samples them,
+//! publishes the following through the [`metrics`] facade, labeled with
+//! `queue` set to the queue's name:
+//!
+//! - `llq_queue_depth`: a gauge of the number of elements in the queue.
+//! - `llq_queue_push_rate`: a gauge of the elements pushed per second since
+//!   the previous sample.
+//! - `llq_queue_pushed_total`, `llq_queue_popped_total` and
+//!   `llq_queue_dropped_total`: counters of the elements pushed, popped, and
+//!   rejected or dropped unconsumed.
+//!
+//! [`Sampler::spawn()`] samples periodically on a background thread, and the
+//! recorder installed by the application, such as a Prometheus exporter,
+//! takes care of the rest.
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use llq::stats::{self, Sampler};
+//! use llq::{Node, Queue};
+//!
+//! let mut queue = Queue::new();
+//! let stats = stats::instrument(&mut queue);
+//! let (mut producer, mut consumer) = queue.split();
+//!
+//! let sampler = Arc::new(Sampler::new());
+//! sampler.register("audio-commands", stats.clone());
+//! let _thread = sampler.spawn(Duration::from_secs(1));
+//!
+//! producer.push(Node::new(1));
+//! producer.push(Node::new(2));
+//! consumer.pop();
+//! assert_eq!(stats.depth(), 1);
+//! ```
+//!
+//! [`instrument()`]: crate::stats::instrument
+//! [`Observer`]: crate::observer::Observer
+//! [`QueueStats`]: crate::stats::QueueStats
+//! [`Sampler`]: crate::stats::Sampler
+//! [`Sampler::spawn()`]: crate::stats::Sampler::spawn
+//! [`metrics`]: https://docs.rs/metrics/0.24
+
+use crate::observer::Observer;
+use crate::Queue;
+
+use std::string::String;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+#[derive(Default)]
+struct Counters {
+    pushed: AtomicU64,
+    popped: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// A handle to the counts kept for a queue by [`instrument()`].
+///
+/// [`instrument()`]: crate::stats::instrument
+#[derive(Clone)]
+pub struct QueueStats {
+    counters: Arc<Counters>,
+}
+
+impl QueueStats {
+    /// Returns the number of elements pushed.
+    pub fn pushed(&self) -> u64 {
+        self.counters.pushed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of elements popped, including those evicted under
+    /// the [`Overflow::DropOldest`] policy.
+    ///
+    /// [`Overflow::DropOldest`]: crate::Overflow::DropOldest
+    pub fn popped(&self) -> u64 {
+        self.counters.popped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of elements rejected because the queue was full,
+    /// or still in the queue when it was dropped.
+    pub fn dropped(&self) -> u64 {
+        self.counters.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of elements which have been pushed but not yet
+    /// popped. The count is approximate while the queue is in use.
+    pub fn depth(&self) -> u64 {
+        self.pushed().saturating_sub(self.popped())
+    }
+}
+
+struct StatsObserver {
+    counters: Arc<Counters>,
+}
+
+impl<T> Observer<T> for StatsObserver {
+    fn on_push(&self, _value: &T) {
+        self.counters.pushed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_pop(&self, _value: &T) {
+        self.counters.popped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_drop_unconsumed(&self, _value: &T) {
+        self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_reject(&self, _value: &T) {
+        self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Starts counting the elements passing through `queue`, returning a handle
+/// to the counts.
+///
+/// This installs an observer with [`Queue::set_observer()`], replacing any
+/// previous observer. Elements already in the queue are not counted.
+///
+/// [`Queue::set_observer()`]: crate::Queue::set_observer
+pub fn instrument<T>(queue: &mut Queue<T>) -> QueueStats {
+    let counters = Arc::new(Counters::default());
+    queue.set_observer(StatsObserver { counters: counters.clone() });
+    QueueStats { counters }
+}
+
+struct Entry {
+    name: String,
+    stats: QueueStats,
+    last_pushed: u64,
+    last_sampled: Instant,
+}
+
+/// A set of named queues whose statistics are published together; see the
+/// [module documentation].
+///
+/// [module documentation]: crate::stats
+#[derive(Default)]
+pub struct Sampler {
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl Sampler {
+    /// Creates a sampler without any queues.
+    pub fn new() -> Sampler {
+        Sampler::default()
+    }
+
+    /// Adds a queue, whose metrics will be labeled with `name`.
+    pub fn register(&self, name: &str, stats: QueueStats) {
+        let last_pushed = stats.pushed();
+        self.lock().push(Entry {
+            name: String::from(name),
+            stats,
+            last_pushed,
+            last_sampled: Instant::now(),
+        });
+    }
+
+    /// Removes the queues registered under `name`, returning `true` if there
+    /// were any.
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut entries = self.lock();
+        let len = entries.len();
+        entries.retain(|entry| entry.name != name);
+        entries.len() != len
+    }
+
+    /// Publishes the current statistics of every registered queue.
+    pub fn sample(&self) {
+        let now = Instant::now();
+        for entry in self.lock().iter_mut() {
+            let stats = &entry.stats;
+            let pushed = stats.pushed();
+            let elapsed = now.duration_since(entry.last_sampled).as_secs_f64();
+            if elapsed > 0.0 {
+                let rate = (pushed - entry.last_pushed) as f64 / elapsed;
+                ::metrics::gauge!("llq_queue_push_rate", "queue" => entry.name.clone()).set(rate);
+            }
+            entry.last_pushed = pushed;
+            entry.last_sampled = now;
+
+            let name = entry.name.clone();
+            ::metrics::gauge!("llq_queue_depth", "queue" => name.clone()).set(stats.depth() as f64);
+            ::metrics::counter!("llq_queue_pushed_total", "queue" => name.clone()).absolute(pushed);
+            ::metrics::counter!("llq_queue_popped_total", "queue" => name.clone())
+                .absolute(stats.popped());
+            ::metrics::counter!("llq_queue_dropped_total", "queue" => name)
+                .absolute(stats.dropped());
+        }
+    }
+
+    /// Samples every `interval` on a new thread, until the returned handle is
+    /// dropped.
+    pub fn spawn(self: &Arc<Sampler>, interval: Duration) -> SamplerThread {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let sampler = self.clone();
+        let signal = stop.clone();
+
+        let thread = thread::spawn(move || {
+            let (stopped, condvar) = &*signal;
+            let mut stopped = stopped.lock().unwrap();
+            while !*stopped {
+                sampler.sample();
+                stopped = condvar.wait_timeout(stopped, interval).unwrap().0;
+            }
+        });
+
+        SamplerThread { stop, thread: Some(thread) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// A background thread sampling a [`Sampler`], which stops when dropped.
+///
+/// Returned by [`Sampler::spawn()`].
+///
+/// [`Sampler`]: crate::stats::Sampler
+/// [`Sampler::spawn()`]: crate::stats::Sampler::spawn
+pub struct SamplerThread {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for SamplerThread {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        condvar.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::{Node, Overflow};
+
+    #[test]
+    fn counts() {
+        let mut queue = Queue::with_capacity(2, Overflow::Reject);
+        let stats = instrument(&mut queue);
+        let (mut producer, mut consumer) = queue.split();
+
+        for i in 0..3 {
+            let _ = producer.try_push(Node::new(i));
+        }
+        consumer.pop();
+        assert_eq!((stats.pushed(), stats.popped(), stats.dropped()), (2, 1, 1));
+        assert_eq!(stats.depth(), 1);
+
+        drop((producer, consumer));
+        assert_eq!(stats.dropped(), 2);
+
+        let sampler = Arc::new(Sampler::new());
+        sampler.register("test", stats);
+        sampler.sample();
+        drop(sampler.spawn(Duration::from_millis(1)));
+        assert!(sampler.unregister("test"));
+        assert!(!sampler.unregister("test"));
+    }
+}
@@ -0,0 +1,142 @@
+//! Sharing a replaceable value with a real-time thread.
+//!
+//! A [`RealtimeArc`] holds the current version of a value, such as a large
+//! immutable configuration, for a real-time thread, while the matching
+//! [`ArcPublisher`] replaces it from elsewhere. New versions travel to the
+//! real-time thread over one queue, and the versions they replace travel back
+//! over a second one, so that the real-time thread never drops the last
+//! reference to an `Arc` and never frees memory.
+//!
+//! [`RealtimeArc::load()`] is wait-free and does not allocate. Retired
+//! versions are dropped by the publisher the next time it publishes or calls
+//! [`ArcPublisher::collect()`], and publishing reuses the node of a retired
+//! version if there is one.
+//!
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use llq::swap::RealtimeArc;
+//!
+//! let (mut publisher, mut config) = RealtimeArc::new(Arc::new(vec![1.0; 4]));
+//!
+//! let audio = std::thread::spawn(move || {
+//!     // Typically called once per audio callback.
+//!     while config.load().len() < 8 {}
+//!     config
+//! });
+//!
+//! publisher.publish(Arc::new(vec![0.5; 8]));
+//! let config = audio.join().unwrap();
+//! assert_eq!(config.current()[0], 0.5);
+//! ```
+//!
+//! [`RealtimeArc`]: crate::swap::RealtimeArc
+//! [`ArcPublisher`]: crate::swap::ArcPublisher
+//! [`RealtimeArc::load()`]: crate::swap::RealtimeArc::load
+//! [`ArcPublisher::collect()`]: crate::swap::ArcPublisher::collect
+
+use crate::{Consumer, Node, Producer, Queue};
+
+use alloc::sync::Arc;
+use core::mem;
+
+/// The real-time side of a shared value; see the [module documentation].
+///
+/// [module documentation]: crate::swap
+pub struct RealtimeArc<T> {
+    current: Arc<T>,
+    updates: Consumer<Arc<T>>,
+    retired: Producer<Arc<T>>,
+}
+
+impl<T> RealtimeArc<T> {
+    /// Creates a shared value starting out as `initial`, returning the
+    /// publisher and the real-time side.
+    pub fn new(initial: Arc<T>) -> (ArcPublisher<T>, RealtimeArc<T>) {
+        let (update_producer, update_consumer) = Queue::new().split();
+        let (retired_producer, retired_consumer) = Queue::new().split();
+        (
+            ArcPublisher { updates: update_producer, retired: retired_consumer },
+            RealtimeArc { current: initial, updates: update_consumer, retired: retired_producer },
+        )
+    }
+
+    /// Returns the most recently published version, sending any versions it
+    /// replaces back to the publisher.
+    pub fn load(&mut self) -> &Arc<T> {
+        while let Some(mut node) = self.updates.pop() {
+            mem::swap(&mut *node, &mut self.current);
+            self.retired.push(node);
+        }
+
+        &self.current
+    }
+
+    /// Returns the version returned by the last call to [`load()`], without
+    /// checking for newer ones.
+    ///
+    /// [`load()`]: crate::swap::RealtimeArc::load
+    pub fn current(&self) -> &Arc<T> {
+        &self.current
+    }
+}
+
+/// The publishing side of a shared value; see the [module documentation].
+///
+/// [module documentation]: crate::swap
+pub struct ArcPublisher<T> {
+    updates: Producer<Arc<T>>,
+    retired: Consumer<Arc<T>>,
+}
+
+impl<T> ArcPublisher<T> {
+    /// Publishes a new version, which the real-time side will return from
+    /// its next call to [`RealtimeArc::load()`]. Drops any retired versions.
+    ///
+    /// [`RealtimeArc::load()`]: crate::swap::RealtimeArc::load
+    pub fn publish(&mut self, value: Arc<T>) {
+        let node = match self.retired.pop() {
+            Some(mut node) => {
+                drop(mem::replace(&mut *node, value));
+                node
+            }
+            None => Node::new(value),
+        };
+
+        self.collect();
+        self.updates.push(node);
+    }
+
+    /// Drops the versions which the real-time side has retired, returning
+    /// how many there were.
+    pub fn collect(&mut self) -> usize {
+        let mut count = 0;
+        while self.retired.pop().is_some() {
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retire_off_thread() {
+        let first = Arc::new(0);
+        let (mut publisher, mut shared) = RealtimeArc::new(first.clone());
+        assert_eq!(**shared.load(), 0);
+
+        publisher.publish(Arc::new(1));
+        publisher.publish(Arc::new(2));
+        assert_eq!(**shared.current(), 0);
+        assert_eq!(**shared.load(), 2);
+
+        // Both replaced versions are still referenced until the publisher
+        // collects them.
+        assert_eq!(Arc::strong_count(&first), 2);
+        assert_eq!(publisher.collect(), 2);
+        assert_eq!(Arc::strong_count(&first), 1);
+    }
+}
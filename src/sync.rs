@@ -0,0 +1,49 @@
+//! The atomic types and memory orderings used throughout the crate.
+//!
+//! With the `seqcst` feature enabled, every ordering is upgraded to
+//! `SeqCst`. With the `shuttle` feature enabled, the atomic types (and, in
+//! tests, thread spawning) are replaced by shuttle's instrumented versions so
+//! that the crate's interleavings can be explored by shuttle's schedulers.
+
+use core::sync::atomic::Ordering;
+
+#[cfg(not(feature = "shuttle"))]
+#[allow(unused_imports)]
+pub(crate) use core::sync::atomic::{
+    fence, AtomicBool, AtomicPtr, AtomicU16, AtomicU64, AtomicU8, AtomicUsize,
+};
+#[cfg(feature = "shuttle")]
+#[allow(unused_imports)]
+pub(crate) use shuttle::sync::atomic::{
+    fence, AtomicBool, AtomicPtr, AtomicU16, AtomicU64, AtomicU8, AtomicUsize,
+};
+
+#[cfg(all(test, feature = "shuttle"))]
+#[allow(unused_imports)]
+pub(crate) use shuttle::thread;
+#[cfg(all(test, feature = "std", not(feature = "shuttle")))]
+#[allow(unused_imports)]
+pub(crate) use std::thread;
+
+/// Used where sequential consistency is required regardless of the `seqcst`
+/// feature.
+#[allow(dead_code)]
+pub(crate) const SEQ_CST: Ordering = Ordering::SeqCst;
+
+#[cfg(not(feature = "seqcst"))]
+pub(crate) const RELAXED: Ordering = Ordering::Relaxed;
+#[cfg(not(feature = "seqcst"))]
+pub(crate) const ACQUIRE: Ordering = Ordering::Acquire;
+#[cfg(not(feature = "seqcst"))]
+pub(crate) const RELEASE: Ordering = Ordering::Release;
+#[cfg(not(feature = "seqcst"))]
+pub(crate) const ACQ_REL: Ordering = Ordering::AcqRel;
+
+#[cfg(feature = "seqcst")]
+pub(crate) const RELAXED: Ordering = Ordering::SeqCst;
+#[cfg(feature = "seqcst")]
+pub(crate) const ACQUIRE: Ordering = Ordering::SeqCst;
+#[cfg(feature = "seqcst")]
+pub(crate) const RELEASE: Ordering = Ordering::SeqCst;
+#[cfg(feature = "seqcst")]
+pub(crate) const ACQ_REL: Ordering = Ordering::SeqCst;
@@ -0,0 +1,113 @@
+use crate::sync::{AtomicBool, ACQUIRE, RELAXED, RELEASE};
+use crate::{Consumer, Node};
+
+use core::cell::UnsafeCell;
+use core::hint;
+
+/// A [`Consumer`] which can be used through a shared reference.
+///
+/// Access to the inner consumer is serialized with an atomic claim flag, and
+/// callers spin while another thread holds the claim. This allows a consumer
+/// to be used from contexts which only hand out shared references, such as
+/// plugin callbacks or statics, without resorting to `static mut`.
+///
+/// Since a caller may have to spin, `SyncConsumer` is not wait-free when it
+/// is used from more than one thread at a time.
+///
+/// ```rust
+/// use llq::{Node, Queue, SyncConsumer};
+///
+/// let (mut producer, consumer) = Queue::new().split();
+/// let consumer = SyncConsumer::new(consumer);
+///
+/// producer.push(Node::new(1));
+/// let shared = &consumer;
+/// assert_eq!(*shared.pop().unwrap(), 1);
+/// assert!(shared.pop().is_none());
+/// ```
+///
+/// [`Consumer`]: crate::Consumer
+pub struct SyncConsumer<T> {
+    consumer: UnsafeCell<Consumer<T>>,
+    claimed: AtomicBool,
+}
+
+unsafe impl<T: Send> Send for SyncConsumer<T> {}
+unsafe impl<T: Send> Sync for SyncConsumer<T> {}
+
+impl<T> SyncConsumer<T> {
+    /// Wraps a consumer for use through shared references.
+    pub fn new(consumer: Consumer<T>) -> SyncConsumer<T> {
+        SyncConsumer { consumer: UnsafeCell::new(consumer), claimed: AtomicBool::new(false) }
+    }
+
+    /// Removes an element from the front of the queue. Returns `None` if the
+    /// queue is empty.
+    pub fn pop(&self) -> Option<Node<T>> {
+        self.with(Consumer::pop)
+    }
+
+    /// Calls `f` with exclusive access to the inner consumer.
+    pub fn with<R, F: FnOnce(&mut Consumer<T>) -> R>(&self, f: F) -> R {
+        while self.claimed.compare_exchange_weak(false, true, ACQUIRE, RELAXED).is_err() {
+            hint::spin_loop();
+        }
+
+        // Releases the claim even if `f` panics.
+        struct Claim<'a>(&'a AtomicBool);
+
+        impl Drop for Claim<'_> {
+            fn drop(&mut self) {
+                self.0.store(false, RELEASE);
+            }
+        }
+
+        let _claim = Claim(&self.claimed);
+        f(unsafe { &mut *self.consumer.get() })
+    }
+
+    /// Returns a mutable reference to the inner consumer.
+    pub fn get_mut(&mut self) -> &mut Consumer<T> {
+        self.consumer.get_mut()
+    }
+
+    /// Returns the inner consumer.
+    pub fn into_inner(self) -> Consumer<T> {
+        self.consumer.into_inner()
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    extern crate std;
+
+    #[test]
+    fn shared_pop() {
+        let (mut producer, consumer) = Queue::new().split();
+        let consumer = SyncConsumer::new(consumer);
+
+        for i in 0..10000 {
+            producer.push(Node::new(i));
+        }
+
+        let total = std::thread::scope(|scope| {
+            let threads: alloc::vec::Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut sum = 0;
+                        while let Some(node) = consumer.pop() {
+                            sum += *node;
+                        }
+                        sum
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|thread| thread.join().unwrap()).sum::<usize>()
+        });
+
+        assert_eq!(total, (0..10000).sum());
+    }
+}
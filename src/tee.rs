@@ -0,0 +1,139 @@
+//! Duplication of a stream of elements into several queues.
+//!
+//! A [`Tee`] owns the producer halves of two or more queues and pushes every
+//! element onto each of them: the original node onto the first queue and a
+//! clone onto each of the others, e.g. to mirror a command stream to a
+//! recorder or debugger alongside the live consumer. The clones are placed
+//! in nodes taken from a [`SharedPool`], so that once the pool has been
+//! preloaded and the consumers recycle into it, pushing does not allocate.
+//!
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use llq::pool::SharedPool;
+//! use llq::tee::Tee;
+//! use llq::{Node, Queue};
+//!
+//! let pool = Arc::new(SharedPool::with_capacity(16));
+//! let (live, mut live_consumer) = Queue::new().split();
+//! let (recorder, mut recorder_consumer) = Queue::new().split();
+//! let mut tee = Tee::new(vec![live, recorder], pool.clone());
+//!
+//! tee.try_push(Node::new("play")).unwrap();
+//!
+//! assert_eq!(*live_consumer.pop().unwrap(), "play");
+//! let node = recorder_consumer.pop().unwrap();
+//! assert_eq!(pool.recycle(node), "play");
+//! ```
+//!
+//! [`Tee`]: crate::tee::Tee
+//! [`SharedPool`]: crate::pool::SharedPool
+
+use crate::error::PushError;
+use crate::pool::SharedPool;
+use crate::{Chain, Node, Producer};
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::iter;
+
+/// A set of producers which each receive every element.
+pub struct Tee<T> {
+    producers: Vec<Producer<T>>,
+    pool: Arc<SharedPool<T>>,
+}
+
+impl<T: Clone> Tee<T> {
+    /// Creates a tee which pushes every element onto each of `producers`,
+    /// taking the nodes for clones from `pool`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `producers` is empty.
+    pub fn new(producers: Vec<Producer<T>>, pool: Arc<SharedPool<T>>) -> Tee<T> {
+        assert!(!producers.is_empty(), "a tee needs at least one producer");
+        Tee { producers, pool }
+    }
+
+    /// Adds an element to every queue, allocating nodes for the clones if
+    /// the pool runs out.
+    #[cfg(feature = "alloc-constructors")]
+    pub fn push(&mut self, node: Node<T>) {
+        let (first, rest) = self.producers.split_first_mut().unwrap();
+        for producer in rest {
+            producer.push(self.pool.alloc(T::clone(&node)));
+        }
+        first.push(node);
+    }
+
+    /// Adds an element to every queue if the pool holds enough allocations
+    /// for the clones. Never allocates; otherwise, returns the node in an
+    /// error without pushing anything.
+    pub fn try_push(&mut self, node: Node<T>) -> Result<(), PushError<Node<T>>> {
+        let mut copies = Chain::new();
+        while copies.len() < self.producers.len() - 1 {
+            match self.pool.try_alloc(T::clone(&node)) {
+                Ok(copy) => copies.push_back(copy),
+                Err(_) => {
+                    while let Some(copy) = copies.pop_front() {
+                        self.pool.recycle(copy);
+                    }
+                    return Err(PushError::Full(node));
+                }
+            }
+        }
+
+        let (first, rest) = self.producers.split_first_mut().unwrap();
+        for (producer, copy) in rest.iter_mut().zip(iter::from_fn(|| copies.pop_front())) {
+            producer.push(copy);
+        }
+        first.push(node);
+        Ok(())
+    }
+}
+
+impl<T> Tee<T> {
+    /// Returns the pool from which nodes for clones are taken.
+    pub fn pool(&self) -> &Arc<SharedPool<T>> {
+        &self.pool
+    }
+
+    /// Returns the producers, in the order in which they were given.
+    pub fn producers(&self) -> &[Producer<T>] {
+        &self.producers
+    }
+
+    /// Returns the producers and the pool.
+    pub fn into_inner(self) -> (Vec<Producer<T>>, Arc<SharedPool<T>>) {
+        (self.producers, self.pool)
+    }
+}
+
+#[cfg(all(test, feature = "alloc-constructors"))]
+mod tests {
+    use super::*;
+    use crate::Queue;
+
+    #[test]
+    fn duplicate() {
+        let pool = Arc::new(SharedPool::with_capacity(3));
+        let (producers, mut consumers): (Vec<_>, Vec<_>) =
+            (0..3).map(|_| Queue::new().split()).unzip();
+        let mut tee = Tee::new(producers, pool.clone());
+
+        tee.try_push(Node::new(alloc::string::String::from("a"))).unwrap();
+        assert_eq!(pool.len(), 1);
+        let err = tee.try_push(Node::new(alloc::string::String::from("b"))).unwrap_err();
+        assert_eq!(*err.into_inner(), "b");
+        assert_eq!(pool.len(), 1);
+
+        tee.push(Node::new(alloc::string::String::from("c")));
+        assert_eq!(*consumers[0].pop().unwrap(), "a");
+        assert_eq!(*consumers[0].pop().unwrap(), "c");
+        for consumer in &mut consumers[1..] {
+            assert_eq!(pool.recycle(consumer.pop().unwrap()), "a");
+            assert_eq!(pool.recycle(consumer.pop().unwrap()), "c");
+            assert!(consumer.pop().is_none());
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Scheduling events into a real-time thread with a timer wheel.
+//!
+//! A [`Scheduler`] sends [`Event`]s, each tagged with the tick at which it
+//! is due, to a [`Wheel`] owned by the real-time thread. Ticks are in
+//! whatever unit the application advances the wheel by, such as sample
+//! frames. Unlike [`Consumer::pop_for_block()`], events may be scheduled in
+//! any order.
+//!
+//! The wheel is hierarchical: each of its levels has 64 slots, each slot
+//! covering 64 times as many ticks as a slot of the level below it, and
+//! every slot is a [`Chain`] linked through the events' own nodes. An event
+//! is filed in the lowest level whose slots are narrow enough to tell it
+//! apart from the current tick, and moves down a level each time its slot is
+//! reached, until it expires. [`Wheel::advance()`] therefore never
+//! allocates, never frees and never waits, and its cost depends on the
+//! number of events handled rather than on the number of ticks elapsed.
+//!
+//! ```rust
+//! use llq::timer::Wheel;
+//!
+//! let (mut scheduler, mut wheel) = Wheel::new(0);
+//! scheduler.schedule_at(300, "note off");
+//! scheduler.schedule_at(100, "note on");
+//!
+//! // Process the block of 256 frames starting at frame 0.
+//! let expired: Vec<_> = wheel.advance(255).map(|event| (event.time, event.data)).collect();
+//! assert_eq!(expired, [(100, "note on")]);
+//!
+//! // Process the next block.
+//! let event = wheel.advance(511).next().unwrap();
+//! assert_eq!((event.time, event.data), (300, "note off"));
+//! ```
+//!
+//! [`Scheduler`]: crate::timer::Scheduler
+//! [`Event`]: crate::audio::Event
+//! [`Wheel`]: crate::timer::Wheel
+//! [`Consumer::pop_for_block()`]: crate::Consumer::pop_for_block
+//! [`Chain`]: crate::Chain
+//! [`Wheel::advance()`]: crate::timer::Wheel::advance
+
+use crate::audio::Event;
+use crate::{Chain, Consumer, Node, Producer, Queue};
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::array;
+use core::mem;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+// Enough levels to cover every `u64` tick.
+const LEVELS: usize = (u64::BITS as usize).div_ceil(SLOT_BITS as usize);
+
+struct Level<T> {
+    // Bit `i` is set if slot `i` is not empty.
+    occupied: u64,
+    slots: [Chain<Event<T>>; SLOTS],
+}
+
+/// The scheduling side of a timer wheel; see the [module documentation].
+///
+/// [module documentation]: crate::timer
+pub struct Scheduler<T> {
+    producer: Producer<Event<T>>,
+}
+
+impl<T> Scheduler<T> {
+    /// Schedules `value` to expire at tick `deadline`, allocating a node for
+    /// it.
+    pub fn schedule_at(&mut self, deadline: u64, value: T) {
+        self.producer.push(Node::new(Event::new(deadline, value)));
+    }
+
+    /// Schedules an event to expire at its `time`, reusing its node.
+    pub fn schedule(&mut self, event: Node<Event<T>>) {
+        self.producer.push(event);
+    }
+}
+
+/// The real-time side of a timer wheel; see the [module documentation].
+///
+/// [module documentation]: crate::timer
+pub struct Wheel<T> {
+    incoming: Consumer<Event<T>>,
+    levels: Box<[Level<T>]>,
+    expired: Chain<Event<T>>,
+    elapsed: u64,
+}
+
+impl<T> Wheel<T> {
+    /// Creates a timer wheel whose current tick is `start`, returning the
+    /// scheduling side and the wheel.
+    pub fn new(start: u64) -> (Scheduler<T>, Wheel<T>) {
+        let (producer, consumer) = Queue::new().split();
+        let levels: Vec<Level<T>> = (0..LEVELS)
+            .map(|_| Level { occupied: 0, slots: array::from_fn(|_| Chain::new()) })
+            .collect();
+
+        (
+            Scheduler { producer },
+            Wheel {
+                incoming: consumer,
+                levels: levels.into_boxed_slice(),
+                expired: Chain::new(),
+                elapsed: start,
+            },
+        )
+    }
+
+    /// Returns the tick up to which the wheel has been advanced.
+    pub fn elapsed(&self) -> u64 {
+        self.elapsed
+    }
+
+    /// Schedules an event from the real-time side, for instance to reschedule
+    /// a periodic event without sending it through the scheduler.
+    ///
+    /// An event which is already due is returned by the next call to
+    /// [`advance()`].
+    ///
+    /// [`advance()`]: crate::timer::Wheel::advance
+    pub fn insert(&mut self, event: Node<Event<T>>) {
+        let deadline = event.time;
+        if deadline <= self.elapsed {
+            self.expired.push_back(event);
+            return;
+        }
+
+        let level = level_for(self.elapsed, deadline);
+        let slot = slot_for(level, deadline);
+        let level = &mut self.levels[level];
+        level.slots[slot].push_back(event);
+        level.occupied |= 1 << slot;
+    }
+
+    /// Advances the wheel to tick `now`, returning an iterator over the
+    /// events which are due at or before it.
+    ///
+    /// Events are returned in order of their deadlines, and events with the
+    /// same deadline in the order in which they were scheduled. Events which
+    /// were scheduled for a tick the wheel had already passed are returned
+    /// first. Any events left in the iterator when it is dropped are returned
+    /// by the next call.
+    pub fn advance(&mut self, now: u64) -> Expired<'_, T> {
+        while let Some(event) = self.incoming.pop() {
+            self.insert(event);
+        }
+
+        while let Some((level, slot, deadline)) = self.next_expiration() {
+            if deadline > now {
+                break;
+            }
+
+            self.elapsed = deadline;
+            let level = &mut self.levels[level];
+            level.occupied &= !(1 << slot);
+            let chain = mem::take(&mut level.slots[slot]);
+            for event in chain {
+                self.insert(event);
+            }
+        }
+
+        self.elapsed = self.elapsed.max(now);
+        Expired { chain: &mut self.expired }
+    }
+
+    /// Returns the level and slot of the next slot to be reached, along with
+    /// the tick at which it begins.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        // Events in a level always expire before those in the levels above.
+        let (index, level) =
+            self.levels.iter().enumerate().find(|(_, level)| level.occupied != 0)?;
+
+        let shift = SLOT_BITS * index as u32;
+        let current = slot_for(index, self.elapsed);
+        // Occupied slots are always ahead of the current one.
+        let slot = current + (level.occupied >> current).trailing_zeros() as usize;
+
+        let level_start = match 1u64.checked_shl(shift + SLOT_BITS) {
+            Some(level_range) => self.elapsed & !(level_range - 1),
+            None => 0,
+        };
+        Some((index, slot, level_start + ((slot as u64) << shift)))
+    }
+}
+
+/// Returns the level in which an event due at `deadline` is filed when the
+/// current tick is `elapsed`.
+fn level_for(elapsed: u64, deadline: u64) -> usize {
+    let highest = u64::BITS - 1 - (elapsed ^ deadline).leading_zeros();
+    (highest / SLOT_BITS) as usize
+}
+
+fn slot_for(level: usize, tick: u64) -> usize {
+    ((tick >> (SLOT_BITS * level as u32)) as usize) & (SLOTS - 1)
+}
+
+/// An iterator over the events expired by [`Wheel::advance()`].
+///
+/// [`Wheel::advance()`]: crate::timer::Wheel::advance
+pub struct Expired<'a, T> {
+    chain: &'a mut Chain<Event<T>>,
+}
+
+impl<T> Iterator for Expired<'_, T> {
+    type Item = Node<Event<T>>;
+
+    fn next(&mut self) -> Option<Node<Event<T>>> {
+        self.chain.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.chain.len(), Some(self.chain.len()))
+    }
+}
+
+impl<T> ExactSizeIterator for Expired<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expire_in_order() {
+        let (mut scheduler, mut wheel) = Wheel::new(1000);
+
+        // Deadlines spread across several levels, in no particular order.
+        let mut deadlines: Vec<u64> =
+            (0..500u64).map(|i| 1000 + (i * 7919) % (1 << (i % 30))).collect();
+        deadlines.push(999);
+        deadlines.push(u64::MAX);
+        for (i, &deadline) in deadlines.iter().enumerate() {
+            scheduler.schedule_at(deadline, i);
+        }
+
+        let mut fired = Vec::new();
+        let mut previous = 1000;
+        for now in (0..40).map(|shift| 1000 + (1u64 << shift)).chain([u64::MAX]) {
+            let expired: Vec<_> = wheel.advance(now).collect();
+            for mut event in expired {
+                assert!(event.time <= now);
+                assert!(event.time > previous || event.time <= 1000);
+                fired.push(event.time);
+
+                // Reschedule the first event once, from the real-time side.
+                if event.data == 0 && event.time == deadlines[0] {
+                    event.time = now + 3;
+                    wheel.insert(event);
+                } else if event.data != 0 {
+                    assert_eq!(event.time, deadlines[event.data]);
+                }
+            }
+            assert_eq!(wheel.elapsed(), now);
+            previous = now;
+        }
+
+        assert_eq!(fired.len(), deadlines.len() + 1);
+        // Apart from those already due when scheduled, events expire in order.
+        let timely: Vec<_> = fired.into_iter().filter(|&time| time > 1000).collect();
+        assert!(timely.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
@@ -0,0 +1,37 @@
+use crate::sync::{fence, AtomicBool, ACQUIRE, RELAXED, RELEASE, SEQ_CST};
+
+/// A flag recording whether the consumer is waiting for elements, so that
+/// the producer only performs a wakeup when one is needed.
+///
+/// The consumer calls [`Waiting::set()`] after finding the queue empty, and
+/// then checks the queue again before waiting. The producer calls
+/// [`Waiting::take()`] after pushing. The fences guarantee that either the
+/// consumer's second check sees the new element or the producer sees the
+/// flag.
+pub(crate) struct Waiting {
+    waiting: AtomicBool,
+}
+
+impl Waiting {
+    pub(crate) fn new(waiting: bool) -> Waiting {
+        Waiting { waiting: AtomicBool::new(waiting) }
+    }
+
+    /// Marks the consumer as waiting.
+    pub(crate) fn set(&self) {
+        self.waiting.store(true, RELEASE);
+        fence(SEQ_CST);
+    }
+
+    /// Marks the consumer as no longer waiting.
+    pub(crate) fn clear(&self) {
+        self.waiting.store(false, RELAXED);
+    }
+
+    /// Returns `true`, clearing the flag, if the consumer is waiting and
+    /// should be woken.
+    pub(crate) fn take(&self) -> bool {
+        fence(SEQ_CST);
+        self.waiting.load(RELAXED) && self.waiting.swap(false, ACQUIRE)
+    }
+}
@@ -0,0 +1,303 @@
+//! Write-ahead logging of consumed elements.
+//!
+//! [`WalConsumer`] wraps a [`Consumer`] and appends each element it removes
+//! from the queue to a log, using a caller-provided serializer. After a
+//! restart, [`replay()`] reads the log back into a fresh [`Queue`], preserving
+//! the original order.
+//!
+//! Records are framed with a 32-bit little-endian length prefix. An incomplete
+//! record at the end of the log, as left behind by a crash during a write, is
+//! ignored by [`replay()`]. A record whose write fails partway is removed
+//! again through the log's [`Rollback`] impl, so that later records are not
+//! appended after a torn one.
+//!
+//! ```rust
+//! use llq::wal::{replay, WalConsumer};
+//! use llq::{Node, Queue};
+//!
+//! let (mut producer, consumer) = Queue::<u32>::new().split();
+//! let mut consumer = WalConsumer::new(consumer, Vec::new(), |value: &u32, buf: &mut Vec<u8>| {
+//!     buf.extend_from_slice(&value.to_le_bytes());
+//! });
+//!
+//! producer.push(Node::new(1));
+//! producer.push(Node::new(2));
+//! while consumer.pop().unwrap().is_some() {}
+//!
+//! let (_, log) = consumer.into_inner();
+//! let queue = replay(&log[..], |bytes| {
+//!     let mut value = [0; 4];
+//!     value.copy_from_slice(bytes);
+//!     Ok(u32::from_le_bytes(value))
+//! })
+//! .unwrap();
+//!
+//! let (_, mut consumer) = queue.split();
+//! assert_eq!(*consumer.pop().unwrap(), 1);
+//! assert_eq!(*consumer.pop().unwrap(), 2);
+//! ```
+//!
+//! [`WalConsumer`]: crate::wal::WalConsumer
+//! [`Consumer`]: crate::Consumer
+//! [`Queue`]: crate::Queue
+//! [`replay()`]: crate::wal::replay
+//! [`Rollback`]: crate::wal::Rollback
+
+use crate::{Consumer, Node, Queue};
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::vec::Vec;
+
+/// A log which can remove the bytes most recently written to it.
+pub trait Rollback: Write {
+    /// Removes the last `n` bytes written, so that the next write continues
+    /// from where they began.
+    fn rollback(&mut self, n: u64) -> io::Result<()>;
+}
+
+impl Rollback for Vec<u8> {
+    fn rollback(&mut self, n: u64) -> io::Result<()> {
+        let len = (self.len() as u64).saturating_sub(n);
+        self.truncate(len as usize);
+        Ok(())
+    }
+}
+
+impl Rollback for File {
+    fn rollback(&mut self, n: u64) -> io::Result<()> {
+        let end = self.seek(SeekFrom::End(0))?;
+        self.set_len(end.saturating_sub(n))?;
+        self.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+impl<L: Rollback + ?Sized> Rollback for &mut L {
+    fn rollback(&mut self, n: u64) -> io::Result<()> {
+        (**self).rollback(n)
+    }
+}
+
+/// A [`Consumer`] which appends each element it removes to a log.
+///
+/// [`Consumer`]: crate::Consumer
+pub struct WalConsumer<T, W, S> {
+    consumer: Consumer<T>,
+    log: W,
+    serialize: S,
+    buf: Vec<u8>,
+}
+
+impl<T, W, S> WalConsumer<T, W, S>
+where
+    W: Rollback,
+    S: FnMut(&T, &mut Vec<u8>),
+{
+    /// Creates a new `WalConsumer` which writes to `log`. `serialize` is
+    /// called to append the serialized form of each element to a buffer.
+    pub fn new(consumer: Consumer<T>, log: W, serialize: S) -> WalConsumer<T, W, S> {
+        WalConsumer { consumer, log, serialize, buf: Vec::new() }
+    }
+
+    /// Attempts to remove and return an element from the queue, appending it
+    /// to the log first. Returns `Ok(None)` if the queue is empty.
+    ///
+    /// If writing to the log fails, the part of the record which was written
+    /// is rolled back and the element is left in the queue. If the rollback
+    /// fails as well, its error is returned instead.
+    pub fn pop(&mut self) -> io::Result<Option<Node<T>>> {
+        let value = match self.consumer.peek() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        self.buf.clear();
+        self.buf.extend_from_slice(&[0; 4]);
+        (self.serialize)(value, &mut self.buf);
+
+        let len = u32::try_from(self.buf.len() - 4)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "record too large"))?;
+        self.buf[..4].copy_from_slice(&len.to_le_bytes());
+
+        let mut written = 0;
+        while written < self.buf.len() {
+            let err = match self.log.write(&self.buf[written..]) {
+                Ok(0) => io::Error::new(io::ErrorKind::WriteZero, "failed to write whole record"),
+                Ok(n) => {
+                    written += n;
+                    continue;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => err,
+            };
+
+            if written > 0 {
+                self.log.rollback(written as u64)?;
+            }
+            return Err(err);
+        }
+
+        Ok(self.consumer.pop())
+    }
+
+    /// Flushes the underlying log.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.log.flush()
+    }
+
+    /// Returns the inner consumer and log.
+    pub fn into_inner(self) -> (Consumer<T>, W) {
+        (self.consumer, self.log)
+    }
+}
+
+/// Reads a log written by a [`WalConsumer`] into a new queue.
+///
+/// `deserialize` is called with the bytes of each record. Record lengths are
+/// not trusted: a record is only read as far as the log extends, and one
+/// which is cut short is treated as a torn write at the end of the log.
+///
+/// [`WalConsumer`]: crate::wal::WalConsumer
+pub fn replay<T, R, D>(mut log: R, mut deserialize: D) -> io::Result<Queue<T>>
+where
+    R: Read,
+    D: FnMut(&[u8]) -> io::Result<T>,
+{
+    let mut values = Vec::new();
+    let mut buf = Vec::new();
+
+    loop {
+        let mut len = [0; 4];
+        if !read_record(&mut log, &mut len)? {
+            break;
+        }
+
+        // Reading through `take()` only grows the buffer as data arrives, so
+        // a corrupt length cannot cause a huge allocation.
+        let len = u64::from(u32::from_le_bytes(len));
+        buf.clear();
+        if (&mut log).take(len).read_to_end(&mut buf)? as u64 != len {
+            break;
+        }
+
+        values.push(deserialize(&buf)?);
+    }
+
+    Ok(Queue::from_snapshot(values))
+}
+
+/// Fills `buf` from `log`, returning `false` if the end of the log is reached
+/// first.
+fn read_record<R: Read>(log: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    match log.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::string::String;
+
+    #[test]
+    fn torn_record() {
+        let (mut producer, consumer) = Queue::new().split();
+        let mut consumer =
+            WalConsumer::new(consumer, Vec::new(), |value: &String, buf: &mut Vec<u8>| {
+                buf.extend_from_slice(value.as_bytes());
+            });
+
+        for value in &["a", "bc", "def"] {
+            producer.push(Node::new(String::from(*value)));
+        }
+        while consumer.pop().unwrap().is_some() {}
+
+        let (_, mut log) = consumer.into_inner();
+        log.truncate(log.len() - 1);
+
+        let queue = replay(&log[..], |bytes| {
+            String::from_utf8(bytes.to_vec())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        })
+        .unwrap();
+
+        let (_, mut consumer) = queue.split();
+        assert_eq!(&**consumer.pop().unwrap(), "a");
+        assert_eq!(&**consumer.pop().unwrap(), "bc");
+        assert!(consumer.pop().is_none());
+    }
+
+    #[test]
+    fn oversized_length() {
+        let mut log = Vec::new();
+        log.extend_from_slice(&1u32.to_le_bytes());
+        log.push(7);
+        // A corrupt length followed by far fewer bytes.
+        log.extend_from_slice(&u32::MAX.to_le_bytes());
+        log.extend_from_slice(&[1, 2, 3]);
+
+        let queue = replay(&log[..], |bytes| Ok(bytes.to_vec())).unwrap();
+        let (_, mut consumer) = queue.split();
+        assert_eq!(*consumer.pop().unwrap(), [7]);
+        assert!(consumer.pop().is_none());
+    }
+
+    /// A log which fails once it holds `limit` bytes.
+    struct LimitedLog {
+        data: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for LimitedLog {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.limit - self.data.len());
+            if n == 0 {
+                return Err(io::Error::other("log full"));
+            }
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Rollback for LimitedLog {
+        fn rollback(&mut self, n: u64) -> io::Result<()> {
+            self.data.rollback(n)
+        }
+    }
+
+    #[test]
+    fn rollback_partial_write() {
+        fn serialize(value: &u32, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let (mut producer, consumer) = Queue::new().split();
+        let log = LimitedLog { data: Vec::new(), limit: 10 };
+        let mut consumer = WalConsumer::new(consumer, log, serialize);
+
+        producer.push(Node::new(1));
+        producer.push(Node::new(2));
+        assert_eq!(*consumer.pop().unwrap().unwrap(), 1);
+        assert!(consumer.pop().is_err());
+
+        // The torn record is removed, and the element stays in the queue.
+        let (consumer, mut log) = consumer.into_inner();
+        assert_eq!(log.data.len(), 8);
+        log.limit = 16;
+        let mut consumer = WalConsumer::new(consumer, log, serialize);
+        assert_eq!(*consumer.pop().unwrap().unwrap(), 2);
+
+        let (_, log) = consumer.into_inner();
+        let queue = replay(&log.data[..], |bytes| Ok(bytes.len())).unwrap();
+        assert_eq!(queue.into_iter().count(), 2);
+    }
+}